@@ -0,0 +1,179 @@
+/// 基于内容的分块（FastCDC）
+///
+/// 使用 Gear 滚动哈希寻找切分点，切分点只取决于附近的内容，
+/// 因此在文件中间插入或追加数据时，大部分块保持不变，可以被去重。
+pub struct Chunker {
+    min_size: usize,
+    avg_size: usize,
+    max_size: usize,
+    mask_small: u64,
+    mask_large: u64,
+}
+
+pub const DEFAULT_MIN_CHUNK_SIZE: usize = 16 * 1024;
+pub const DEFAULT_AVG_CHUNK_SIZE: usize = 64 * 1024;
+pub const DEFAULT_MAX_CHUNK_SIZE: usize = 256 * 1024;
+
+/// 平均块大小的取值范围，保证掩码位数在 [6, 30] 内
+const MIN_AVG_CHUNK_SIZE: usize = 64;
+const MAX_AVG_CHUNK_SIZE: usize = 1 << 30;
+
+impl Default for Chunker {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_MIN_CHUNK_SIZE,
+            DEFAULT_AVG_CHUNK_SIZE,
+            DEFAULT_MAX_CHUNK_SIZE,
+        )
+    }
+}
+
+impl Chunker {
+    /// 参数超出范围时自动调整：平均大小限制在合理范围内，且 min <= avg <= max
+    pub fn new(min_size: usize, avg_size: usize, max_size: usize) -> Self {
+        let avg_size = avg_size.clamp(MIN_AVG_CHUNK_SIZE, MAX_AVG_CHUNK_SIZE);
+        let min_size = min_size.min(avg_size);
+        let max_size = max_size.max(avg_size);
+        let bits = avg_size.ilog2();
+
+        // 归一化分块：达到平均大小前使用更严格的掩码，之后使用更宽松的掩码
+        // 掩码取高位，使判断依赖于最近 64 字节的内容，而不仅仅是最后几个字节
+        let high_bits = |n: u32| -> u64 { (u64::MAX >> (64 - n)) << (64 - n) };
+
+        Self {
+            min_size,
+            avg_size,
+            max_size,
+            mask_small: high_bits(bits + 1),
+            mask_large: high_bits(bits - 1),
+        }
+    }
+
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// 返回 data 中第一个块的长度
+    pub fn next_boundary(&self, data: &[u8]) -> usize {
+        let len = data.len();
+        if len <= self.min_size {
+            return len;
+        }
+
+        let normal = self.avg_size.min(len);
+        let limit = self.max_size.min(len);
+
+        let mut fingerprint: u64 = 0;
+        let mut i = self.min_size;
+
+        while i < normal {
+            fingerprint = (fingerprint << 1).wrapping_add(GEAR[data[i] as usize]);
+            if fingerprint & self.mask_small == 0 {
+                return i + 1;
+            }
+            i += 1;
+        }
+
+        while i < limit {
+            fingerprint = (fingerprint << 1).wrapping_add(GEAR[data[i] as usize]);
+            if fingerprint & self.mask_large == 0 {
+                return i + 1;
+            }
+            i += 1;
+        }
+
+        limit
+    }
+
+    /// 将数据切分为若干块
+    pub fn chunks<'a>(&self, data: &'a [u8]) -> Vec<&'a [u8]> {
+        let mut chunks = Vec::new();
+        let mut rest = data;
+
+        while !rest.is_empty() {
+            let (chunk, tail) = rest.split_at(self.next_boundary(rest));
+            chunks.push(chunk);
+            rest = tail;
+        }
+
+        chunks
+    }
+}
+
+/// Gear 哈希表，由固定种子的 splitmix64 生成，保证不同版本之间切分结果一致
+static GEAR: [u64; 256] = build_gear_table();
+
+const fn build_gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut state: u64 = 0x5275_7374_6f72_7921;
+    let mut i = 0;
+
+    while i < 256 {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::random_data;
+    use std::collections::HashSet;
+
+    #[test]
+    fn chunks_cover_data_within_size_limits() {
+        let chunker = Chunker::new(1024, 4096, 16 * 1024);
+        let data = random_data(200_000, 1);
+        let chunks = chunker.chunks(&data);
+
+        assert_eq!(chunks.concat(), data);
+        for chunk in &chunks[..chunks.len() - 1] {
+            assert!(chunk.len() > 1024 && chunk.len() <= 16 * 1024);
+        }
+    }
+
+    #[test]
+    fn boundaries_are_stable_after_insertion() {
+        let chunker = Chunker::new(1024, 4096, 16 * 1024);
+        let data = random_data(200_000, 2);
+        let mut edited = data.clone();
+        edited.splice(100_000..100_000, b"inserted bytes".iter().copied());
+
+        let before = chunker.chunks(&data);
+        let after = chunker.chunks(&edited);
+
+        // 插入点之前的块完全相同
+        let unchanged_prefix = before
+            .iter()
+            .zip(&after)
+            .take_while(|(a, b)| a == b)
+            .map(|(a, _)| a.len())
+            .sum::<usize>();
+        assert!(unchanged_prefix >= 100_000 - 16 * 1024);
+
+        // 插入只影响附近的少数几个块
+        let original: HashSet<&[u8]> = before.iter().copied().collect();
+        let changed = after.iter().filter(|c| !original.contains(*c)).count();
+        assert!(changed <= 3, "{} chunks changed", changed);
+    }
+
+    #[test]
+    fn small_sizes_are_clamped() {
+        for (min, avg, max) in [(0, 0, 0), (0, 1, 0), (8, 2, 4), (usize::MAX, 3, 1)] {
+            let chunker = Chunker::new(min, avg, max);
+            let data = random_data(10_000, 3);
+            assert_eq!(chunker.chunks(&data).concat(), data);
+        }
+    }
+
+    #[test]
+    fn empty_input_has_no_chunks() {
+        assert!(Chunker::default().chunks(&[]).is_empty());
+    }
+}
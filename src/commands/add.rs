@@ -1,7 +1,8 @@
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use std::env;
 
-use crate::Repository;
+use crate::restore::Conflicts;
+use crate::{Repository, utils};
 
 pub struct AddCommand;
 
@@ -9,7 +10,20 @@ impl AddCommand {
     pub fn execute(message: Option<String>, json_output: bool) -> Result<()> {
         let current_dir = env::current_dir()?;
         let root = Repository::find_root(&current_dir)?;
-        let mut repo = Repository::new(root)?;
+        let mut repo = Repository::new(root.clone())?;
+
+        // 含有冲突标记的文件不能进入快照
+        let conflicts = Conflicts::unresolved(&root)?;
+        if !conflicts.is_empty() {
+            let files: Vec<String> = conflicts
+                .iter()
+                .map(|path| format!("  {}", path.display()))
+                .collect();
+            return Err(anyhow!(
+                "Unresolved merge conflicts in:\n{}\nRemove the conflict markers before creating a snapshot",
+                files.join("\n")
+            ));
+        }
 
         let message = message.unwrap_or_default();
         let snapshot_id = repo.create_snapshot(message.clone())?;
@@ -32,7 +46,7 @@ impl AddCommand {
         } else {
             println!(
                 "[snapshot {}] {}  added={} modified={} deleted={}",
-                snapshot_id,
+                utils::short_id(&snapshot_id),
                 history.timestamp.format("%Y-%m-%dT%H:%M:%S"),
                 history.added,
                 history.modified,
@@ -1,29 +1,33 @@
-use anyhow::Result;
+use anyhow::{Result, anyhow};
+use colored::*;
+use std::collections::HashSet;
 use std::env;
-use std::path::Path;
-use walkdir::WalkDir;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 
-use crate::{Repository, utils};
+use crate::index::IndexManager;
+use crate::restore::{Conflicts, MergeOutcome, RestorePlan};
+use crate::{Index, Repository, revision, utils};
 
 pub struct BackCommand;
 
 impl BackCommand {
-    pub fn execute(snapshot_id: String, restore: bool, keep_index: bool) -> Result<()> {
+    pub fn execute(
+        snapshot_id: String,
+        restore: bool,
+        keep_index: bool,
+        merge: bool,
+    ) -> Result<()> {
         let current_dir = env::current_dir()?;
         let root = Repository::find_root(&current_dir)?;
         let repo = Repository::new(root.clone())?;
 
-        let snapshot_id = if snapshot_id.parse::<usize>().is_ok() {
-            // 如果 snapshot_id 是数字，尝试通过快照编号解析
-            repo.resolve_snapshot_number(&repo, snapshot_id.parse::<usize>()?)?
-        } else {
-            // 否则直接使用 snapshot_id
-            snapshot_id
-        };
+        let snapshot_id = revision::resolve(&repo, &snapshot_id)?;
 
         if restore {
             // 直接恢复到工作区
-            Self::restore_to_working_dir(&repo, &root, &snapshot_id, keep_index)?;
+            Self::restore_to_working_dir(&repo, &root, &snapshot_id, keep_index, merge)?;
+            Self::offer_timeline_switch(&repo, &snapshot_id)?;
         } else {
             // 导出到备份目录
             Self::export_to_backup(&repo, &root, &snapshot_id)?;
@@ -32,77 +36,154 @@ impl BackCommand {
         Ok(())
     }
 
-    fn restore_to_working_dir(
+    /// 恢复的快照不在当前时间线上时，询问是否切换到包含它的时间线
+    fn offer_timeline_switch(repo: &Repository, snapshot_id: &str) -> Result<()> {
+        let current = repo.refs.current_timeline()?;
+        let timelines = repo
+            .refs
+            .timelines_containing(&repo.snapshot_manager, snapshot_id)?;
+        if timelines.is_empty() || timelines.contains(&current) {
+            return Ok(());
+        }
+
+        let target = &timelines[0];
+        print!(
+            "Snapshot {} is on timeline \"{}\", not on the current timeline \"{}\". Switch to \"{}\"? (y/n): ",
+            utils::short_id(snapshot_id),
+            target,
+            current,
+            target
+        );
+        io::stdout().flush()?;
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+
+        let answer = input.trim().to_lowercase();
+        if answer == "y" || answer == "yes" {
+            repo.refs.set_current_timeline(target)?;
+            println!("Switched to timeline \"{}\"", target);
+        } else {
+            println!(
+                "Staying on timeline \"{}\"; the next snapshot will continue it from {}",
+                current,
+                utils::short_id(snapshot_id)
+            );
+        }
+
+        Ok(())
+    }
+
+    pub fn restore_to_working_dir(
         repo: &Repository,
         root: &Path,
         snapshot_id: &str,
         keep_index: bool,
+        merge: bool,
     ) -> Result<()> {
-        // 创建备份
-        let backup_dir = root.join(utils::create_backup_name());
-        std::fs::create_dir_all(&backup_dir)?;
-
-        // 备份当前工作区
-        for entry in WalkDir::new(root)
-            .follow_links(false)
-            .into_iter()
-            .filter_map(Result::ok)
-        {
-            let path = entry.path();
-            if path.is_file() {
-                let relative_path = path.strip_prefix(root)?;
-
-                // 跳过 .rustory 目录和 rustory-rollback 目录
-                if relative_path.starts_with(".rustory")
-                    || relative_path.starts_with("rustory-rollback")
-                {
-                    continue;
-                }
-
-                let backup_path = backup_dir.join(relative_path);
-                if let Some(parent) = backup_path.parent() {
-                    std::fs::create_dir_all(parent)?;
-                }
-                std::fs::copy(path, backup_path)?;
+        if merge {
+            let unresolved = Conflicts::unresolved(root)?;
+            if !unresolved.is_empty() {
+                return Err(anyhow!(
+                    "Resolve the conflicts in {} file(s) before merging again (see 'rustory status')",
+                    unresolved.len()
+                ));
             }
         }
 
-        // 清空工作区（除了 .rustory 和 rustory-rollback）
-        for entry in WalkDir::new(root)
-            .follow_links(false)
-            .into_iter()
-            .filter_map(Result::ok)
-        {
-            let path = entry.path();
-            if path.is_file() {
-                let relative_path = path.strip_prefix(root)?;
-
-                // 跳过 .rustory 目录和 rustory-rollback 目录
-                if relative_path.starts_with(".rustory")
-                    || relative_path.starts_with("rustory-rollback")
-                {
-                    continue;
-                }
+        let snapshot = repo.snapshot_manager.load_snapshot(snapshot_id)?;
+        let tracked = repo.index_manager.load()?;
 
-                std::fs::remove_file(path)?;
-            }
-        }
+        // 只处理目标快照和当前索引中的路径，忽略的和未跟踪的文件保持不动
+        let plan = RestorePlan::build(root, &snapshot.files, &repo.index_manager, &tracked)?;
 
-        // 恢复快照内容
-        repo.snapshot_manager
-            .restore_snapshot(snapshot_id, root, &repo.object_store)?;
+        println!("Restoring snapshot {}:", utils::short_id(snapshot_id));
+        plan.print();
 
-        // 更新索引（如果不保持索引）
+        // 只备份将被覆盖或删除的文件
+        let backup_dir = root.join(utils::create_backup_name());
+        let backed_up = plan.backup(root, &backup_dir)?;
+
+        // 合并模式下保留本地修改，否则直接覆盖（覆盖后之前的冲突也不复存在）
+        let outcome = if merge {
+            plan.apply_merge(
+                root,
+                &repo.object_store,
+                &repo.index_manager,
+                &tracked,
+                &format!("snapshot {}", utils::short_id(snapshot_id)),
+            )?
+        } else {
+            plan.apply(root, &repo.object_store)?;
+            MergeOutcome::default()
+        };
+        Conflicts {
+            snapshot_id: snapshot_id.to_string(),
+            files: outcome.conflicts.clone(),
+        }
+        .save(&repo.rustory_dir)?;
+
+        // 更新索引（如果不保持索引），记录恢复后文件实际的 stat 信息，之后的 status/diff 不必重新计算哈希。
+        // 合并过、保留本地版本或被跳过的文件内容可能与快照不同，沿用快照中的记录，下次扫描时重新计算
         if !keep_index {
-            let snapshot = repo.snapshot_manager.load_snapshot(snapshot_id)?;
-            let index = crate::Index {
-                files: snapshot.files,
-            };
+            let untouched: HashSet<&PathBuf> = outcome
+                .merged
+                .iter()
+                .chain(&outcome.conflicts)
+                .chain(&outcome.kept)
+                .chain(&plan.skipped)
+                .collect();
+
+            let mut index = Index::new();
+            for (path, entry) in snapshot.files {
+                if untouched.contains(&path) || !root.join(&path).is_file() {
+                    index.files.insert(path, entry);
+                } else {
+                    IndexManager::record_stat(&mut index, root, entry)?;
+                }
+            }
             repo.index_manager.save(&index)?;
         }
 
-        println!("Restored snapshot {} to working directory", snapshot_id);
-        println!("Original files backed up to {}", backup_dir.display());
+        // 之后的快照以恢复的快照为父快照
+        repo.snapshot_manager.set_head(snapshot_id)?;
+
+        println!(
+            "Restored snapshot {} to working directory",
+            utils::short_id(snapshot_id)
+        );
+        if backed_up > 0 {
+            println!(
+                "{} original file(s) backed up to {}",
+                backed_up,
+                backup_dir.display()
+            );
+        }
+
+        for path in &outcome.merged {
+            println!("{} {}", "Merged local changes:".green(), path.display());
+        }
+        for path in &outcome.kept {
+            println!(
+                "{} {}",
+                "Kept local version (cannot merge):".yellow(),
+                path.display()
+            );
+        }
+        if !outcome.conflicts.is_empty() {
+            println!(
+                "{}",
+                format!("Conflicts in {} file(s):", outcome.conflicts.len())
+                    .red()
+                    .bold()
+            );
+            for path in &outcome.conflicts {
+                println!("  {} {}", "!".red(), path.display());
+            }
+            println!(
+                "Edit the files to resolve the conflict markers before the next 'rustory add'"
+            );
+        }
 
         Ok(())
     }
@@ -115,21 +196,10 @@ impl BackCommand {
 
         println!(
             "Exported snapshot {} to {}",
-            snapshot_id,
+            utils::short_id(snapshot_id),
             backup_dir.display()
         );
 
         Ok(())
     }
 }
-
-impl Repository {
-    pub fn resolve_snapshot_number(&self, repo: &Repository, number: usize) -> Result<String> {
-        let history = repo.snapshot_manager.list_history()?;
-        history
-            .iter()
-            .find(|entry| entry.number == number)
-            .map(|entry| entry.snapshot_id.clone())
-            .ok_or_else(|| anyhow::anyhow!("Snapshot number {} not found", number))
-    }
-}
@@ -0,0 +1,143 @@
+use anyhow::{Result, anyhow};
+use colored::*;
+use serde::Serialize;
+use std::env;
+
+use crate::diff_engine::{DiffEngine, DiffLineType};
+use crate::{HistoryEntry, Repository, pathspec, revision, utils};
+
+pub struct BlameCommand;
+
+/// 一行内容及引入它的快照
+#[derive(Debug, Serialize)]
+struct BlameLine<'a> {
+    line: usize,
+    content: String,
+    snapshot_id: &'a str,
+    number: usize,
+    timestamp: chrono::DateTime<chrono::Utc>,
+    message: &'a str,
+}
+
+impl BlameCommand {
+    /// 将文件的每一行归属到最后引入它的快照
+    pub fn execute(path: String, snapshot_id: Option<String>, json_output: bool) -> Result<()> {
+        let current_dir = env::current_dir()?;
+        let root = Repository::find_root(&current_dir)?;
+        let repo = Repository::new(root.clone())?;
+
+        let path = pathspec::repo_relative(&root, &current_dir, &path)?;
+        let snapshot_id = match snapshot_id {
+            Some(spec) => revision::resolve(&repo, &spec)?,
+            None => repo
+                .snapshot_manager
+                .head()?
+                .ok_or_else(|| anyhow!("error: no snapshots yet"))?,
+        };
+
+        // 沿父快照回溯，收集文件内容发生变化的各个版本（从旧到新）
+        let mut versions: Vec<(HistoryEntry, String)> = Vec::new();
+        for entry in repo.snapshot_manager.lineage(&snapshot_id)? {
+            let snapshot = repo.snapshot_manager.load_snapshot(&entry.snapshot_id)?;
+            let Some(file) = snapshot.files.get(&path) else {
+                break;
+            };
+
+            match versions.last_mut() {
+                // 更早的快照中内容相同，说明这些行在更早时就已引入
+                Some(last) if last.1 == file.hash => last.0 = entry,
+                _ => versions.push((entry, file.hash.clone())),
+            }
+        }
+        versions.reverse();
+
+        if versions.is_empty() {
+            return Err(anyhow!(
+                "error: path '{}' does not exist in snapshot {}",
+                path.display(),
+                utils::short_id(&snapshot_id)
+            ));
+        }
+
+        // 超过大小限制时没有保存内容：较早的版本直接跳过，其中的行归属于之后保存了内容的版本
+        if versions
+            .last()
+            .is_some_and(|(_, hash)| !repo.object_store.exists(hash))
+        {
+            return Err(anyhow!(
+                "error: content of '{}' was not stored in snapshot {}",
+                path.display(),
+                utils::short_id(&snapshot_id)
+            ));
+        }
+        versions.retain(|(_, hash)| repo.object_store.exists(hash));
+
+        // 从最早的版本开始逐个版本比较，未改变的行保留原来的归属，新增行归属于新版本
+        let mut text = String::new();
+        let mut owners: Vec<usize> = Vec::new();
+        for (version, (_, hash)) in versions.iter().enumerate() {
+            let content = repo.object_store.get_content(hash)?;
+            if DiffEngine::is_binary(&content) {
+                return Err(anyhow!(
+                    "error: cannot blame binary file '{}'",
+                    path.display()
+                ));
+            }
+            let new_text = String::from_utf8_lossy(&content).into_owned();
+
+            owners = DiffEngine::diff_text(&text, &new_text)
+                .iter()
+                .filter_map(|line| match line.line_type {
+                    DiffLineType::Context => line.old_line_number.map(|n| owners[n - 1]),
+                    DiffLineType::Added => Some(version),
+                    DiffLineType::Removed => None,
+                })
+                .collect();
+            text = new_text;
+        }
+
+        let lines: Vec<BlameLine> = text
+            .lines()
+            .zip(&owners)
+            .enumerate()
+            .map(|(i, (content, &owner))| {
+                let entry = &versions[owner].0;
+                BlameLine {
+                    line: i + 1,
+                    content: content.to_string(),
+                    snapshot_id: &entry.snapshot_id,
+                    number: entry.number,
+                    timestamp: entry.timestamp,
+                    message: &entry.message,
+                }
+            })
+            .collect();
+
+        if json_output {
+            println!("{}", serde_json::to_string_pretty(&lines)?);
+            return Ok(());
+        }
+
+        let width = lines.len().to_string().len();
+        for line in &lines {
+            let message: String = line.message.chars().take(20).collect();
+            let date = if repo.config.use_local_timezone {
+                line.timestamp.with_timezone(&chrono::Local).date_naive()
+            } else {
+                line.timestamp.date_naive()
+            };
+            println!(
+                "{} {} {} {:<20} {:>width$}) {}",
+                utils::short_id(line.snapshot_id).yellow(),
+                format!("#{:<3}", line.number).cyan(),
+                date.to_string().dimmed(),
+                message,
+                line.line,
+                line.content,
+                width = width
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+use anyhow::{Result, anyhow};
+use std::env;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::pathspec;
+use crate::{Repository, revision};
+
+pub struct CatCommand;
+
+impl CatCommand {
+    /// 将快照中单个文件的内容输出到标准输出，参数格式为 `<rev>:<path>`
+    pub fn execute(spec: String) -> Result<()> {
+        let current_dir = env::current_dir()?;
+        let root = Repository::find_root(&current_dir)?;
+        let repo = Repository::new(root.clone())?;
+
+        let (rev, path) = Self::split_spec(&spec)?;
+
+        // 路径相对于仓库根目录，以 ./ 或 ../ 开头时相对于当前目录
+        let path = if path.starts_with("./") || path.starts_with("../") {
+            pathspec::repo_relative(&root, &current_dir, path)?
+        } else {
+            pathspec::normalize(Path::new(path))
+        };
+
+        let snapshot_id = revision::resolve(&repo, rev)?;
+        let snapshot = repo.snapshot_manager.load_snapshot(&snapshot_id)?;
+        let entry = snapshot.files.get(&path).ok_or_else(|| {
+            anyhow!(
+                "error: path '{}' does not exist in snapshot {}",
+                path.display(),
+                rev
+            )
+        })?;
+
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        let result = repo
+            .object_store
+            .write_content(&entry.hash, &mut out)
+            .and_then(|_| Ok(out.flush()?));
+
+        // 输出被管道提前关闭（例如 `| head`）时正常退出
+        match result {
+            Err(e)
+                if e.downcast_ref::<io::Error>()
+                    .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe) =>
+            {
+                Ok(())
+            }
+            other => other,
+        }
+    }
+
+    /// 拆分 `<rev>:<path>`，时间表达式 `@{...}` 中的冒号不作为分隔符
+    fn split_spec(spec: &str) -> Result<(&str, &str)> {
+        let search_from = spec.rfind('}').map(|i| i + 1).unwrap_or(0);
+        let colon = spec[search_from..]
+            .find(':')
+            .map(|i| i + search_from)
+            .ok_or_else(|| anyhow!("error: expected <revision>:<path>, got '{}'", spec))?;
+
+        let (rev, path) = (&spec[..colon], &spec[colon + 1..]);
+        if rev.is_empty() || path.is_empty() {
+            return Err(anyhow!("error: expected <revision>:<path>, got '{}'", spec));
+        }
+        Ok((rev, path))
+    }
+}
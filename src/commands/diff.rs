@@ -1,28 +1,36 @@
 use anyhow::Result;
+use std::collections::{BTreeSet, HashMap};
 use std::env;
+use std::path::{Path, PathBuf};
 
-use crate::Repository;
+use crate::diff_engine::DiffEngine;
+use crate::{FileEntry, Repository, revision};
 
 pub struct DiffCommand;
 
 impl DiffCommand {
-    pub fn execute(id1: Option<String>, id2: Option<String>) -> Result<()> {
+    pub fn execute(id1: Option<String>, id2: Option<String>, context: usize) -> Result<()> {
         let current_dir = env::current_dir()?;
         let root = Repository::find_root(&current_dir)?;
         let repo = Repository::new(root.clone())?;
 
+        let resolve = |id: Option<String>| -> Result<Option<String>> {
+            id.map(|id| revision::resolve(&repo, &id)).transpose()
+        };
+        let (id1, id2) = (resolve(id1)?, resolve(id2)?);
+
         match (id1, id2) {
             (None, None) => {
                 // 与当前工作区比较
-                Self::diff_with_working_dir(&repo, &root)?;
+                Self::diff_with_working_dir(&repo, &root, context)?;
             }
             (Some(id), None) => {
                 // 指定快照与当前工作区比较
-                Self::diff_snapshot_with_working_dir(&repo, &root, &id)?;
+                Self::diff_snapshot_with_working_dir(&repo, &root, &id, context)?;
             }
             (Some(id1), Some(id2)) => {
                 // 两个快照之间比较
-                Self::diff_snapshots(&repo, &id1, &id2)?;
+                Self::diff_snapshots(&repo, &id1, &id2, context)?;
             }
             (None, Some(_)) => {
                 return Err(anyhow::anyhow!(
@@ -34,35 +42,28 @@ impl DiffCommand {
         Ok(())
     }
 
-    fn diff_with_working_dir(repo: &Repository, root: &std::path::Path) -> Result<()> {
+    fn diff_with_working_dir(repo: &Repository, root: &Path, context: usize) -> Result<()> {
         // 创建一个虚拟的忽略匹配器（现在在内部处理）
         let dummy_matcher = ignore::gitignore::GitignoreBuilder::new(root).build()?;
 
-        let (added, modified, deleted) = repo
-            .index_manager
-            .compare_with_current(root, &dummy_matcher)?;
-
-        println!("diff --rustory");
-
-        for path in &added {
-            println!("+ {}", path.display());
-        }
-
-        for path in &modified {
-            println!("~ {}", path.display());
-        }
-
-        for path in &deleted {
-            println!("- {}", path.display());
-        }
-
-        Ok(())
+        let index = repo.index_manager.load()?;
+        let current_index = repo.index_manager.scan_directory(root, &dummy_matcher)?;
+
+        Self::diff_file_sets(
+            repo,
+            &index.files,
+            &FileSource::Snapshot,
+            &current_index.files,
+            &FileSource::WorkingDir(root),
+            context,
+        )
     }
 
     fn diff_snapshot_with_working_dir(
         repo: &Repository,
-        root: &std::path::Path,
+        root: &Path,
         snapshot_id: &str,
+        context: usize,
     ) -> Result<()> {
         let snapshot = repo.snapshot_manager.load_snapshot(snapshot_id)?;
         let current_index = {
@@ -70,53 +71,164 @@ impl DiffCommand {
             repo.index_manager.scan_directory(root, &dummy_matcher)?
         };
 
-        println!("diff --rustory {} current", snapshot_id);
+        Self::diff_file_sets(
+            repo,
+            &snapshot.files,
+            &FileSource::Snapshot,
+            &current_index.files,
+            &FileSource::WorkingDir(root),
+            context,
+        )
+    }
 
-        // 简单的文件级差异
-        for path in current_index.files.keys() {
-            if !snapshot.files.contains_key(path) {
-                println!("+ {}", path.display());
-            }
-        }
+    fn diff_snapshots(repo: &Repository, id1: &str, id2: &str, context: usize) -> Result<()> {
+        let snapshot1 = repo.snapshot_manager.load_snapshot(id1)?;
+        let snapshot2 = repo.snapshot_manager.load_snapshot(id2)?;
 
-        for (path, entry) in &snapshot.files {
-            match current_index.files.get(path) {
-                None => println!("- {}", path.display()),
-                Some(current_entry) => {
-                    if entry.hash != current_entry.hash {
-                        println!("~ {}", path.display());
-                    }
-                }
+        Self::diff_file_sets(
+            repo,
+            &snapshot1.files,
+            &FileSource::Snapshot,
+            &snapshot2.files,
+            &FileSource::Snapshot,
+            context,
+        )
+    }
+
+    /// 输出两个快照文件集合之间的逐行差异
+    pub fn diff_snapshot_files(
+        repo: &Repository,
+        old_files: &HashMap<PathBuf, FileEntry>,
+        new_files: &HashMap<PathBuf, FileEntry>,
+        context: usize,
+    ) -> Result<()> {
+        Self::diff_file_sets(
+            repo,
+            old_files,
+            &FileSource::Snapshot,
+            new_files,
+            &FileSource::Snapshot,
+            context,
+        )
+    }
+
+    /// 输出同一文件两个快照版本之间的差异（任一版本可以不存在）
+    pub fn diff_snapshot_entries(
+        repo: &Repository,
+        path: &Path,
+        old_entry: Option<&FileEntry>,
+        new_entry: Option<&FileEntry>,
+        context: usize,
+    ) -> Result<()> {
+        let old_content = old_entry
+            .map(|entry| FileSource::Snapshot.load(repo, entry))
+            .transpose()?;
+        let new_content = new_entry
+            .map(|entry| FileSource::Snapshot.load(repo, entry))
+            .transpose()?;
+
+        Self::print_file_diff(path, old_content, new_content, context);
+        Ok(())
+    }
+
+    /// 按路径排序输出两组文件之间的逐行差异
+    fn diff_file_sets(
+        repo: &Repository,
+        old_files: &HashMap<PathBuf, FileEntry>,
+        old_source: &FileSource,
+        new_files: &HashMap<PathBuf, FileEntry>,
+        new_source: &FileSource,
+        context: usize,
+    ) -> Result<()> {
+        let paths: BTreeSet<&PathBuf> = old_files.keys().chain(new_files.keys()).collect();
+
+        for path in paths {
+            let old_entry = old_files.get(path);
+            let new_entry = new_files.get(path);
+
+            if let (Some(old), Some(new)) = (old_entry, new_entry)
+                && old.hash == new.hash
+            {
+                continue;
             }
+
+            let old_content = match old_entry {
+                Some(entry) => Some(old_source.load(repo, entry)?),
+                None => None,
+            };
+            let new_content = match new_entry {
+                Some(entry) => Some(new_source.load(repo, entry)?),
+                None => None,
+            };
+
+            Self::print_file_diff(path, old_content, new_content, context);
         }
 
         Ok(())
     }
 
-    fn diff_snapshots(repo: &Repository, id1: &str, id2: &str) -> Result<()> {
-        let snapshot1 = repo.snapshot_manager.load_snapshot(id1)?;
-        let snapshot2 = repo.snapshot_manager.load_snapshot(id2)?;
-
-        println!("diff --rustory {} {}", id1, id2);
+    fn print_file_diff(
+        path: &Path,
+        old_content: Option<Option<Vec<u8>>>,
+        new_content: Option<Option<Vec<u8>>>,
+        context: usize,
+    ) {
+        let display = path.display().to_string();
+        println!("diff --rustory a/{} b/{}", display, display);
+
+        let old_label = if old_content.is_some() {
+            format!("a/{}", display)
+        } else {
+            "/dev/null".to_string()
+        };
+        let new_label = if new_content.is_some() {
+            format!("b/{}", display)
+        } else {
+            "/dev/null".to_string()
+        };
 
-        // 简单的文件级差异
-        for path in snapshot2.files.keys() {
-            if !snapshot1.files.contains_key(path) {
-                println!("+ {}", path.display());
+        // 外层 None 表示文件不存在，内层 None 表示内容不可用（例如超过大小限制未存储）
+        let (old_bytes, new_bytes) = match (old_content, new_content) {
+            (Some(None), _) | (_, Some(None)) => {
+                println!("Content not available (file was not stored)");
+                return;
             }
+            (old, new) => (
+                old.flatten().unwrap_or_default(),
+                new.flatten().unwrap_or_default(),
+            ),
+        };
+
+        if DiffEngine::is_binary(&old_bytes) || DiffEngine::is_binary(&new_bytes) {
+            println!("Binary files {} and {} differ", old_label, new_label);
+            return;
         }
 
-        for (path, entry1) in &snapshot1.files {
-            match snapshot2.files.get(path) {
-                None => println!("- {}", path.display()),
-                Some(entry2) => {
-                    if entry1.hash != entry2.hash {
-                        println!("~ {}", path.display());
-                    }
+        let old_text = String::from_utf8_lossy(&old_bytes);
+        let new_text = String::from_utf8_lossy(&new_bytes);
+        let hunks = DiffEngine::unified_diff(&old_text, &new_text, context);
+
+        DiffEngine::print_diff(&hunks, &old_label, &new_label);
+    }
+}
+
+/// 文件内容来源：对象存储中的快照版本或工作区中的文件
+enum FileSource<'a> {
+    Snapshot,
+    WorkingDir(&'a Path),
+}
+
+impl FileSource<'_> {
+    fn load(&self, repo: &Repository, entry: &FileEntry) -> Result<Option<Vec<u8>>> {
+        match self {
+            FileSource::Snapshot => {
+                if repo.object_store.exists(&entry.hash) {
+                    Ok(Some(repo.object_store.get_content(&entry.hash)?))
+                } else {
+                    Ok(None)
                 }
             }
+            FileSource::WorkingDir(root) => Ok(Some(std::fs::read(root.join(&entry.path))?)),
         }
-
-        Ok(())
     }
 }
@@ -0,0 +1,206 @@
+use anyhow::{Result, anyhow};
+use colored::*;
+use regex::{Regex, RegexBuilder};
+use std::collections::HashMap;
+use std::env;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use crate::diff_engine::DiffEngine;
+use crate::pathspec::PathSpec;
+use crate::{Repository, revision};
+
+pub struct GrepCommand;
+
+/// 对象中匹配的行（行号、内容）；二进制对象和未保存内容的文件为 None
+type ObjectMatches = Option<Rc<Vec<(usize, String)>>>;
+
+/// 二进制检测只看开头的这部分内容，与 `DiffEngine::is_binary` 一致
+const BINARY_SNIFF_LEN: usize = 8000;
+
+impl GrepCommand {
+    /// 在一个、多个或全部快照中搜索文件内容，输出 `rev:path:line:text`
+    pub fn execute(
+        pattern: String,
+        revs: Vec<String>,
+        paths: Vec<String>,
+        ignore_case: bool,
+        files_only: bool,
+    ) -> Result<()> {
+        let current_dir = env::current_dir()?;
+        let root = Repository::find_root(&current_dir)?;
+        let repo = Repository::new(root.clone())?;
+
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(ignore_case)
+            .build()
+            .map_err(|e| anyhow!("error: invalid pattern: {}", e))?;
+        let pathspecs = paths
+            .iter()
+            .map(|path| PathSpec::new(&root, &current_dir, path))
+            .collect::<Result<Vec<_>>>()?;
+
+        // 未指定版本时搜索全部快照（从新到旧）
+        let snapshot_ids = if revs.is_empty() {
+            repo.snapshot_manager
+                .list_history()?
+                .into_iter()
+                .map(|entry| entry.snapshot_id)
+                .collect()
+        } else {
+            let mut ids = Vec::new();
+            for rev in &revs {
+                if revision::is_range(&repo, rev)? {
+                    ids.extend(revision::resolve_range(&repo, rev)?);
+                } else {
+                    ids.push(revision::resolve(&repo, rev)?);
+                }
+            }
+            ids
+        };
+
+        // 许多快照共享同一个对象，每个对象只解码和搜索一次
+        let mut cache: HashMap<String, ObjectMatches> = HashMap::new();
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+
+        for snapshot_id in &snapshot_ids {
+            let snapshot = repo.snapshot_manager.load_snapshot(snapshot_id)?;
+            let rev = snapshot.number.to_string();
+
+            let mut files: Vec<(&PathBuf, &String)> = snapshot
+                .files
+                .iter()
+                .filter(|(path, _)| {
+                    pathspecs.is_empty() || pathspecs.iter().any(|spec| spec.matches(path))
+                })
+                .map(|(path, entry)| (path, &entry.hash))
+                .collect();
+            files.sort();
+
+            for (path, hash) in files {
+                let matches = match cache.get(hash) {
+                    Some(matches) => matches.clone(),
+                    None => {
+                        // 超过大小限制的文件没有保存内容，跳过
+                        let matches = if repo.object_store.exists(hash) {
+                            LineSearch::run(&repo, hash, &regex)?
+                        } else {
+                            None
+                        };
+                        cache.insert(hash.clone(), matches.clone());
+                        matches
+                    }
+                };
+
+                let Some(matches) = matches.filter(|matches| !matches.is_empty()) else {
+                    continue;
+                };
+
+                let result = if files_only {
+                    writeln!(out, "{}:{}", rev.magenta(), path.display())
+                } else {
+                    matches.iter().try_for_each(|(number, line)| {
+                        writeln!(
+                            out,
+                            "{}:{}:{}:{}",
+                            rev.magenta(),
+                            path.display(),
+                            number.to_string().green(),
+                            line
+                        )
+                    })
+                };
+
+                // 输出被管道提前关闭（例如 `| head`）时正常退出
+                match result {
+                    Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+                    other => other?,
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// 边解码边逐行搜索，内存占用与文件大小无关
+struct LineSearch<'a> {
+    regex: &'a Regex,
+    /// 尚未遇到换行符的行
+    pending: Vec<u8>,
+    line: usize,
+    sniffed: usize,
+    binary: bool,
+    matches: Vec<(usize, String)>,
+}
+
+impl<'a> LineSearch<'a> {
+    fn run(repo: &Repository, hash: &str, regex: &'a Regex) -> Result<ObjectMatches> {
+        let mut search = LineSearch {
+            regex,
+            pending: Vec::new(),
+            line: 0,
+            sniffed: 0,
+            binary: false,
+            matches: Vec::new(),
+        };
+
+        // 发现二进制内容后写入报错以提前结束解码
+        if let Err(e) = repo.object_store.write_content(hash, &mut search) {
+            if search.binary {
+                return Ok(None);
+            }
+            return Err(e);
+        }
+
+        if !search.pending.is_empty() {
+            let line = std::mem::take(&mut search.pending);
+            search.check(&line);
+        }
+        Ok(Some(Rc::new(search.matches)))
+    }
+
+    fn check(&mut self, line: &[u8]) {
+        self.line += 1;
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let text = String::from_utf8_lossy(line);
+        if self.regex.is_match(&text) {
+            self.matches.push((self.line, text.into_owned()));
+        }
+    }
+}
+
+impl Write for LineSearch<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.sniffed < BINARY_SNIFF_LEN {
+            let sample = &buf[..buf.len().min(BINARY_SNIFF_LEN - self.sniffed)];
+            self.sniffed += sample.len();
+            if DiffEngine::is_binary(sample) {
+                self.binary = true;
+                return Err(io::Error::other("binary content"));
+            }
+        }
+
+        let mut rest = buf;
+        while let Some(end) = rest.iter().position(|&byte| byte == b'\n') {
+            let (line, tail) = rest.split_at(end + 1);
+            if self.pending.is_empty() {
+                self.check(line);
+            } else {
+                let mut full = std::mem::take(&mut self.pending);
+                full.extend_from_slice(line);
+                self.check(&full);
+            }
+            rest = tail;
+        }
+        self.pending.extend_from_slice(rest);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -1,17 +1,50 @@
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use std::env;
 
-use crate::Repository;
+use crate::{Repository, utils};
 
 pub struct HistoryCommand;
 
 impl HistoryCommand {
-    pub fn execute(json_output: bool) -> Result<()> {
+    pub fn execute(
+        json_output: bool,
+        lineage: bool,
+        all: bool,
+        timeline: Option<String>,
+    ) -> Result<()> {
         let current_dir = env::current_dir()?;
         let root = Repository::find_root(&current_dir)?;
         let repo = Repository::new(root)?;
 
-        let history = repo.snapshot_manager.list_history()?;
+        let head = repo.snapshot_manager.head()?;
+        let all_history = repo.snapshot_manager.list_history()?;
+
+        // 默认只显示当前时间线（从时间线上最新的快照沿父快照回溯），
+        // --lineage 从 HEAD 回溯，--all 显示所有快照
+        let mut history = if all {
+            all_history.clone()
+        } else if lineage {
+            match &head {
+                Some(head) => repo.snapshot_manager.lineage(head)?,
+                None => Vec::new(),
+            }
+        } else {
+            let name = match timeline {
+                Some(name) => name,
+                None => repo.refs.current_timeline()?,
+            };
+            match repo.refs.get_timeline(&name)? {
+                Some(timeline) => repo.snapshot_manager.lineage(&timeline.snapshot_id)?,
+                None if name == repo.refs.current_timeline()? => all_history.clone(),
+                None => return Err(anyhow!("Timeline '{}' not found", name)),
+            }
+        };
+
+        // 附上指向各快照的标签
+        let mut tags = repo.refs.tags_by_snapshot()?;
+        for entry in &mut history {
+            entry.tags = tags.remove(&entry.snapshot_id).unwrap_or_default();
+        }
 
         if json_output {
             println!("{}", serde_json::to_string_pretty(&history)?);
@@ -21,6 +54,8 @@ impl HistoryCommand {
                 return Ok(());
             }
 
+            let timelines = repo.refs.list_timelines()?;
+
             println!(
                 "{:<3} {:<8} {:<20} {:>2} {:>2} {:>2} Message",
                 "#", "ID", "Time", "+", "~", "-"
@@ -39,15 +74,52 @@ impl HistoryCommand {
                     entry.timestamp.format("%Y-%m-%dT%H:%M:%SZ").to_string()
                 };
 
+                let mut decorations = Vec::new();
+                if head.as_deref() == Some(entry.snapshot_id.as_str()) {
+                    decorations.push("HEAD".to_string());
+                }
+                // 以该快照为最新快照的时间线
+                decorations.extend(
+                    timelines
+                        .iter()
+                        .filter(|timeline| timeline.snapshot_id == entry.snapshot_id)
+                        .map(|timeline| timeline.name.clone()),
+                );
+                if !entry.tags.is_empty() {
+                    decorations.push(format!("tag: {}", entry.tags.join(", ")));
+                }
+                // 父快照不是编号上的前一个快照时（在回滚后创建），标出父快照；
+                // 父快照已被删除时不显示
+                if let Some(parent) = &entry.parent
+                    && let Some(parent_entry) = all_history
+                        .iter()
+                        .find(|other| &other.snapshot_id == parent)
+                {
+                    let previous = all_history
+                        .iter()
+                        .filter(|other| other.number < entry.number)
+                        .max_by_key(|other| other.number);
+                    if previous.map(|other| &other.snapshot_id) != Some(parent) {
+                        decorations.push(format!("parent: #{}", parent_entry.number));
+                    }
+                }
+
+                let tags_display = if decorations.is_empty() {
+                    String::new()
+                } else {
+                    format!(" ({})", decorations.join(", "))
+                };
+
                 println!(
-                    "{:<3} {:<8} {:<20} {:>2} {:>2} {:>2} \"{}\"",
+                    "{:<3} {:<8} {:<20} {:>2} {:>2} {:>2} \"{}\"{}",
                     entry.number,
-                    entry.snapshot_id,
+                    utils::short_id(&entry.snapshot_id),
                     time_display,
                     entry.added,
                     entry.modified,
                     entry.deleted,
-                    entry.message
+                    entry.message,
+                    tags_display
                 );
             }
         }
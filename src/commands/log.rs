@@ -0,0 +1,167 @@
+use anyhow::{Result, anyhow};
+use colored::*;
+use std::collections::HashMap;
+use std::env;
+use std::path::PathBuf;
+
+use crate::commands::DiffCommand;
+use crate::{FileEntry, HistoryEntry, Repository, pathspec, utils};
+
+pub struct LogCommand;
+
+/// 文件在某个快照中的变化
+enum FileChange {
+    Added,
+    Modified,
+    Deleted,
+    Renamed(PathBuf),
+}
+
+impl LogCommand {
+    /// 沿当前时间线列出指定文件发生变化的每个快照
+    pub fn execute(path: String, patch: bool, follow: bool, context: usize) -> Result<()> {
+        let current_dir = env::current_dir()?;
+        let root = Repository::find_root(&current_dir)?;
+        let repo = Repository::new(root.clone())?;
+
+        let mut path = pathspec::repo_relative(&root, &current_dir, &path)?;
+
+        // 从当前时间线上最新的快照沿父快照回溯
+        let tip = match repo.refs.get_timeline(&repo.refs.current_timeline()?)? {
+            Some(timeline) => Some(timeline.snapshot_id),
+            None => repo.snapshot_manager.head()?,
+        };
+        let lineage: Vec<HistoryEntry> = match tip {
+            Some(tip) => repo.snapshot_manager.lineage(&tip)?,
+            None => Vec::new(),
+        };
+
+        let mut found = false;
+        let mut parent_files: Option<HashMap<PathBuf, FileEntry>> = None;
+
+        for (i, entry) in lineage.iter().enumerate() {
+            let files = match parent_files.take() {
+                Some(files) => files,
+                None => {
+                    repo.snapshot_manager
+                        .load_snapshot(&entry.snapshot_id)?
+                        .files
+                }
+            };
+            let parent = match lineage.get(i + 1) {
+                Some(parent) => {
+                    repo.snapshot_manager
+                        .load_snapshot(&parent.snapshot_id)?
+                        .files
+                }
+                None => HashMap::new(),
+            };
+
+            let current = files.get(&path);
+            let change = match (parent.get(&path), current) {
+                (None, Some(new)) => match Self::rename_source(follow, new, &parent, &files) {
+                    Some(old_path) => FileChange::Renamed(old_path),
+                    None => FileChange::Added,
+                },
+                (Some(old), Some(new)) if old.hash != new.hash => FileChange::Modified,
+                (Some(_), None) => FileChange::Deleted,
+                _ => {
+                    parent_files = Some(parent);
+                    continue;
+                }
+            };
+            found = true;
+
+            let old_path = match &change {
+                FileChange::Renamed(old_path) => old_path.clone(),
+                _ => path.clone(),
+            };
+            let previous = parent.get(&old_path);
+            Self::print_change(entry, &change, current, repo.config.use_local_timezone);
+
+            if patch {
+                DiffCommand::diff_snapshot_entries(&repo, &path, previous, current, context)?;
+                println!();
+            }
+
+            // 跟踪重命名：更早的快照中使用旧路径
+            path = old_path;
+            parent_files = Some(parent);
+        }
+
+        if !found {
+            return Err(anyhow!(
+                "error: '{}' does not appear in any snapshot of this timeline",
+                path.display()
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// 启用 --follow 时，父快照中内容相同、在本快照中已不存在的文件视为重命名前的文件
+    fn rename_source(
+        follow: bool,
+        new: &FileEntry,
+        parent: &HashMap<PathBuf, FileEntry>,
+        files: &HashMap<PathBuf, FileEntry>,
+    ) -> Option<PathBuf> {
+        if !follow {
+            return None;
+        }
+
+        let mut candidates: Vec<&PathBuf> = parent
+            .iter()
+            .filter(|(old_path, old)| old.hash == new.hash && !files.contains_key(*old_path))
+            .map(|(old_path, _)| old_path)
+            .collect();
+        candidates.sort();
+        candidates.first().map(|path| (*path).clone())
+    }
+
+    fn print_change(
+        entry: &HistoryEntry,
+        change: &FileChange,
+        version: Option<&FileEntry>,
+        local_time: bool,
+    ) {
+        let action = match change {
+            FileChange::Added => "added".green().to_string(),
+            FileChange::Modified => "modified".yellow().to_string(),
+            FileChange::Deleted => "deleted".red().to_string(),
+            FileChange::Renamed(old_path) => {
+                format!("{} from {}", "renamed".cyan(), old_path.display())
+            }
+        };
+
+        // 删除后没有新版本，大小和哈希显示为 -
+        let (size, hash) = match version {
+            Some(version) => (
+                utils::format_size(version.size),
+                utils::short_id(&version.hash),
+            ),
+            None => ("-".to_string(), "-".to_string()),
+        };
+
+        let time_display = if local_time {
+            entry
+                .timestamp
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        } else {
+            entry.timestamp.format("%Y-%m-%dT%H:%MZ").to_string()
+        };
+
+        println!(
+            "{:<4} {} {} {:>10} {:<8} {} \"{}\"",
+            format!("#{}", entry.number),
+            utils::short_id(&entry.snapshot_id).yellow(),
+            time_display,
+            size,
+            hash,
+            action,
+            entry.message
+        );
+    }
+}
@@ -0,0 +1,56 @@
+use anyhow::Result;
+use std::env;
+
+use crate::pathspec::PathSpec;
+use crate::{FileEntry, Repository, revision};
+
+pub struct LsCommand;
+
+impl LsCommand {
+    /// 列出快照中的文件（可限定目录或 glob），附带大小和哈希
+    pub fn execute(snapshot_id: String, path: Option<String>, json_output: bool) -> Result<()> {
+        let current_dir = env::current_dir()?;
+        let root = Repository::find_root(&current_dir)?;
+        let repo = Repository::new(root.clone())?;
+
+        let snapshot_id = revision::resolve(&repo, &snapshot_id)?;
+        let snapshot = repo.snapshot_manager.load_snapshot(&snapshot_id)?;
+
+        let spec = path
+            .map(|path| PathSpec::new(&root, &current_dir, &path))
+            .transpose()?;
+        let mut files: Vec<&FileEntry> = snapshot
+            .files
+            .values()
+            .filter(|entry| spec.as_ref().is_none_or(|spec| spec.matches(&entry.path)))
+            .collect();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        if json_output {
+            let entries: Vec<_> = files
+                .iter()
+                .map(|entry| {
+                    serde_json::json!({
+                        "path": entry.path,
+                        "size": entry.size,
+                        "hash": entry.hash,
+                        "modified": entry.modified,
+                    })
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&entries)?);
+            return Ok(());
+        }
+
+        for entry in &files {
+            println!(
+                "{} {:>10}  {}",
+                entry.hash,
+                entry.size,
+                entry.path.display()
+            );
+        }
+
+        Ok(())
+    }
+}
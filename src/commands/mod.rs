@@ -1,21 +1,41 @@
 pub mod add;
 pub mod back;
+pub mod blame;
+pub mod cat;
 pub mod config;
 pub mod diff;
+pub mod grep;
 pub mod history;
 pub mod ignore;
 pub mod init;
+pub mod log;
+pub mod ls;
+pub mod restore;
+pub mod show;
+pub mod squash;
 pub mod status;
 pub mod tag;
+pub mod timeline;
+pub mod trash;
 pub mod utils;
 
 pub use add::AddCommand;
 pub use back::BackCommand;
+pub use blame::BlameCommand;
+pub use cat::CatCommand;
 pub use config::ConfigCommand;
 pub use diff::DiffCommand;
+pub use grep::GrepCommand;
 pub use history::HistoryCommand;
 pub use ignore::IgnoreCommand;
 pub use init::InitCommand;
+pub use log::LogCommand;
+pub use ls::LsCommand;
+pub use restore::RestoreCommand;
+pub use show::ShowCommand;
+pub use squash::SquashCommand;
 pub use status::StatusCommand;
 pub use tag::TagCommand;
+pub use timeline::TimelineCommand;
+pub use trash::TrashCommand;
 pub use utils::UtilsCommand;
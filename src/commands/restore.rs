@@ -0,0 +1,117 @@
+use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+use std::env;
+use std::path::{Path, PathBuf};
+
+use crate::index::IndexManager;
+use crate::pathspec::PathSpec;
+use crate::restore::{RestoreAction, RestorePlan};
+use crate::{FileEntry, Index, Repository, revision, utils};
+
+pub struct RestoreCommand;
+
+impl RestoreCommand {
+    /// 从快照中恢复指定的文件或目录，路径相对于当前目录，支持 glob
+    pub fn execute(
+        snapshot_id: String,
+        paths: Vec<String>,
+        to: Option<PathBuf>,
+        dry_run: bool,
+    ) -> Result<()> {
+        let current_dir = env::current_dir()?;
+        let root = Repository::find_root(&current_dir)?;
+        let repo = Repository::new(root.clone())?;
+
+        let snapshot_id = revision::resolve(&repo, &snapshot_id)?;
+        let snapshot = repo.snapshot_manager.load_snapshot(&snapshot_id)?;
+
+        let selected = Self::select_files(&snapshot.files, &root, &current_dir, &paths)?;
+
+        // --to 写到其他目录，否则恢复到工作区
+        let target_dir = match &to {
+            Some(dir) => current_dir.join(dir),
+            None => root.clone(),
+        };
+        let tracked = if to.is_none() {
+            let mut index = repo.index_manager.load()?;
+            index.files.retain(|path, _| selected.contains_key(path));
+            index
+        } else {
+            Index::new()
+        };
+        let plan = RestorePlan::build(&target_dir, &selected, &repo.index_manager, &tracked)?;
+
+        println!(
+            "Restoring {} file(s) from snapshot {} into {}:",
+            selected.len(),
+            utils::short_id(&snapshot_id),
+            target_dir.display()
+        );
+        plan.print();
+
+        if dry_run {
+            println!("Dry run: no files were changed");
+            return Ok(());
+        }
+
+        if to.is_none() {
+            let backup_dir = root.join(utils::create_backup_name());
+            let backed_up = plan.backup(&root, &backup_dir)?;
+            plan.apply(&root, &repo.object_store)?;
+
+            // 只更新恢复的路径在索引中的记录，使用恢复后文件实际的 stat 信息
+            let mut index = repo.index_manager.load()?;
+            for change in &plan.changes {
+                if change.action != RestoreAction::Delete {
+                    IndexManager::record_stat(&mut index, &root, selected[&change.path].clone())?;
+                }
+            }
+            repo.index_manager.save(&index)?;
+
+            if backed_up > 0 {
+                println!(
+                    "{} overwritten file(s) backed up to {}",
+                    backed_up,
+                    backup_dir.display()
+                );
+            }
+        } else {
+            plan.apply(&target_dir, &repo.object_store)?;
+        }
+
+        println!("Restored {} file(s)", plan.changes.len());
+
+        Ok(())
+    }
+
+    /// 按路径或 glob 选出快照中的文件，目录会包含其下的所有文件
+    fn select_files(
+        files: &HashMap<PathBuf, FileEntry>,
+        root: &Path,
+        current_dir: &Path,
+        patterns: &[String],
+    ) -> Result<HashMap<PathBuf, FileEntry>> {
+        let mut selected = HashMap::new();
+
+        for pattern in patterns {
+            let matcher = PathSpec::new(root, current_dir, pattern)?;
+            let matched: Vec<_> = files
+                .iter()
+                .filter(|(path, _)| matcher.matches(path))
+                .collect();
+
+            if matched.is_empty() {
+                return Err(anyhow!(
+                    "error: pathspec '{}' did not match any file in the snapshot",
+                    pattern
+                ));
+            }
+
+            for (path, entry) in matched {
+                selected.insert(path.clone(), entry.clone());
+            }
+        }
+
+        Ok(selected)
+    }
+}
@@ -0,0 +1,96 @@
+use anyhow::Result;
+use colored::*;
+use std::collections::{BTreeSet, HashMap};
+use std::env;
+
+use crate::commands::DiffCommand;
+use crate::{Repository, revision, utils};
+
+pub struct ShowCommand;
+
+impl ShowCommand {
+    /// 显示快照的元数据、变更摘要以及相对于父快照的逐文件差异
+    pub fn execute(snapshot_id: String, stat_only: bool, context: usize) -> Result<()> {
+        let current_dir = env::current_dir()?;
+        let root = Repository::find_root(&current_dir)?;
+        let repo = Repository::new(root)?;
+
+        let snapshot_id = revision::resolve(&repo, &snapshot_id)?;
+        let snapshot = repo.snapshot_manager.load_snapshot(&snapshot_id)?;
+        let parent_id = repo.snapshot_manager.parent_id(&snapshot)?;
+        let history = repo.snapshot_manager.list_history()?;
+
+        println!("{}", format!("snapshot {}", snapshot.id).yellow());
+        println!("Number:  {}", snapshot.number);
+        if let Some(parent_id) = &parent_id {
+            let number = history
+                .iter()
+                .find(|entry| &entry.snapshot_id == parent_id)
+                .map(|entry| format!(" (#{})", entry.number))
+                .unwrap_or_default();
+            println!("Parent:  {}{}", utils::short_id(parent_id), number);
+        }
+
+        let time_display = if repo.config.use_local_timezone {
+            snapshot
+                .timestamp
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string()
+        } else {
+            snapshot.timestamp.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+        };
+        println!("Date:    {}", time_display);
+
+        let tags = repo
+            .refs
+            .tags_by_snapshot()?
+            .remove(&snapshot.id)
+            .unwrap_or_default();
+        if !tags.is_empty() {
+            println!("Tags:    {}", tags.join(", "));
+        }
+
+        println!();
+        println!("    {}", snapshot.message);
+        println!();
+
+        // 相对于父快照的变更；第一个快照与空目录比较
+        let parent_files = match &parent_id {
+            Some(id) => repo.snapshot_manager.load_snapshot(id)?.files,
+            None => HashMap::new(),
+        };
+
+        let paths: BTreeSet<_> = parent_files.keys().chain(snapshot.files.keys()).collect();
+        let mut counts = (0, 0, 0);
+        for path in paths {
+            let (status, label) = match (parent_files.get(path), snapshot.files.get(path)) {
+                (None, Some(_)) => {
+                    counts.0 += 1;
+                    ("A".green(), path)
+                }
+                (Some(old), Some(new)) if old.hash != new.hash => {
+                    counts.1 += 1;
+                    ("M".yellow(), path)
+                }
+                (Some(_), None) => {
+                    counts.2 += 1;
+                    ("D".red(), path)
+                }
+                _ => continue,
+            };
+            println!(" {} {}", status, label.display());
+        }
+        println!(
+            " {} added, {} modified, {} deleted",
+            counts.0, counts.1, counts.2
+        );
+
+        if !stat_only {
+            println!();
+            DiffCommand::diff_snapshot_files(&repo, &parent_files, &snapshot.files, context)?;
+        }
+
+        Ok(())
+    }
+}
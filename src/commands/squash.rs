@@ -0,0 +1,83 @@
+use anyhow::{Result, anyhow};
+use std::env;
+
+use crate::{Repository, revision, utils};
+
+pub struct SquashCommand;
+
+impl SquashCommand {
+    /// 将 `<from>..<to>` 范围内的连续快照合并为一个快照
+    pub fn execute(range: String, message: Option<String>, dry_run: bool) -> Result<()> {
+        let current_dir = env::current_dir()?;
+        let root = Repository::find_root(&current_dir)?;
+        let mut repo = Repository::new(root)?;
+
+        if !revision::is_range(&repo, &range)? {
+            return Err(anyhow!(
+                "error: expected a range such as 3..7, got '{}'",
+                range
+            ));
+        }
+        let snapshot_ids = revision::resolve_range(&repo, &range)?;
+        if snapshot_ids.len() < 2 {
+            return Err(anyhow!(
+                "error: the range contains fewer than two snapshots"
+            ));
+        }
+
+        let history = repo.snapshot_manager.list_history()?;
+        let number = |id: &str| {
+            history
+                .iter()
+                .find(|entry| entry.snapshot_id == id)
+                .map(|entry| entry.number)
+                .unwrap_or(0)
+        };
+        let first = number(&snapshot_ids[0]);
+        let last = number(&snapshot_ids[snapshot_ids.len() - 1]);
+        let renumbered = history.iter().filter(|entry| entry.number > last).count();
+
+        if dry_run {
+            println!(
+                "Would squash {} snapshots (#{}..#{}) into snapshot #{}",
+                snapshot_ids.len(),
+                first,
+                last,
+                first
+            );
+            for id in &snapshot_ids {
+                if let Some(entry) = history.iter().find(|entry| &entry.snapshot_id == id) {
+                    println!(
+                        "  #{:<4} {} \"{}\"",
+                        entry.number,
+                        utils::short_id(id),
+                        entry.message
+                    );
+                }
+            }
+            if renumbered > 0 {
+                println!("Would renumber {} later snapshots", renumbered);
+            }
+            return Ok(());
+        }
+
+        let squashed = repo.squash_snapshots(&snapshot_ids, message)?;
+        println!(
+            "Squashed {} snapshots (#{}..#{}) into snapshot #{} {}",
+            snapshot_ids.len(),
+            first,
+            last,
+            first,
+            utils::short_id(&squashed)
+        );
+        if renumbered > 0 {
+            println!(
+                "Renumbered {} later snapshots (their IDs changed)",
+                renumbered
+            );
+        }
+
+        // 中间快照独占的对象不再被引用，在同一次运行中回收
+        repo.collect_garbage(false, false, &[])
+    }
+}
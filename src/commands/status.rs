@@ -2,7 +2,8 @@ use anyhow::Result;
 use colored::*;
 use std::env;
 
-use crate::Repository;
+use crate::restore::Conflicts;
+use crate::{Repository, utils};
 
 pub struct StatusCommand;
 
@@ -19,8 +20,36 @@ impl StatusCommand {
             .index_manager
             .compare_with_current(&root, &dummy_matcher)?;
 
+        // 工作区当前基于的快照
+        let history = repo.snapshot_manager.list_history()?;
+        let head = repo.snapshot_manager.head()?;
+        let head_entry = head
+            .as_ref()
+            .and_then(|id| history.iter().find(|entry| &entry.snapshot_id == id));
+
+        // 当前时间线上最新的快照，回滚后 HEAD 可能落后于它
+        let timeline = repo.refs.current_timeline()?;
+        let latest_number = match repo.refs.get_timeline(&timeline)? {
+            Some(tip) => history
+                .iter()
+                .find(|entry| entry.snapshot_id == tip.snapshot_id)
+                .map(|entry| entry.number),
+            None => history.iter().map(|entry| entry.number).max(),
+        };
+
+        // 合并恢复留下的未解决冲突
+        let conflicts = Conflicts::unresolved(&root)?;
+
         if json {
             let status = serde_json::json!({
+                "conflicts": conflicts,
+                "timeline": timeline,
+                "head": head_entry.map(|entry| serde_json::json!({
+                    "snapshot_id": entry.snapshot_id,
+                    "number": entry.number,
+                    "message": entry.message,
+                    "latest": Some(entry.number) == latest_number
+                })),
                 "added": added,
                 "modified": modified,
                 "deleted": deleted,
@@ -30,6 +59,40 @@ impl StatusCommand {
             return Ok(());
         }
 
+        println!("On timeline {}", timeline.cyan().bold());
+        if let Some(entry) = head_entry {
+            let behind = match latest_number {
+                Some(latest) if latest != entry.number => {
+                    format!(" (timeline is at snapshot {})", latest)
+                        .dimmed()
+                        .to_string()
+                }
+                _ => String::new(),
+            };
+            println!(
+                "Based on snapshot {} ({}) \"{}\"{}",
+                entry.number.to_string().bold(),
+                utils::short_id(&entry.snapshot_id),
+                entry.message,
+                behind
+            );
+        }
+
+        if !conflicts.is_empty() {
+            println!(
+                "\n{} {}:",
+                "Unresolved conflicts".red().bold(),
+                conflicts.len()
+            );
+            for path in &conflicts {
+                println!("  {} {}", "!".red(), path.display());
+            }
+            println!(
+                "{}",
+                "Remove the conflict markers from these files before creating a snapshot".dimmed()
+            );
+        }
+
         if added.is_empty() && modified.is_empty() && deleted.is_empty() {
             println!("{}", "Working directory clean".green());
             return Ok(());
@@ -1,26 +1,119 @@
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use std::env;
 
-use crate::Repository;
+use crate::refs::{RefStore, Tag};
+use crate::{Repository, revision, utils};
 
 pub struct TagCommand;
 
 impl TagCommand {
-    pub fn execute(name: String, snapshot_id: String) -> Result<()> {
-        let current_dir = env::current_dir()?;
-        let root = Repository::find_root(&current_dir)?;
-        let mut repo = Repository::new(root)?;
+    pub fn execute(name: String, snapshot_id: String, message: Option<String>) -> Result<()> {
+        let repo = Self::open_repo()?;
 
-        // 验证快照是否存在
-        repo.snapshot_manager.load_snapshot(&snapshot_id)?;
+        RefStore::validate_tag_name(&name)?;
+        if repo.refs.get_tag(&name)?.is_some() {
+            return Err(anyhow!(
+                "Tag '{}' already exists (use `rustory tag move` to retarget it)",
+                name
+            ));
+        }
 
-        // 添加标签到配置
-        repo.config
-            .set(&format!("tag.{}", name), snapshot_id.clone())?;
-        repo.config.save(&repo.rustory_dir)?;
+        // 标签保存完整的快照ID
+        let snapshot_id = revision::resolve(&repo, &snapshot_id)?;
+        repo.refs
+            .write_tag(&Tag::new(&name, &snapshot_id, message))?;
 
-        println!("Tagged snapshot {} as \"{}\"", snapshot_id, name);
+        println!(
+            "Tagged snapshot {} as \"{}\"",
+            utils::short_id(&snapshot_id),
+            name
+        );
 
         Ok(())
     }
+
+    pub fn list(json_output: bool) -> Result<()> {
+        let repo = Self::open_repo()?;
+        let tags = repo.refs.list_tags()?;
+
+        if json_output {
+            println!("{}", serde_json::to_string_pretty(&tags)?);
+            return Ok(());
+        }
+
+        if tags.is_empty() {
+            println!("No tags found.");
+            return Ok(());
+        }
+
+        let history = repo.snapshot_manager.list_history()?;
+        for tag in &tags {
+            let number = history
+                .iter()
+                .find(|entry| entry.snapshot_id == tag.snapshot_id)
+                .map(|entry| format!("#{}", entry.number))
+                .unwrap_or_else(|| "(missing)".to_string());
+
+            match &tag.message {
+                Some(message) => println!(
+                    "{:<16} {:<8} {:<10} {} by {}: {}",
+                    tag.name,
+                    utils::short_id(&tag.snapshot_id),
+                    number,
+                    tag.created.format("%Y-%m-%d %H:%M"),
+                    tag.author.as_deref().unwrap_or("unknown"),
+                    message
+                ),
+                None => println!(
+                    "{:<16} {:<8} {}",
+                    tag.name,
+                    utils::short_id(&tag.snapshot_id),
+                    number
+                ),
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn delete(name: String) -> Result<()> {
+        let repo = Self::open_repo()?;
+        let tag = repo.refs.delete_tag(&name)?;
+
+        println!(
+            "Deleted tag \"{}\" (was {})",
+            tag.name,
+            utils::short_id(&tag.snapshot_id)
+        );
+
+        Ok(())
+    }
+
+    /// 将已有标签改为指向另一个快照，保留附注信息
+    pub fn move_tag(name: String, snapshot_id: String) -> Result<()> {
+        let repo = Self::open_repo()?;
+        let mut tag = repo
+            .refs
+            .get_tag(&name)?
+            .ok_or_else(|| anyhow!("Tag '{}' not found", name))?;
+
+        let snapshot_id = revision::resolve(&repo, &snapshot_id)?;
+        let old_id = std::mem::replace(&mut tag.snapshot_id, snapshot_id);
+        repo.refs.write_tag(&tag)?;
+
+        println!(
+            "Moved tag \"{}\" from {} to {}",
+            tag.name,
+            utils::short_id(&old_id),
+            utils::short_id(&tag.snapshot_id)
+        );
+
+        Ok(())
+    }
+
+    fn open_repo() -> Result<Repository> {
+        let current_dir = env::current_dir()?;
+        let root = Repository::find_root(&current_dir)?;
+        Repository::new(root)
+    }
 }
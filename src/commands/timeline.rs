@@ -0,0 +1,128 @@
+use anyhow::{Result, anyhow};
+use std::env;
+
+use crate::commands::BackCommand;
+use crate::refs::{RefStore, Timeline};
+use crate::{Repository, revision, utils};
+
+pub struct TimelineCommand;
+
+impl TimelineCommand {
+    /// 创建时间线，默认从工作区当前基于的快照开始
+    pub fn create(name: String, snapshot_id: Option<String>) -> Result<()> {
+        let repo = Self::open_repo()?;
+
+        RefStore::validate_timeline_name(&name)?;
+        if repo.refs.get_timeline(&name)?.is_some() {
+            return Err(anyhow!("Timeline '{}' already exists", name));
+        }
+
+        let snapshot_id = match snapshot_id {
+            Some(spec) => revision::resolve(&repo, &spec)?,
+            None => repo
+                .snapshot_manager
+                .head()?
+                .ok_or_else(|| anyhow!("error: no snapshots yet"))?,
+        };
+        repo.refs
+            .write_timeline(&Timeline::new(&name, &snapshot_id))?;
+
+        println!(
+            "Created timeline \"{}\" at snapshot {}",
+            name,
+            utils::short_id(&snapshot_id)
+        );
+        println!("Use 'rustory timeline switch {}' to work on it", name);
+
+        Ok(())
+    }
+
+    /// 切换时间线：将工作区恢复到该时间线上最新的快照
+    pub fn switch(name: String, force: bool) -> Result<()> {
+        let current_dir = env::current_dir()?;
+        let root = Repository::find_root(&current_dir)?;
+        let repo = Repository::new(root.clone())?;
+
+        let timeline = repo
+            .refs
+            .get_timeline(&name)?
+            .ok_or_else(|| anyhow!("Timeline '{}' not found", name))?;
+
+        if repo.refs.current_timeline()? == name {
+            println!("Already on timeline \"{}\"", name);
+            return Ok(());
+        }
+
+        if repo.snapshot_manager.head()?.as_deref() != Some(timeline.snapshot_id.as_str()) {
+            // 未保存的改动会被覆盖（虽然会先备份），默认拒绝切换
+            let dummy_matcher = ignore::gitignore::GitignoreBuilder::new(&root).build()?;
+            let (added, modified, deleted) = repo
+                .index_manager
+                .compare_with_current(&root, &dummy_matcher)?;
+            let dirty = !added.is_empty() || !modified.is_empty() || !deleted.is_empty();
+            if dirty && !force {
+                return Err(anyhow!(
+                    "Working directory has unsaved changes; run 'rustory add' first or use --force"
+                ));
+            }
+
+            BackCommand::restore_to_working_dir(&repo, &root, &timeline.snapshot_id, false, false)?;
+        }
+
+        repo.refs.set_current_timeline(&name)?;
+        println!("Switched to timeline \"{}\"", name);
+
+        Ok(())
+    }
+
+    pub fn list() -> Result<()> {
+        let repo = Self::open_repo()?;
+        let timelines = repo.refs.list_timelines()?;
+        let current = repo.refs.current_timeline()?;
+
+        if timelines.is_empty() {
+            println!("No timelines found.");
+            return Ok(());
+        }
+
+        let history = repo.snapshot_manager.list_history()?;
+        for timeline in &timelines {
+            let marker = if timeline.name == current { "*" } else { " " };
+            let (number, message) = history
+                .iter()
+                .find(|entry| entry.snapshot_id == timeline.snapshot_id)
+                .map(|entry| (format!("#{}", entry.number), entry.message.as_str()))
+                .unwrap_or_else(|| ("(missing)".to_string(), ""));
+
+            println!(
+                "{} {:<16} {:<8} {:<6} \"{}\"",
+                marker,
+                timeline.name,
+                utils::short_id(&timeline.snapshot_id),
+                number,
+                message
+            );
+        }
+
+        Ok(())
+    }
+
+    pub fn delete(name: String) -> Result<()> {
+        let repo = Self::open_repo()?;
+        let timeline = repo.refs.delete_timeline(&name)?;
+
+        println!(
+            "Deleted timeline \"{}\" (was {}); its snapshots are kept",
+            timeline.name,
+            utils::short_id(&timeline.snapshot_id)
+        );
+
+        Ok(())
+    }
+
+    fn open_repo() -> Result<Repository> {
+        let current_dir = env::current_dir()?;
+        let root = Repository::find_root(&current_dir)?;
+        Repository::new(root)
+    }
+}
@@ -0,0 +1,80 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use colored::*;
+use std::env;
+
+use crate::{Repository, utils};
+
+pub struct TrashCommand;
+
+impl TrashCommand {
+    /// 列出回收站中的快照及其永久删除的时间
+    pub fn list() -> Result<()> {
+        let repo = Self::open_repo()?;
+        let trashed = repo.snapshot_manager.trash().list()?;
+
+        if trashed.is_empty() {
+            println!("Trash is empty.");
+            return Ok(());
+        }
+
+        let format_time = |time: DateTime<Utc>| {
+            if repo.config.use_local_timezone {
+                time.with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string()
+            } else {
+                time.format("%Y-%m-%d %H:%MZ").to_string()
+            }
+        };
+
+        println!(
+            "{:<8} {:<5} {:<17} {:<17} Message",
+            "ID", "#", "Deleted", "Expires"
+        );
+        println!("{}", "-".repeat(68));
+
+        let grace_days = repo.config.trash_grace_days;
+        for item in &trashed {
+            let expires_at = item.expires_at(grace_days);
+            let expires = if expires_at <= Utc::now() {
+                format!("{:<17}", "next gc").red()
+            } else {
+                format!("{:<17}", format_time(expires_at)).normal()
+            };
+            println!(
+                "{:<8} {:<5} {:<17} {} \"{}\"",
+                utils::short_id(&item.snapshot.id).yellow(),
+                item.snapshot.number,
+                format_time(item.deleted_at),
+                expires,
+                item.snapshot.message
+            );
+        }
+
+        println!("Use 'rustory undelete <id>' to restore a snapshot");
+        Ok(())
+    }
+
+    /// 从回收站恢复快照（按ID前缀或编号）
+    pub fn undelete(spec: String) -> Result<()> {
+        let repo = Self::open_repo()?;
+        let snapshot_id = repo.snapshot_manager.trash().resolve(&spec)?;
+        let entry = repo.undelete_snapshot(&snapshot_id)?;
+
+        println!(
+            "Restored snapshot #{} {} \"{}\"",
+            entry.number,
+            utils::short_id(&entry.snapshot_id),
+            entry.message
+        );
+
+        Ok(())
+    }
+
+    fn open_repo() -> Result<Repository> {
+        let current_dir = env::current_dir()?;
+        let root = Repository::find_root(&current_dir)?;
+        Repository::new(root)
+    }
+}
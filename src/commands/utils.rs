@@ -1,8 +1,8 @@
-use anyhow::{Result, anyhow};
+use anyhow::Result;
 use std::env;
 use std::io::{self, Write};
 
-use crate::{Repository, stats::StatsCollector};
+use crate::{Repository, revision, stats::StatsCollector, utils};
 
 pub struct UtilsCommand;
 
@@ -125,7 +125,7 @@ impl UtilsCommand {
         // 简单验证：检查是否能成功解析为快照元数据
         match serde_json::from_str::<crate::SnapshotMetadata>(&content) {
             Ok(snapshot) => {
-                println!("Snapshot {} integrity: OK", snapshot.id);
+                println!("Snapshot {} integrity: OK", utils::short_id(&snapshot.id));
                 Ok(true)
             }
             Err(e) => {
@@ -168,100 +168,48 @@ impl UtilsCommand {
         Ok(corrupted_objects)
     }
 
-    /// 删除特定的快照
-    pub fn remove_snapshots(target: String, dry_run: bool) -> Result<()> {
+    /// 删除特定的快照，并在同一次运行中回收它们独占的对象
+    pub fn remove_snapshots(target: String, dry_run: bool, force: bool) -> Result<()> {
         let current_dir = env::current_dir()?;
         let root = Repository::find_root(&current_dir)?;
-        let repo = Repository::new(root)?;
+        let mut repo = Repository::new(root)?;
 
         if dry_run {
             println!("Running in dry-run mode (no changes will be made)");
         }
 
-        // 解析目标参数 (可能是 number, ID, number-number, ID-ID 格式)
-        if target.contains('-') {
-            // 范围删除
-            Self::remove_snapshot_range(&repo, target, dry_run)?;
-        } else {
-            // 单个删除
-            Self::remove_single_snapshot(&repo, target, dry_run)?;
-        }
-
-        Ok(())
-    }
-
-    /// 删除单个快照
-    fn remove_single_snapshot(repo: &Repository, target: String, dry_run: bool) -> Result<()> {
-        let snapshot_id = if target.chars().all(|c| c.is_ascii_digit()) {
-            // 如果是纯数字，按序号查找
-            let number: usize = target.parse()?;
-            repo.snapshot_manager.find_snapshot_by_number(number)?
+        // 解析目标参数（修订表达式或范围）
+        let is_range = revision::is_range(&repo, &target)?;
+        let mut snapshots_to_remove = if is_range {
+            revision::resolve_range(&repo, &target)?
         } else {
-            // 否则当作ID处理
-            target
+            vec![revision::resolve(&repo, &target)?]
         };
 
-        if dry_run {
-            println!("Would remove snapshot: {}", snapshot_id);
-        } else {
-            repo.snapshot_manager.delete_snapshot(&snapshot_id)?;
-            println!("Removed snapshot: {}", snapshot_id);
-        }
-
-        Ok(())
-    }
-
-    /// 删除范围内的快照
-    fn remove_snapshot_range(repo: &Repository, range: String, dry_run: bool) -> Result<()> {
-        let parts: Vec<&str> = range.split('-').collect();
-        if parts.len() != 2 {
-            return Err(anyhow!("Invalid range format. Use number-number or ID-ID"));
-        }
-
-        let start_str = parts[0];
-        let end_str = parts[1];
-
-        // 获取所有历史记录
-        let history = repo.snapshot_manager.list_history()?;
-
-        let snapshots_to_remove = if start_str.chars().all(|c| c.is_ascii_digit())
-            && end_str.chars().all(|c| c.is_ascii_digit())
-        {
-            // 数字范围
-            let start_num: usize = start_str.parse()?;
-            let end_num: usize = end_str.parse()?;
-
-            if start_num > end_num {
-                return Err(anyhow!("Invalid range: start number must be <= end number"));
+        // HEAD、有标签指向的快照和时间线上最新的快照受保护：
+        // 单个删除时报错，范围删除时跳过，--force 时一并删除
+        if !force {
+            let protected = repo.protected_snapshots(true)?;
+            if !is_range && let Some(reasons) = protected.get(&snapshots_to_remove[0]) {
+                return Err(anyhow::anyhow!(
+                    "Snapshot {} is protected ({}); use --force to remove it anyway",
+                    utils::short_id(&snapshots_to_remove[0]),
+                    reasons.join(", ")
+                ));
             }
 
-            history
-                .iter()
-                .filter(|entry| entry.number >= start_num && entry.number <= end_num)
-                .map(|entry| entry.snapshot_id.clone())
-                .collect::<Vec<_>>()
-        } else {
-            // ID范围 - 在历史记录中找到这两个ID之间的所有快照
-            let start_idx = history.iter().position(|e| e.snapshot_id == start_str);
-            let end_idx = history.iter().position(|e| e.snapshot_id == end_str);
-
-            match (start_idx, end_idx) {
-                (Some(start), Some(end)) => {
-                    let (from, to) = if start <= end {
-                        (start, end)
-                    } else {
-                        (end, start)
-                    };
-                    history[from..=to]
-                        .iter()
-                        .map(|entry| entry.snapshot_id.clone())
-                        .collect()
-                }
-                _ => {
-                    return Err(anyhow!("One or both snapshot IDs not found"));
+            snapshots_to_remove.retain(|snapshot_id| match protected.get(snapshot_id) {
+                Some(reasons) => {
+                    println!(
+                        "Skipping protected snapshot {} ({})",
+                        utils::short_id(snapshot_id),
+                        reasons.join(", ")
+                    );
+                    false
                 }
-            }
-        };
+                None => true,
+            });
+        }
 
         if snapshots_to_remove.is_empty() {
             println!("No snapshots found in the specified range");
@@ -269,16 +217,7 @@ impl UtilsCommand {
         }
 
         println!("Found {} snapshots to remove", snapshots_to_remove.len());
-
-        for snapshot_id in &snapshots_to_remove {
-            if dry_run {
-                println!("Would remove snapshot: {}", snapshot_id);
-            } else {
-                repo.snapshot_manager.delete_snapshot(snapshot_id)?;
-                println!("Removed snapshot: {}", snapshot_id);
-            }
-        }
-
-        Ok(())
+        repo.drop_snapshots(&snapshots_to_remove, dry_run)?;
+        repo.collect_garbage(dry_run, false, &snapshots_to_remove)
     }
 }
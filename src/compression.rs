@@ -0,0 +1,159 @@
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::io::{BufReader, Read, Write};
+
+/// 对象负载使用的压缩算法，对应对象头中的压缩算法字节
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Codec {
+    /// 不压缩，适用于已经压缩过的格式（jpg、zip、mp4 等）
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Codec {
+    pub fn tag(self) -> u8 {
+        match self {
+            Codec::None => 0,
+            Codec::Gzip => 1,
+            Codec::Zstd => 2,
+        }
+    }
+
+    pub fn from_tag(tag: u8) -> Result<Self> {
+        match tag {
+            0 => Ok(Codec::None),
+            1 => Ok(Codec::Gzip),
+            2 => Ok(Codec::Zstd),
+            _ => Err(anyhow!("Unknown compression codec: {}", tag)),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Codec::None => "none",
+            Codec::Gzip => "gzip",
+            Codec::Zstd => "zstd",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "none" | "store" => Ok(Codec::None),
+            "gzip" | "gz" => Ok(Codec::Gzip),
+            "zstd" | "zst" => Ok(Codec::Zstd),
+            _ => Err(anyhow!(
+                "Unknown compression codec '{}' (expected zstd, gzip or none)",
+                name
+            )),
+        }
+    }
+
+    pub fn default_level(self) -> i32 {
+        match self {
+            Codec::None => 0,
+            Codec::Gzip => 6,
+            Codec::Zstd => 3,
+        }
+    }
+
+    /// 最高压缩级别，gc --aggressive 重新压缩时使用
+    pub fn max_level(self) -> i32 {
+        match self {
+            Codec::None => 0,
+            Codec::Gzip => 9,
+            Codec::Zstd => 19,
+        }
+    }
+
+    pub fn compress(self, data: &[u8], level: i32) -> Result<Vec<u8>> {
+        let level = level.clamp(1, self.max_level().max(1));
+
+        match self {
+            Codec::None => Ok(data.to_vec()),
+            Codec::Gzip => {
+                let mut encoder = flate2::write::GzEncoder::new(
+                    Vec::new(),
+                    flate2::Compression::new(level as u32),
+                );
+                encoder.write_all(data)?;
+                Ok(encoder.finish()?)
+            }
+            Codec::Zstd => Ok(zstd::stream::encode_all(data, level)?),
+        }
+    }
+
+    pub fn decompress(self, data: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Codec::None => Ok(data.to_vec()),
+            Codec::Gzip => {
+                let mut decoder = flate2::read::GzDecoder::new(data);
+                let mut content = Vec::new();
+                decoder.read_to_end(&mut content)?;
+                Ok(content)
+            }
+            Codec::Zstd => Ok(zstd::stream::decode_all(data)?),
+        }
+    }
+    /// 创建流式压缩写入器，数据写入后需要调用 `finish` 完成压缩
+    pub fn encoder<W: Write>(self, writer: W, level: i32) -> Result<Encoder<W>> {
+        let level = level.clamp(1, self.max_level().max(1));
+
+        Ok(match self {
+            Codec::None => Encoder::None(writer),
+            Codec::Gzip => Encoder::Gzip(flate2::write::GzEncoder::new(
+                writer,
+                flate2::Compression::new(level as u32),
+            )),
+            Codec::Zstd => Encoder::Zstd(zstd::stream::write::Encoder::new(writer, level)?),
+        })
+    }
+
+    /// 创建流式解压读取器
+    pub fn decoder<'a, R: Read + 'a>(self, reader: R) -> Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            Codec::None => Box::new(reader),
+            Codec::Gzip => Box::new(flate2::read::GzDecoder::new(reader)),
+            Codec::Zstd => Box::new(zstd::stream::read::Decoder::with_buffer(BufReader::new(
+                reader,
+            ))?),
+        })
+    }
+}
+
+/// 流式压缩写入器
+pub enum Encoder<W: Write> {
+    None(W),
+    Gzip(flate2::write::GzEncoder<W>),
+    Zstd(zstd::stream::write::Encoder<'static, W>),
+}
+
+impl<W: Write> Encoder<W> {
+    /// 写出剩余的压缩数据，返回底层写入器
+    pub fn finish(self) -> Result<W> {
+        Ok(match self {
+            Encoder::None(writer) => writer,
+            Encoder::Gzip(encoder) => encoder.finish()?,
+            Encoder::Zstd(encoder) => encoder.finish()?,
+        })
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Encoder::None(writer) => writer.write(buf),
+            Encoder::Gzip(encoder) => encoder.write(buf),
+            Encoder::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Encoder::None(writer) => writer.flush(),
+            Encoder::Gzip(encoder) => encoder.flush(),
+            Encoder::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
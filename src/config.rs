@@ -4,6 +4,8 @@ use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 
+use crate::compression::Codec;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     #[serde(default = "default_output_format")]
@@ -31,11 +33,156 @@ pub struct Config {
     #[serde(default)]
     pub gc_auto_enabled: bool,
 
+    // GC 时自动合并在这么多分钟内连续创建的小快照，未设置时不合并
+    #[serde(default)]
+    pub gc_squash_within_minutes: Option<u64>,
+
+    // 变更文件数不超过此值的快照视为小快照
+    #[serde(default = "default_gc_squash_max_changes")]
+    pub gc_squash_max_changes: usize,
+
+    // 删除的快照在回收站中保留的天数，期间可以用 undelete 恢复；0 表示直接删除
+    #[serde(default = "default_trash_grace_days")]
+    pub trash_grace_days: u32,
+
+    // 快照保留策略，设置后取代 gc_keep_days 和 gc_keep_snapshots
+    #[serde(default)]
+    pub retention: RetentionConfig,
+
+    // 分块存储设置：大文件按内容切块，修改后只需存储变化的块
+    #[serde(default)]
+    pub chunking_enabled: bool,
+
+    #[serde(default = "default_chunk_threshold_mb")]
+    pub chunk_threshold_mb: u64,
+
+    // 差量存储设置：文件修改后只存储相对于上一版本的差量
+    #[serde(default)]
+    pub delta_enabled: bool,
+
+    #[serde(default = "default_delta_max_chain")]
+    pub delta_max_chain: usize,
+
+    // 对象压缩设置
+    #[serde(default)]
+    pub compression: CompressionConfig,
+
+    // 并行任务使用的线程数，0 表示使用所有 CPU 核心
+    #[serde(default)]
+    pub jobs: usize,
+
     // 时区显示设置
     #[serde(default = "default_use_local_timezone")]
     pub use_local_timezone: bool,
 }
 
+/// `[compression]` 配置段
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompressionConfig {
+    #[serde(default = "default_codec")]
+    pub codec: Codec,
+
+    /// 压缩级别，未设置时使用算法的默认级别
+    #[serde(default)]
+    pub level: Option<i32>,
+
+    /// 这些扩展名的文件已经是压缩格式，直接存储不再压缩
+    #[serde(default = "default_skip_extensions")]
+    pub skip_extensions: Vec<String>,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            codec: default_codec(),
+            level: None,
+            skip_extensions: default_skip_extensions(),
+        }
+    }
+}
+
+impl CompressionConfig {
+    pub fn effective_level(&self) -> i32 {
+        self.level.unwrap_or_else(|| self.codec.default_level())
+    }
+}
+
+/// `[retention]` 配置段：按时间段保留快照（类似 restic/borg 的 forget 策略）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionConfig {
+    /// 保留最新的 N 个快照
+    #[serde(default)]
+    pub keep_last: Option<usize>,
+
+    /// 最近 N 个有快照的小时/天/周/月/年，各保留其中最新的一个快照
+    #[serde(default)]
+    pub keep_hourly: Option<usize>,
+
+    #[serde(default)]
+    pub keep_daily: Option<usize>,
+
+    #[serde(default)]
+    pub keep_weekly: Option<usize>,
+
+    #[serde(default)]
+    pub keep_monthly: Option<usize>,
+
+    #[serde(default)]
+    pub keep_yearly: Option<usize>,
+
+    /// 总是保留有标签指向的快照
+    #[serde(default = "default_keep_tagged")]
+    pub keep_tagged: bool,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            keep_last: None,
+            keep_hourly: None,
+            keep_daily: None,
+            keep_weekly: None,
+            keep_monthly: None,
+            keep_yearly: None,
+            keep_tagged: default_keep_tagged(),
+        }
+    }
+}
+
+impl RetentionConfig {
+    /// 是否设置了任何保留规则
+    pub fn is_configured(&self) -> bool {
+        [
+            self.keep_last,
+            self.keep_hourly,
+            self.keep_daily,
+            self.keep_weekly,
+            self.keep_monthly,
+            self.keep_yearly,
+        ]
+        .iter()
+        .any(Option::is_some)
+    }
+}
+
+fn default_keep_tagged() -> bool {
+    true
+}
+
+fn default_codec() -> Codec {
+    Codec::Zstd
+}
+
+fn default_skip_extensions() -> Vec<String> {
+    [
+        "jpg", "jpeg", "png", "gif", "webp", "heic", "mp3", "mp4", "m4a", "mkv", "mov", "avi",
+        "webm", "zip", "gz", "tgz", "bz2", "xz", "zst", "7z", "rar", "jar", "docx", "xlsx", "pptx",
+    ]
+    .iter()
+    .map(|ext| ext.to_string())
+    .collect()
+}
+
 fn default_output_format() -> String {
     "table".to_string()
 }
@@ -48,6 +195,22 @@ fn default_max_file_size() -> u64 {
     100 // MB
 }
 
+fn default_gc_squash_max_changes() -> usize {
+    2
+}
+
+fn default_trash_grace_days() -> u32 {
+    14
+}
+
+fn default_chunk_threshold_mb() -> u64 {
+    8 // MB
+}
+
+fn default_delta_max_chain() -> usize {
+    10
+}
+
 fn default_use_local_timezone() -> bool {
     true // 默认使用本地时区
 }
@@ -63,6 +226,16 @@ impl Default for Config {
             gc_keep_days: Some(30),
             gc_keep_snapshots: Some(50),
             gc_auto_enabled: false,
+            gc_squash_within_minutes: None,
+            gc_squash_max_changes: default_gc_squash_max_changes(),
+            trash_grace_days: default_trash_grace_days(),
+            retention: RetentionConfig::default(),
+            chunking_enabled: false,
+            chunk_threshold_mb: default_chunk_threshold_mb(),
+            delta_enabled: false,
+            delta_max_chain: default_delta_max_chain(),
+            compression: CompressionConfig::default(),
+            jobs: 0,
             use_local_timezone: default_use_local_timezone(),
         }
     }
@@ -95,6 +268,24 @@ impl Config {
             "gc_keep_days" => self.gc_keep_days.map(|v| v.to_string()),
             "gc_keep_snapshots" => self.gc_keep_snapshots.map(|v| v.to_string()),
             "gc_auto_enabled" => Some(self.gc_auto_enabled.to_string()),
+            "gc_squash_within_minutes" => self.gc_squash_within_minutes.map(|v| v.to_string()),
+            "gc_squash_max_changes" => Some(self.gc_squash_max_changes.to_string()),
+            "trash_grace_days" => Some(self.trash_grace_days.to_string()),
+            "retention.keep_last" => self.retention.keep_last.map(|v| v.to_string()),
+            "retention.keep_hourly" => self.retention.keep_hourly.map(|v| v.to_string()),
+            "retention.keep_daily" => self.retention.keep_daily.map(|v| v.to_string()),
+            "retention.keep_weekly" => self.retention.keep_weekly.map(|v| v.to_string()),
+            "retention.keep_monthly" => self.retention.keep_monthly.map(|v| v.to_string()),
+            "retention.keep_yearly" => self.retention.keep_yearly.map(|v| v.to_string()),
+            "retention.keep_tagged" => Some(self.retention.keep_tagged.to_string()),
+            "chunking_enabled" => Some(self.chunking_enabled.to_string()),
+            "chunk_threshold_mb" => Some(self.chunk_threshold_mb.to_string()),
+            "delta_enabled" => Some(self.delta_enabled.to_string()),
+            "delta_max_chain" => Some(self.delta_max_chain.to_string()),
+            "compression.codec" => Some(self.compression.codec.name().to_string()),
+            "compression.level" => Some(self.compression.effective_level().to_string()),
+            "compression.skip_extensions" => Some(self.compression.skip_extensions.join(",")),
+            "jobs" => Some(self.jobs.to_string()),
             "use_local_timezone" => Some(self.use_local_timezone.to_string()),
             _ => self.tags.get(key).cloned(),
         }
@@ -109,6 +300,32 @@ impl Config {
             "gc_keep_days" => self.gc_keep_days = Some(value.parse()?),
             "gc_keep_snapshots" => self.gc_keep_snapshots = Some(value.parse()?),
             "gc_auto_enabled" => self.gc_auto_enabled = value.parse()?,
+            "gc_squash_within_minutes" => {
+                self.gc_squash_within_minutes = parse_optional(&value)?.map(|v| v as u64)
+            }
+            "gc_squash_max_changes" => self.gc_squash_max_changes = value.parse()?,
+            "trash_grace_days" => self.trash_grace_days = value.parse()?,
+            "retention.keep_last" => self.retention.keep_last = parse_optional(&value)?,
+            "retention.keep_hourly" => self.retention.keep_hourly = parse_optional(&value)?,
+            "retention.keep_daily" => self.retention.keep_daily = parse_optional(&value)?,
+            "retention.keep_weekly" => self.retention.keep_weekly = parse_optional(&value)?,
+            "retention.keep_monthly" => self.retention.keep_monthly = parse_optional(&value)?,
+            "retention.keep_yearly" => self.retention.keep_yearly = parse_optional(&value)?,
+            "retention.keep_tagged" => self.retention.keep_tagged = value.parse()?,
+            "chunking_enabled" => self.chunking_enabled = value.parse()?,
+            "chunk_threshold_mb" => self.chunk_threshold_mb = value.parse()?,
+            "delta_enabled" => self.delta_enabled = value.parse()?,
+            "delta_max_chain" => self.delta_max_chain = value.parse()?,
+            "compression.codec" => self.compression.codec = Codec::parse(&value)?,
+            "compression.level" => self.compression.level = Some(value.parse()?),
+            "compression.skip_extensions" => {
+                self.compression.skip_extensions = value
+                    .split(',')
+                    .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+                    .filter(|ext| !ext.is_empty())
+                    .collect()
+            }
+            "jobs" => self.jobs = value.parse()?,
             "use_local_timezone" => self.use_local_timezone = value.parse()?,
             _ => {
                 self.tags.insert(key.to_string(), value);
@@ -117,3 +334,11 @@ impl Config {
         Ok(())
     }
 }
+
+/// 解析可选的数值设置，`none` 或空值表示取消该设置
+fn parse_optional(value: &str) -> Result<Option<usize>> {
+    match value.trim() {
+        "" | "none" => Ok(None),
+        value => Ok(Some(value.parse()?)),
+    }
+}
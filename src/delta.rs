@@ -0,0 +1,254 @@
+use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+
+/// 二进制差量编码
+///
+/// 格式：基础内容长度、目标内容长度（均为 varint），随后是一系列操作：
+/// - `COPY`：从基础内容的 offset 处复制 length 个字节
+/// - `INSERT`：直接插入随后的 length 个字节
+const OP_COPY: u8 = 1;
+const OP_INSERT: u8 = 2;
+
+/// 基础内容按固定大小分块建立索引
+const BLOCK_SIZE: usize = 16;
+
+/// 生成从 base 到 target 的差量
+pub fn encode(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_varint(&mut out, base.len() as u64);
+    write_varint(&mut out, target.len() as u64);
+
+    // 索引基础内容中每个对齐块第一次出现的位置
+    let mut blocks: HashMap<&[u8], usize> = HashMap::new();
+    let mut pos = 0;
+    while pos + BLOCK_SIZE <= base.len() {
+        blocks.entry(&base[pos..pos + BLOCK_SIZE]).or_insert(pos);
+        pos += BLOCK_SIZE;
+    }
+
+    let mut pending_start = 0;
+    let mut i = 0;
+
+    while i + BLOCK_SIZE <= target.len() {
+        let Some(&base_pos) = blocks.get(&target[i..i + BLOCK_SIZE]) else {
+            i += 1;
+            continue;
+        };
+
+        // 向前扩展匹配
+        let mut len = BLOCK_SIZE;
+        while base_pos + len < base.len()
+            && i + len < target.len()
+            && base[base_pos + len] == target[i + len]
+        {
+            len += 1;
+        }
+
+        // 向后扩展匹配，吞掉待插入数据的尾部
+        let mut back = 0;
+        while back < base_pos
+            && i - back > pending_start
+            && base[base_pos - back - 1] == target[i - back - 1]
+        {
+            back += 1;
+        }
+
+        let copy_start = i - back;
+        emit_insert(&mut out, &target[pending_start..copy_start]);
+        emit_copy(&mut out, (base_pos - back) as u64, (len + back) as u64);
+
+        i += len;
+        pending_start = i;
+    }
+
+    emit_insert(&mut out, &target[pending_start..]);
+    out
+}
+
+/// 将差量应用到 base 上还原目标内容
+pub fn apply(base: &[u8], delta: &[u8]) -> Result<Vec<u8>> {
+    let mut pos = 0;
+    let base_len = read_varint(delta, &mut pos)? as usize;
+    let target_len = read_varint(delta, &mut pos)? as usize;
+
+    if base_len != base.len() {
+        return Err(anyhow!(
+            "Delta base size mismatch: expected {}, got {}",
+            base_len,
+            base.len()
+        ));
+    }
+
+    let mut out = Vec::with_capacity(target_len);
+
+    while pos < delta.len() {
+        let op = delta[pos];
+        pos += 1;
+
+        match op {
+            OP_COPY => {
+                let offset = read_varint(delta, &mut pos)? as usize;
+                let len = read_varint(delta, &mut pos)? as usize;
+                let end = offset
+                    .checked_add(len)
+                    .filter(|end| *end <= base.len())
+                    .ok_or_else(|| anyhow!("Delta copy out of range"))?;
+                out.extend_from_slice(&base[offset..end]);
+            }
+            OP_INSERT => {
+                let len = read_varint(delta, &mut pos)? as usize;
+                let end = pos
+                    .checked_add(len)
+                    .filter(|end| *end <= delta.len())
+                    .ok_or_else(|| anyhow!("Delta insert out of range"))?;
+                out.extend_from_slice(&delta[pos..end]);
+                pos = end;
+            }
+            _ => return Err(anyhow!("Invalid delta operation: {}", op)),
+        }
+    }
+
+    if out.len() != target_len {
+        return Err(anyhow!(
+            "Delta result size mismatch: expected {}, got {}",
+            target_len,
+            out.len()
+        ));
+    }
+
+    Ok(out)
+}
+
+/// 读取差量描述的目标内容长度
+pub fn target_size(delta: &[u8]) -> Result<u64> {
+    let mut pos = 0;
+    read_varint(delta, &mut pos)?;
+    read_varint(delta, &mut pos)
+}
+
+fn emit_insert(out: &mut Vec<u8>, data: &[u8]) {
+    if !data.is_empty() {
+        out.push(OP_INSERT);
+        write_varint(out, data.len() as u64);
+        out.extend_from_slice(data);
+    }
+}
+
+fn emit_copy(out: &mut Vec<u8>, offset: u64, len: u64) {
+    out.push(OP_COPY);
+    write_varint(out, offset);
+    write_varint(out, len);
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            break;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> Result<u64> {
+    let mut value = 0u64;
+    let mut shift = 0;
+
+    loop {
+        let byte = *data
+            .get(*pos)
+            .ok_or_else(|| anyhow!("Truncated delta data"))?;
+        *pos += 1;
+
+        if shift >= 64 {
+            return Err(anyhow!("Invalid varint in delta data"));
+        }
+        value |= ((byte & 0x7f) as u64) << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::random_data;
+
+    fn round_trip(base: &[u8], target: &[u8]) -> Vec<u8> {
+        let delta = encode(base, target);
+        assert_eq!(apply(base, &delta).unwrap(), target);
+        assert_eq!(target_size(&delta).unwrap(), target.len() as u64);
+        delta
+    }
+
+    #[test]
+    fn round_trip_edge_cases() {
+        round_trip(b"", b"");
+        round_trip(b"", b"new content");
+        round_trip(b"old content", b"");
+        round_trip(b"short", b"short");
+        round_trip(b"abc", b"xyz");
+    }
+
+    #[test]
+    fn round_trip_edits_on_large_content() {
+        let base = random_data(64 * 1024, 1);
+
+        let mut inserted = base.clone();
+        inserted.splice(1000..1000, b"inserted".iter().copied());
+        let mut deleted = base.clone();
+        deleted.drain(30_000..31_000);
+        let mut replaced = base.clone();
+        replaced[50_000..50_100].copy_from_slice(&[0u8; 100]);
+        let mut appended = base.clone();
+        appended.extend_from_slice(&random_data(5000, 2));
+
+        for target in [inserted, deleted, replaced, appended] {
+            let delta = round_trip(&base, &target);
+            // 大部分内容通过复制操作引用基础内容
+            assert!(delta.len() < 6000, "delta is {} bytes", delta.len());
+        }
+    }
+
+    #[test]
+    fn unrelated_content_round_trips() {
+        round_trip(&random_data(10_000, 3), &random_data(8000, 4));
+    }
+
+    #[test]
+    fn wrong_base_is_rejected() {
+        let delta = encode(b"base content here", b"target content here");
+        assert!(apply(b"other base", &delta).is_err());
+    }
+
+    #[test]
+    fn corrupt_delta_is_rejected() {
+        let base = random_data(1000, 5);
+        let mut target = base.clone();
+        target.extend_from_slice(b"tail");
+        let delta = encode(&base, &target);
+
+        assert!(apply(&base, &delta[..delta.len() - 1]).is_err());
+        assert!(apply(&base, &[]).is_err());
+
+        let mut bad_op = delta.clone();
+        bad_op.push(0xff);
+        assert!(apply(&base, &bad_op).is_err());
+    }
+
+    #[test]
+    fn varint_round_trip() {
+        for value in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            let mut out = Vec::new();
+            write_varint(&mut out, value);
+            let mut pos = 0;
+            assert_eq!(read_varint(&out, &mut pos).unwrap(), value);
+            assert_eq!(pos, out.len());
+        }
+    }
+}
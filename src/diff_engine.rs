@@ -4,17 +4,57 @@ pub struct DiffEngine;
 pub struct DiffLine {
     pub line_type: DiffLineType,
     pub content: String,
-    pub line_number: Option<usize>,
+    /// 该行在旧文件中的行号（从1开始，新增行为 None）
+    pub old_line_number: Option<usize>,
+    /// 该行在新文件中的行号（从1开始，删除行为 None）
+    pub new_line_number: Option<usize>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum DiffLineType {
     Added,
     Removed,
-    Modified,
     Context,
 }
 
+/// 统一差异格式中的一个块（@@ -a,b +c,d @@）
+#[derive(Debug, Clone)]
+pub struct DiffHunk {
+    pub old_start: usize,
+    pub old_count: usize,
+    pub new_start: usize,
+    pub new_count: usize,
+    pub lines: Vec<DiffLine>,
+}
+
+impl DiffHunk {
+    pub fn header(&self) -> String {
+        format!(
+            "@@ -{},{} +{},{} @@",
+            self.old_start, self.old_count, self.new_start, self.new_count
+        )
+    }
+}
+
+/// 默认的上下文行数，与 diff -u 保持一致
+pub const DEFAULT_CONTEXT_LINES: usize = 3;
+
+/// 三方合并的结果
+#[derive(Debug, Clone)]
+pub struct MergeResult {
+    pub content: Vec<u8>,
+    /// 写入冲突标记的区域数
+    pub conflicts: usize,
+}
+
+/// 相对于基础版本的一处修改：将 base[start..end] 替换为 lines
+#[derive(Debug, Clone)]
+struct Change<'a> {
+    start: usize,
+    end: usize,
+    lines: Vec<&'a [u8]>,
+}
+
 impl DiffEngine {
     /// 生成两个文本内容之间的差异
     pub fn diff_text(old_content: &str, new_content: &str) -> Vec<DiffLine> {
@@ -24,91 +64,674 @@ impl DiffEngine {
         Self::myers_diff(&old_lines, &new_lines)
     }
 
-    /// 使用Myers差异算法生成差异
-    fn myers_diff(old_lines: &[&str], new_lines: &[&str]) -> Vec<DiffLine> {
+    /// 生成统一差异格式的块列表
+    pub fn unified_diff(old_content: &str, new_content: &str, context: usize) -> Vec<DiffHunk> {
+        let diff_lines = Self::diff_text(old_content, new_content);
+        Self::build_hunks(&diff_lines, context)
+    }
+
+    /// 按行三方合并：分别计算 base 到 ours、base 到 theirs 的差异，
+    /// 只有一方修改的区域直接采用该方的内容，双方修改重叠（或相邻）且结果不同时写入冲突标记。
+    /// 按字节处理，每行连同换行符原样写回，不改变行尾格式、末尾换行和文本编码
+    pub fn merge3(
+        base: &[u8],
+        ours: &[u8],
+        theirs: &[u8],
+        ours_label: &str,
+        theirs_label: &str,
+    ) -> MergeResult {
+        let base_lines = Self::split_lines(base);
+        let our_lines = Self::split_lines(ours);
+        let their_lines = Self::split_lines(theirs);
+        let our_changes = Self::changes(&base_lines, &our_lines);
+        let their_changes = Self::changes(&base_lines, &their_lines);
+
+        // 冲突标记沿用文件的行尾格式
+        let newline: &[u8] = if [&base_lines, &our_lines, &their_lines]
+            .iter()
+            .find_map(|lines| lines.first())
+            .is_some_and(|line| line.ends_with(b"\r\n"))
+        {
+            b"\r\n"
+        } else {
+            b"\n"
+        };
+
+        let mut content: Vec<u8> = Vec::with_capacity(ours.len().max(theirs.len()));
+        let mut conflicts = 0;
+        let (mut i, mut j, mut pos) = (0, 0, 0);
+
+        loop {
+            let start = match (our_changes.get(i), their_changes.get(j)) {
+                (Some(a), Some(b)) => a.start.min(b.start),
+                (Some(a), None) => a.start,
+                (None, Some(b)) => b.start,
+                (None, None) => break,
+            };
+
+            // 未修改的部分
+            base_lines[pos..start]
+                .iter()
+                .for_each(|line| content.extend_from_slice(line));
+
+            // 扩展区域，直到没有与之重叠或相邻的修改
+            let (first_ours, first_theirs) = (i, j);
+            let mut end = start;
+            loop {
+                let mut extended = false;
+                while let Some(change) = our_changes.get(i).filter(|c| c.start <= end) {
+                    end = end.max(change.end);
+                    i += 1;
+                    extended = true;
+                }
+                while let Some(change) = their_changes.get(j).filter(|c| c.start <= end) {
+                    end = end.max(change.end);
+                    j += 1;
+                    extended = true;
+                }
+                if !extended {
+                    break;
+                }
+            }
+
+            let ours_region =
+                Self::apply_changes(&base_lines, start, end, &our_changes[first_ours..i]);
+            let theirs_region =
+                Self::apply_changes(&base_lines, start, end, &their_changes[first_theirs..j]);
+
+            if first_theirs == j {
+                content.extend(ours_region);
+            } else if first_ours == i || ours_region == theirs_region {
+                content.extend(theirs_region);
+            } else {
+                conflicts += 1;
+                // 没有末尾换行的最后一行后补上换行，保证冲突标记独占一行
+                let push_region = |content: &mut Vec<u8>, region: Vec<u8>| {
+                    let terminated = region.is_empty() || region.ends_with(b"\n");
+                    content.extend(region);
+                    if !terminated {
+                        content.extend_from_slice(newline);
+                    }
+                };
+                content.extend_from_slice(format!("<<<<<<< {}", ours_label).as_bytes());
+                content.extend_from_slice(newline);
+                push_region(&mut content, ours_region);
+                content.extend_from_slice(b"=======");
+                content.extend_from_slice(newline);
+                push_region(&mut content, theirs_region);
+                content.extend_from_slice(format!(">>>>>>> {}", theirs_label).as_bytes());
+                content.extend_from_slice(newline);
+            }
+
+            pos = end;
+        }
+
+        base_lines[pos..]
+            .iter()
+            .for_each(|line| content.extend_from_slice(line));
+
+        MergeResult { content, conflicts }
+    }
+
+    /// 按换行符切分，每行保留自身的行尾（最后一行可能没有）
+    fn split_lines(content: &[u8]) -> Vec<&[u8]> {
+        content.split_inclusive(|&byte| byte == b'\n').collect()
+    }
+
+    /// 计算 base 到 other 的修改列表（连续的删除和新增行为一处修改）
+    fn changes<'a>(base: &[&[u8]], other: &[&'a [u8]]) -> Vec<Change<'a>> {
+        let mut changes: Vec<Change> = Vec::new();
+        let (mut base_pos, mut other_pos) = (0, 0);
+        let mut in_change = false;
+
+        for op in Self::edit_script(base, other) {
+            if op == EditOp::Equal {
+                base_pos += 1;
+                other_pos += 1;
+                in_change = false;
+                continue;
+            }
+
+            if !in_change {
+                changes.push(Change {
+                    start: base_pos,
+                    end: base_pos,
+                    lines: Vec::new(),
+                });
+                in_change = true;
+            }
+
+            let change = changes.last_mut().unwrap();
+            if op == EditOp::Delete {
+                base_pos += 1;
+                change.end = base_pos;
+            } else {
+                change.lines.push(other[other_pos]);
+                other_pos += 1;
+            }
+        }
+
+        changes
+    }
+
+    /// 将一组修改应用到 base[start..end] 上
+    fn apply_changes(base: &[&[u8]], start: usize, end: usize, changes: &[Change]) -> Vec<u8> {
         let mut result = Vec::new();
+        let mut pos = start;
+        for change in changes {
+            base[pos..change.start]
+                .iter()
+                .chain(&change.lines)
+                .for_each(|line| result.extend_from_slice(line));
+            pos = change.end;
+        }
+        base[pos..end]
+            .iter()
+            .for_each(|line| result.extend_from_slice(line));
+        result
+    }
+
+    /// 简单的二进制内容检测：开头部分包含 NUL 字节。
+    /// 非 UTF-8 编码的文本（GBK、Latin-1 等）仍按文本处理
+    pub fn is_binary(content: &[u8]) -> bool {
+        let sample = &content[..content.len().min(8000)];
+        sample.contains(&0)
+    }
+
+    /// 使用 Myers 差异算法生成差异行
+    fn myers_diff(old_lines: &[&str], new_lines: &[&str]) -> Vec<DiffLine> {
+        let ops = Self::edit_script(old_lines, new_lines);
+
+        // 根据编辑操作生成带行号的差异行
+        let mut result = Vec::with_capacity(ops.len());
         let mut old_idx = 0;
         let mut new_idx = 0;
+        for op in ops {
+            match op {
+                EditOp::Equal => {
+                    result.push(DiffLine {
+                        line_type: DiffLineType::Context,
+                        content: old_lines[old_idx].to_string(),
+                        old_line_number: Some(old_idx + 1),
+                        new_line_number: Some(new_idx + 1),
+                    });
+                    old_idx += 1;
+                    new_idx += 1;
+                }
+                EditOp::Delete => {
+                    result.push(DiffLine {
+                        line_type: DiffLineType::Removed,
+                        content: old_lines[old_idx].to_string(),
+                        old_line_number: Some(old_idx + 1),
+                        new_line_number: None,
+                    });
+                    old_idx += 1;
+                }
+                EditOp::Insert => {
+                    result.push(DiffLine {
+                        line_type: DiffLineType::Added,
+                        content: new_lines[new_idx].to_string(),
+                        old_line_number: None,
+                        new_line_number: Some(new_idx + 1),
+                    });
+                    new_idx += 1;
+                }
+            }
+        }
 
-        // 简化的差异算法 - 实际项目中可以使用更复杂的算法
-        while old_idx < old_lines.len() || new_idx < new_lines.len() {
-            if old_idx >= old_lines.len() {
-                // 只剩新行
-                result.push(DiffLine {
-                    line_type: DiffLineType::Added,
-                    content: format!("+{}", new_lines[new_idx]),
-                    line_number: Some(new_idx + 1),
-                });
-                new_idx += 1;
-            } else if new_idx >= new_lines.len() {
-                // 只剩旧行
-                result.push(DiffLine {
-                    line_type: DiffLineType::Removed,
-                    content: format!("-{}", old_lines[old_idx]),
-                    line_number: Some(old_idx + 1),
-                });
-                old_idx += 1;
-            } else if old_lines[old_idx] == new_lines[new_idx] {
-                // 相同行
-                result.push(DiffLine {
-                    line_type: DiffLineType::Context,
-                    content: format!(" {}", old_lines[old_idx]),
-                    line_number: Some(old_idx + 1),
-                });
-                old_idx += 1;
-                new_idx += 1;
+        result
+    }
+
+    /// 最短编辑脚本：先去掉公共前缀和后缀，缩小需要搜索的范围
+    fn edit_script<T: PartialEq>(old_lines: &[T], new_lines: &[T]) -> Vec<EditOp> {
+        let prefix = old_lines
+            .iter()
+            .zip(new_lines.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let suffix = old_lines[prefix..]
+            .iter()
+            .rev()
+            .zip(new_lines[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        let a = &old_lines[prefix..old_lines.len() - suffix];
+        let b = &new_lines[prefix..new_lines.len() - suffix];
+
+        let mut ops = Vec::with_capacity(old_lines.len().max(new_lines.len()));
+        ops.extend(std::iter::repeat_n(EditOp::Equal, prefix));
+        ops.extend(Self::shortest_edit(a, b));
+        ops.extend(std::iter::repeat_n(EditOp::Equal, suffix));
+        ops
+    }
+
+    /// Myers O(ND) 算法：在编辑图中搜索最短路径，然后回溯出编辑操作
+    fn shortest_edit<T: PartialEq>(a: &[T], b: &[T]) -> Vec<EditOp> {
+        let n = a.len() as isize;
+        let m = b.len() as isize;
+        let max = n + m;
+
+        // 一侧为空时编辑脚本是确定的，不必搜索（trace 的大小与编辑距离的平方成正比）
+        if n == 0 || m == 0 {
+            let op = if n == 0 {
+                EditOp::Insert
             } else {
-                // 不同行 - 简单处理为删除+添加
-                result.push(DiffLine {
-                    line_type: DiffLineType::Removed,
-                    content: format!("-{}", old_lines[old_idx]),
-                    line_number: Some(old_idx + 1),
-                });
-                result.push(DiffLine {
-                    line_type: DiffLineType::Added,
-                    content: format!("+{}", new_lines[new_idx]),
-                    line_number: Some(new_idx + 1),
-                });
-                old_idx += 1;
-                new_idx += 1;
+                EditOp::Delete
+            };
+            return vec![op; max as usize];
+        }
+
+        // v[k] 记录对角线 k 上能到达的最远 x；offset 保证索引非负
+        let offset = max + 1;
+        let mut v = vec![0isize; (2 * offset + 1) as usize];
+        // trace[d] 保存第 d 轮开始前 v 在 [-(d+1), d+1] 范围内的快照
+        let mut trace: Vec<Vec<isize>> = Vec::new();
+
+        'search: for d in 0..=max {
+            let lo = (offset - d - 1) as usize;
+            let hi = (offset + d + 1) as usize;
+            trace.push(v[lo..=hi].to_vec());
+
+            let mut k = -d;
+            while k <= d {
+                let idx = (offset + k) as usize;
+                let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+                    v[idx + 1]
+                } else {
+                    v[idx - 1] + 1
+                };
+                let mut y = x - k;
+
+                while x < n && y < m && a[x as usize] == b[y as usize] {
+                    x += 1;
+                    y += 1;
+                }
+
+                v[idx] = x;
+
+                if x >= n && y >= m {
+                    break 'search;
+                }
+                k += 2;
             }
         }
 
-        result
+        // 回溯
+        let mut ops = Vec::new();
+        let mut x = n;
+        let mut y = m;
+
+        for (d, snapshot) in trace.iter().enumerate().rev() {
+            let d = d as isize;
+            let get = |k: isize| snapshot[(k + d + 1) as usize];
+            let k = x - y;
+
+            let prev_k = if k == -d || (k != d && get(k - 1) < get(k + 1)) {
+                k + 1
+            } else {
+                k - 1
+            };
+            let prev_x = get(prev_k);
+            let prev_y = prev_x - prev_k;
+
+            while x > prev_x && y > prev_y {
+                ops.push(EditOp::Equal);
+                x -= 1;
+                y -= 1;
+            }
+
+            if d > 0 {
+                if x == prev_x {
+                    ops.push(EditOp::Insert);
+                } else {
+                    ops.push(EditOp::Delete);
+                }
+            }
+
+            x = prev_x;
+            y = prev_y;
+        }
+
+        ops.reverse();
+        ops
     }
 
-    /// 打印差异到控制台
-    pub fn print_diff(diff_lines: &[DiffLine], file_path: &str) {
-        use colored::*;
+    /// 将差异行按上下文行数分组为统一差异块
+    pub fn build_hunks(diff_lines: &[DiffLine], context: usize) -> Vec<DiffHunk> {
+        let mut hunks = Vec::new();
+
+        // 找出所有变更行的位置
+        let changes: Vec<usize> = diff_lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| line.line_type != DiffLineType::Context)
+            .map(|(i, _)| i)
+            .collect();
 
-        println!("{}", format!("--- {}", file_path).bold());
-        println!("{}", format!("+++ {}", file_path).bold());
+        if changes.is_empty() {
+            return hunks;
+        }
 
+        // 合并相距不超过 2 * context 的变更区域
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        for &i in &changes {
+            let start = i.saturating_sub(context);
+            let end = (i + context).min(diff_lines.len() - 1);
+            match ranges.last_mut() {
+                Some(last) if start <= last.1 + 1 => last.1 = end,
+                _ => ranges.push((start, end)),
+            }
+        }
+
+        // 计算每个位置之前已经出现的旧/新行数，用于生成块头
+        let mut old_before = Vec::with_capacity(diff_lines.len());
+        let mut new_before = Vec::with_capacity(diff_lines.len());
+        let (mut old_seen, mut new_seen) = (0, 0);
         for line in diff_lines {
-            match line.line_type {
-                DiffLineType::Added => println!("{}", line.content.green()),
-                DiffLineType::Removed => println!("{}", line.content.red()),
-                DiffLineType::Modified => println!("{}", line.content.yellow()),
-                DiffLineType::Context => println!("{}", line.content.dimmed()),
+            old_before.push(old_seen);
+            new_before.push(new_seen);
+            if line.line_type != DiffLineType::Added {
+                old_seen += 1;
+            }
+            if line.line_type != DiffLineType::Removed {
+                new_seen += 1;
+            }
+        }
+
+        for (start, end) in ranges {
+            let lines = diff_lines[start..=end].to_vec();
+            let old_count = lines
+                .iter()
+                .filter(|l| l.line_type != DiffLineType::Added)
+                .count();
+            let new_count = lines
+                .iter()
+                .filter(|l| l.line_type != DiffLineType::Removed)
+                .count();
+
+            // 与 diff -u 一致：空范围的起始行号指向其前一行
+            let old_start = old_before[start] + usize::from(old_count > 0);
+            let new_start = new_before[start] + usize::from(new_count > 0);
+
+            hunks.push(DiffHunk {
+                old_start,
+                old_count,
+                new_start,
+                new_count,
+                lines,
+            });
+        }
+
+        hunks
+    }
+
+    /// 打印统一差异到控制台
+    pub fn print_diff(hunks: &[DiffHunk], old_label: &str, new_label: &str) {
+        use colored::*;
+
+        println!("{}", format!("--- {}", old_label).bold());
+        println!("{}", format!("+++ {}", new_label).bold());
+
+        for hunk in hunks {
+            println!("{}", hunk.header().cyan());
+            for line in &hunk.lines {
+                match line.line_type {
+                    DiffLineType::Added => println!("{}", format!("+{}", line.content).green()),
+                    DiffLineType::Removed => println!("{}", format!("-{}", line.content).red()),
+                    DiffLineType::Context => println!(" {}", line.content),
+                }
             }
         }
     }
 
-    /// 生成统计信息
-    pub fn get_diff_stats(diff_lines: &[DiffLine]) -> (usize, usize, usize) {
+    /// 生成统计信息（新增行数，删除行数）
+    pub fn get_diff_stats(diff_lines: &[DiffLine]) -> (usize, usize) {
         let mut added = 0;
         let mut removed = 0;
-        let mut modified = 0;
 
         for line in diff_lines {
             match line.line_type {
                 DiffLineType::Added => added += 1,
                 DiffLineType::Removed => removed += 1,
-                DiffLineType::Modified => modified += 1,
                 DiffLineType::Context => {}
             }
         }
 
-        (added, removed, modified)
+        (added, removed)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EditOp {
+    Equal,
+    Delete,
+    Insert,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(hunks: &[DiffHunk]) -> Vec<String> {
+        let mut out = Vec::new();
+        for hunk in hunks {
+            out.push(hunk.header());
+            for line in &hunk.lines {
+                let prefix = match line.line_type {
+                    DiffLineType::Added => '+',
+                    DiffLineType::Removed => '-',
+                    DiffLineType::Context => ' ',
+                };
+                out.push(format!("{}{}", prefix, line.content));
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn identical_content_has_no_hunks() {
+        assert!(DiffEngine::unified_diff("a\nb\n", "a\nb\n", 3).is_empty());
+        assert!(DiffEngine::unified_diff("", "", 3).is_empty());
+    }
+
+    #[test]
+    fn insert_into_empty_file() {
+        let hunks = DiffEngine::unified_diff("", "a\nb\n", 3);
+        assert_eq!(render(&hunks), ["@@ -0,0 +1,2 @@", "+a", "+b"]);
+    }
+
+    #[test]
+    fn delete_whole_file() {
+        let hunks = DiffEngine::unified_diff("a\nb\n", "", 3);
+        assert_eq!(render(&hunks), ["@@ -1,2 +0,0 @@", "-a", "-b"]);
+    }
+
+    #[test]
+    fn insert_at_start_and_end() {
+        let hunks = DiffEngine::unified_diff("b\n", "a\nb\nc\n", 3);
+        assert_eq!(render(&hunks), ["@@ -1,1 +1,3 @@", "+a", " b", "+c"]);
+    }
+
+    #[test]
+    fn replace_line_in_middle() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n";
+        let new = "1\n2\n3\n4\nfive\n6\n7\n8\n9\n";
+        let hunks = DiffEngine::unified_diff(old, new, 3);
+        assert_eq!(
+            render(&hunks),
+            [
+                "@@ -2,7 +2,7 @@",
+                " 2",
+                " 3",
+                " 4",
+                "-5",
+                "+five",
+                " 6",
+                " 7",
+                " 8"
+            ]
+        );
+    }
+
+    #[test]
+    fn distant_changes_form_separate_hunks() {
+        let old: String = (1..=20).map(|i| format!("{}\n", i)).collect();
+        let new = old.replace("2\n3\n", "2\nthree\n").replace("18\n", "");
+        let hunks = DiffEngine::unified_diff(&old, &new, 1);
+        assert_eq!(
+            render(&hunks),
+            [
+                "@@ -2,3 +2,3 @@",
+                " 2",
+                "-3",
+                "+three",
+                " 4",
+                "@@ -17,3 +17,2 @@",
+                " 17",
+                "-18",
+                " 19"
+            ]
+        );
+    }
+
+    #[test]
+    fn large_insert_or_delete_from_empty() {
+        let text: String = (0..200_000).map(|i| format!("{}\n", i)).collect();
+        assert_eq!(
+            DiffEngine::get_diff_stats(&DiffEngine::diff_text("", &text)),
+            (200_000, 0)
+        );
+        assert_eq!(
+            DiffEngine::get_diff_stats(&DiffEngine::diff_text(&text, "")),
+            (0, 200_000)
+        );
+    }
+
+    #[test]
+    fn edit_script_is_minimal() {
+        let diff = DiffEngine::diff_text("a\nb\nc\na\nb\nb\na\n", "c\nb\na\nb\na\nc\n");
+        // 经典的 Myers 论文示例，最短编辑距离为 5
+        let (added, removed) = DiffEngine::get_diff_stats(&diff);
+        assert_eq!(added + removed, 5);
+
+        // 按差异重建两侧内容
+        let old: Vec<&str> = diff
+            .iter()
+            .filter(|l| l.line_type != DiffLineType::Added)
+            .map(|l| l.content.as_str())
+            .collect();
+        let new: Vec<&str> = diff
+            .iter()
+            .filter(|l| l.line_type != DiffLineType::Removed)
+            .map(|l| l.content.as_str())
+            .collect();
+        assert_eq!(old, ["a", "b", "c", "a", "b", "b", "a"]);
+        assert_eq!(new, ["c", "b", "a", "b", "a", "c"]);
+    }
+
+    #[test]
+    fn line_numbers_follow_both_sides() {
+        let diff = DiffEngine::diff_text("x\ny\n", "y\nz\n");
+        let numbers: Vec<(Option<usize>, Option<usize>)> = diff
+            .iter()
+            .map(|l| (l.old_line_number, l.new_line_number))
+            .collect();
+        assert_eq!(
+            numbers,
+            [(Some(1), None), (Some(2), Some(1)), (None, Some(2))]
+        );
+    }
+
+    #[test]
+    fn binary_detection_ignores_encoding() {
+        assert!(DiffEngine::is_binary(b"abc\0def"));
+        assert!(!DiffEngine::is_binary("文本".as_bytes()));
+        // GBK 编码的“中文”
+        assert!(!DiffEngine::is_binary(&[0xd6, 0xd0, 0xce, 0xc4, b'\n']));
+    }
+
+    fn merge(base: &str, ours: &str, theirs: &str) -> (String, usize) {
+        let result = DiffEngine::merge3(
+            base.as_bytes(),
+            ours.as_bytes(),
+            theirs.as_bytes(),
+            "local",
+            "snapshot",
+        );
+        (String::from_utf8(result.content).unwrap(), result.conflicts)
+    }
+
+    #[test]
+    fn merge_takes_changes_from_both_sides() {
+        let base = "a\nb\nc\nd\ne\n";
+        let (merged, conflicts) = merge(base, "A\nb\nc\nd\ne\n", "a\nb\nc\nd\nE\n");
+        assert_eq!(merged, "A\nb\nc\nd\nE\n");
+        assert_eq!(conflicts, 0);
+
+        // 只有一方修改
+        assert_eq!(merge(base, base, "a\nb\nX\nd\ne\n").0, "a\nb\nX\nd\ne\n");
+        assert_eq!(merge(base, "a\nb\nd\ne\n", base).0, "a\nb\nd\ne\n");
+        // 双方做了相同的修改
+        assert_eq!(
+            merge(base, "a\nB\nc\nd\ne\n", "a\nB\nc\nd\ne\n"),
+            ("a\nB\nc\nd\ne\n".to_string(), 0)
+        );
+    }
+
+    #[test]
+    fn merge_marks_conflicting_changes() {
+        let (merged, conflicts) = merge("a\nb\nc\n", "a\nours\nc\n", "a\ntheirs\nc\n");
+        assert_eq!(conflicts, 1);
+        assert_eq!(
+            merged,
+            "a\n<<<<<<< local\nours\n=======\ntheirs\n>>>>>>> snapshot\nc\n"
+        );
+
+        // 相邻的修改同样视为冲突
+        let (_, conflicts) = merge("a\nb\nc\n", "A\nb\nc\n", "a\nB\nc\n");
+        assert_eq!(conflicts, 1);
+    }
+
+    #[test]
+    fn merge_preserves_line_endings() {
+        let (merged, conflicts) = merge("a\r\nb\r\nc\r\n", "A\r\nb\r\nc\r\n", "a\r\nb\r\nC\r\n");
+        assert_eq!(merged, "A\r\nb\r\nC\r\n");
+        assert_eq!(conflicts, 0);
+
+        // 冲突标记沿用 CRLF
+        let (merged, _) = merge("a\r\n", "x\r\n", "y\r\n");
+        assert_eq!(
+            merged,
+            "<<<<<<< local\r\nx\r\n=======\r\ny\r\n>>>>>>> snapshot\r\n"
+        );
+    }
+
+    #[test]
+    fn merge_preserves_missing_trailing_newline() {
+        let (merged, _) = merge("a\nb\nc", "A\nb\nc", "a\nb\nc");
+        assert_eq!(merged, "A\nb\nc");
+
+        let (merged, _) = merge("a\nb\nc\n", "A\nb\nc\n", "a\nb\nc\n");
+        assert_eq!(merged, "A\nb\nc\n");
+
+        // 冲突区域的最后一行没有换行时，补上换行使标记独占一行
+        let (merged, conflicts) = merge("a\nb", "a\nx", "a\ny");
+        assert_eq!(conflicts, 1);
+        assert_eq!(
+            merged,
+            "a\n<<<<<<< local\nx\n=======\ny\n>>>>>>> snapshot\n"
+        );
+    }
+
+    #[test]
+    fn merge_keeps_non_utf8_bytes() {
+        // GBK 编码的 "中文"
+        let base = b"\xd6\xd0\xce\xc4\nb\nc\nd\n".to_vec();
+        let ours = b"\xd6\xd0\xce\xc4\xa3\xa1\nb\nc\nd\n".to_vec();
+        let theirs = b"\xd6\xd0\xce\xc4\nb\nc\nD\n".to_vec();
+        let result = DiffEngine::merge3(&base, &ours, &theirs, "local", "snapshot");
+        assert_eq!(result.content, b"\xd6\xd0\xce\xc4\xa3\xa1\nb\nc\nD\n");
+        assert_eq!(result.conflicts, 0);
     }
 }
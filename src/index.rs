@@ -1,8 +1,9 @@
 use anyhow::Result;
+use rayon::prelude::*;
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use crate::{FileEntry, Index};
+use crate::{FileEntry, Index, StatInfo};
 
 pub struct IndexManager {
     index_path: PathBuf,
@@ -33,7 +34,14 @@ impl IndexManager {
         root: &Path,
         _ignore_matcher: &ignore::gitignore::Gitignore,
     ) -> Result<Index> {
+        let cached = self.load().unwrap_or_default();
+        self.scan_with_cache(root, &cached)
+    }
+
+    /// 扫描工作区，对 stat 信息未变化的文件直接复用索引中记录的哈希
+    fn scan_with_cache(&self, root: &Path, cached: &Index) -> Result<Index> {
         let mut index = Index::new();
+        let index_written = self.index_written();
 
         // 使用 ignore::WalkBuilder 来正确处理忽略规则
         let mut builder = ignore::WalkBuilder::new(root);
@@ -45,6 +53,8 @@ impl IndexManager {
             builder.add_custom_ignore_filename(".rustory/ignore");
         }
 
+        // 先遍历目录收集文件，再并行计算哈希
+        let mut files = Vec::new();
         for entry in builder.build() {
             let entry = entry?;
             let path = entry.path();
@@ -59,41 +69,130 @@ impl IndexManager {
                     continue;
                 }
 
-                let metadata = entry.metadata()?;
-                let size = metadata.len();
-                let modified = chrono::DateTime::from(metadata.modified()?);
-
-                // 计算文件哈希
-                let content = fs::read(path)?;
-                let hash = {
-                    use sha1::{Digest, Sha1};
-                    let mut hasher = Sha1::new();
-                    hasher.update(&content);
-                    format!("{:x}", hasher.finalize())
-                };
-
-                index.files.insert(
+                files.push((
+                    path.to_path_buf(),
                     relative_path.to_path_buf(),
-                    FileEntry {
-                        path: relative_path.to_path_buf(),
-                        hash,
-                        size,
-                        modified,
-                    },
-                );
+                    entry.metadata()?,
+                ));
             }
         }
 
+        let entries = files
+            .into_par_iter()
+            .map(|(path, relative_path, metadata)| {
+                let stat = Self::stat_identity(&metadata);
+                let hash = match Self::cached_hash(cached, &relative_path, &metadata, index_written)
+                {
+                    Some(hash) => hash,
+                    None => Self::hash_file(&path)?,
+                };
+
+                let entry = FileEntry {
+                    path: relative_path,
+                    hash,
+                    size: metadata.len(),
+                    modified: chrono::DateTime::from(metadata.modified()?),
+                };
+                Ok((entry, stat))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        for (entry, stat) in entries {
+            index.stat.insert(entry.path.clone(), stat);
+            index.files.insert(entry.path.clone(), entry);
+        }
+
         Ok(index)
     }
 
+    /// 按文件当前的 stat 信息记录索引条目，调用方需保证文件内容与 entry.hash 一致（例如刚从快照恢复），
+    /// 之后的扫描才能直接复用哈希
+    pub fn record_stat(index: &mut Index, root: &Path, mut entry: FileEntry) -> Result<()> {
+        let metadata = fs::metadata(root.join(&entry.path))?;
+        entry.size = metadata.len();
+        entry.modified = chrono::DateTime::from(metadata.modified()?);
+        index
+            .stat
+            .insert(entry.path.clone(), Self::stat_identity(&metadata));
+        index.files.insert(entry.path.clone(), entry);
+        Ok(())
+    }
+
+    /// 工作区中单个文件当前内容的哈希，与扫描时的判断相同：
+    /// stat 信息与索引一致且不是 racy 时复用索引中的哈希，否则重新计算
+    pub fn current_hash(&self, index: &Index, root: &Path, relative_path: &Path) -> Result<String> {
+        let full_path = root.join(relative_path);
+        let metadata = fs::metadata(&full_path)?;
+        match Self::cached_hash(index, relative_path, &metadata, self.index_written()) {
+            Some(hash) => Ok(hash),
+            None => Self::hash_file(&full_path),
+        }
+    }
+
+    /// 索引文件的写入时间，修改时间不早于它的条目可能在写入索引的同时被修改过（racy），需要重新计算哈希
+    fn index_written(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        fs::metadata(&self.index_path)
+            .and_then(|m| m.modified())
+            .ok()
+            .map(chrono::DateTime::from)
+    }
+
+    /// 大小、修改时间、inode 和 ctime 都与索引一致且不是 racy 时，索引中记录的哈希
+    fn cached_hash(
+        cached: &Index,
+        relative_path: &Path,
+        metadata: &fs::Metadata,
+        index_written: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Option<String> {
+        let modified: chrono::DateTime<chrono::Utc> = metadata.modified().ok()?.into();
+        let is_racy = index_written.is_none_or(|written| modified >= written);
+
+        let old = cached.files.get(relative_path)?;
+        let old_stat = cached.stat.get(relative_path)?;
+        (!is_racy
+            && old.size == metadata.len()
+            && old.modified == modified
+            && *old_stat == Self::stat_identity(metadata))
+        .then(|| old.hash.clone())
+    }
+
+    /// 计算文件哈希
+    pub fn hash_file(path: &Path) -> Result<String> {
+        use sha1::{Digest, Sha1};
+
+        // 以固定大小的缓冲区流式计算哈希，大文件不需要整个读入内存
+        let mut file = fs::File::open(path)?;
+        let mut hasher = Sha1::new();
+        std::io::copy(&mut file, &mut hasher)?;
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    /// 获取 inode 和 ctime，用于更可靠地判断文件是否被替换或修改
+    #[cfg(unix)]
+    fn stat_identity(metadata: &fs::Metadata) -> StatInfo {
+        use std::os::unix::fs::MetadataExt;
+
+        StatInfo {
+            inode: Some(metadata.ino()),
+            changed: chrono::DateTime::from_timestamp(
+                metadata.ctime(),
+                metadata.ctime_nsec() as u32,
+            ),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn stat_identity(_metadata: &fs::Metadata) -> StatInfo {
+        StatInfo::default()
+    }
+
     pub fn compare_with_current(
         &self,
         root: &Path,
-        ignore_matcher: &ignore::gitignore::Gitignore,
+        _ignore_matcher: &ignore::gitignore::Gitignore,
     ) -> Result<(Vec<PathBuf>, Vec<PathBuf>, Vec<PathBuf>)> {
         let old_index = self.load()?;
-        let new_index = self.scan_directory(root, ignore_matcher)?;
+        let new_index = self.scan_with_cache(root, &old_index)?;
 
         let mut added = Vec::new();
         let mut modified = Vec::new();
@@ -118,6 +217,11 @@ impl IndexManager {
             }
         }
 
+        // 索引是 HashMap，排序后输出顺序才稳定
+        added.sort();
+        modified.sort();
+        deleted.sort();
+
         Ok((added, modified, deleted))
     }
 }
@@ -2,14 +2,26 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
 
+pub mod chunking;
 pub mod commands;
+pub mod compression;
 pub mod config;
+pub mod delta;
 pub mod diff_engine;
 pub mod index;
 pub mod objects;
+pub mod pack;
+pub mod pathspec;
+pub mod refs;
 pub mod repository;
+pub mod restore;
+pub mod retention;
+pub mod revision;
 pub mod snapshot;
 pub mod stats;
+#[cfg(test)]
+mod test_utils;
+pub mod trash;
 pub mod utils;
 
 pub use repository::Repository;
@@ -22,9 +34,21 @@ pub struct FileEntry {
     pub modified: chrono::DateTime<chrono::Utc>,
 }
 
+/// 索引的 stat 缓存中的一项，只保存在索引中，不写入快照
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StatInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inode: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changed: Option<chrono::DateTime<chrono::Utc>>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Index {
     pub files: HashMap<PathBuf, FileEntry>,
+    /// 文件的 inode 和 ctime，旧索引中可能不存在
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub stat: HashMap<PathBuf, StatInfo>,
 }
 
 impl Default for Index {
@@ -37,6 +61,7 @@ impl Index {
     pub fn new() -> Self {
         Self {
             files: HashMap::new(),
+            stat: HashMap::new(),
         }
     }
 }
@@ -46,6 +71,9 @@ pub struct SnapshotMetadata {
     pub id: String,
     #[serde(default = "default_number")]
     pub number: usize,
+    /// 创建该快照时工作区所基于的快照，第一个快照和旧数据中没有
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
     pub timestamp: chrono::DateTime<chrono::Utc>,
     pub message: String,
     pub added: usize,
@@ -67,4 +95,9 @@ pub struct HistoryEntry {
     pub modified: usize,
     pub deleted: usize,
     pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    /// 指向该快照的标签，仅用于显示，不写入历史日志
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 }
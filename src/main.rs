@@ -7,6 +7,15 @@ fn main() {
         .version("0.1.5")
         .about("A lightweight local version management tool written in Rust")
         .subcommand_required(true)
+        .arg(
+            Arg::new("jobs")
+                .short('j')
+                .long("jobs")
+                .help("Number of worker threads (default: all CPU cores)")
+                .value_name("N")
+                .global(true)
+                .value_parser(clap::value_parser!(usize)),
+        )
         .subcommand(
             Command::new("init")
                 .about("Initialize a new rustory repository")
@@ -20,7 +29,6 @@ fn main() {
             Command::new("add")
                 .about("Create a new snapshot")
                 .alias("commit")
-                .alias("log")
                 .arg(
                     Arg::new("message")
                         .short('m')
@@ -44,6 +52,26 @@ fn main() {
                         .long("json")
                         .help("Output in JSON format")
                         .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("lineage")
+                        .long("lineage")
+                        .help("Only show the snapshots HEAD descends from")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("all")
+                        .long("all")
+                        .help("Show snapshots of all timelines")
+                        .action(clap::ArgAction::SetTrue)
+                        .conflicts_with_all(["lineage", "timeline"]),
+                )
+                .arg(
+                    Arg::new("timeline")
+                        .long("timeline")
+                        .help("Show another timeline instead of the current one")
+                        .value_name("NAME")
+                        .conflicts_with("lineage"),
                 ),
         )
         .subcommand(
@@ -66,8 +94,21 @@ fn main() {
         .subcommand(
             Command::new("diff")
                 .about("Show differences between snapshots or working directory")
-                .arg(Arg::new("id1").help("First snapshot ID").value_name("ID1"))
-                .arg(Arg::new("id2").help("Second snapshot ID").value_name("ID2")),
+                .arg(
+                    Arg::new("id1")
+                        .help("First revision (number, ID prefix, tag, latest~N, @{date})")
+                        .value_name("ID1"),
+                )
+                .arg(Arg::new("id2").help("Second revision").value_name("ID2"))
+                .arg(
+                    Arg::new("unified")
+                        .short('U')
+                        .long("unified")
+                        .help("Number of context lines around each change")
+                        .value_name("N")
+                        .default_value("3")
+                        .value_parser(clap::value_parser!(usize)),
+                ),
         )
         .subcommand(
             Command::new("back")
@@ -75,7 +116,7 @@ fn main() {
                 .alias("rollback")
                 .arg(
                     Arg::new("id")
-                        .help("Snapshot ID to rollback to")
+                        .help("Revision to rollback to (number, ID prefix, tag, latest~N, @{date})")
                         .required(true)
                         .value_name("ID"),
                 )
@@ -90,11 +131,201 @@ fn main() {
                         .long("keep-index")
                         .help("Don't update index.json")
                         .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("merge")
+                        .long("merge")
+                        .help("Keep uncommitted local edits by merging them with the snapshot")
+                        .requires("restore")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("show")
+                .about("Show a snapshot's metadata, changes and diff against its parent")
+                .arg(
+                    Arg::new("id")
+                        .help("Revision to show (defaults to latest)")
+                        .default_value("latest")
+                        .value_name("ID"),
+                )
+                .arg(
+                    Arg::new("stat")
+                        .long("stat")
+                        .help("Only show the change summary, not the diff")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("unified")
+                        .short('U')
+                        .long("unified")
+                        .help("Number of context lines around each change")
+                        .value_name("N")
+                        .default_value("3")
+                        .value_parser(clap::value_parser!(usize)),
+                ),
+        )
+        .subcommand(
+            Command::new("cat")
+                .about("Print a file from a snapshot to stdout")
+                .arg(
+                    Arg::new("spec")
+                        .help("<revision>:<path>, e.g. latest:src/main.rs")
+                        .required(true)
+                        .value_name("REV:PATH"),
+                ),
+        )
+        .subcommand(
+            Command::new("log")
+                .about("Show the snapshots in which a file changed")
+                .arg(
+                    Arg::new("path")
+                        .help("File to show the history of")
+                        .required(true)
+                        .value_name("PATH"),
+                )
+                .arg(
+                    Arg::new("patch")
+                        .short('p')
+                        .long("patch")
+                        .help("Show the content diff of each change")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("follow")
+                        .long("follow")
+                        .help("Continue listing the history across renames")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("unified")
+                        .short('U')
+                        .long("unified")
+                        .help("Number of context lines around each change")
+                        .value_name("N")
+                        .default_value("3")
+                        .value_parser(clap::value_parser!(usize)),
+                ),
+        )
+        .subcommand(
+            Command::new("blame")
+                .about("Show which snapshot last changed each line of a file")
+                .alias("annotate")
+                .arg(
+                    Arg::new("path")
+                        .help("File to annotate")
+                        .required(true)
+                        .value_name("PATH"),
+                )
+                .arg(
+                    Arg::new("id")
+                        .help("Revision to annotate (defaults to HEAD)")
+                        .value_name("ID"),
+                )
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .help("Output in JSON format")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("grep")
+                .about("Search file contents across snapshots")
+                .arg(
+                    Arg::new("pattern")
+                        .help("Regular expression to search for")
+                        .required(true)
+                        .value_name("PATTERN"),
+                )
+                .arg(
+                    Arg::new("revs")
+                        .help("Revisions or ranges to search (defaults to all snapshots)")
+                        .num_args(0..)
+                        .value_name("REVS"),
+                )
+                .arg(
+                    Arg::new("paths")
+                        .help("Limit the search to these files, directories or globs (after --)")
+                        .num_args(1..)
+                        .last(true)
+                        .value_name("PATHS"),
+                )
+                .arg(
+                    Arg::new("ignore-case")
+                        .short('i')
+                        .long("ignore-case")
+                        .help("Match case-insensitively")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("files-with-matches")
+                        .short('l')
+                        .long("files-with-matches")
+                        .help("Only print rev:path for files that match")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("ls")
+                .about("List the files in a snapshot with sizes and hashes")
+                .arg(
+                    Arg::new("id")
+                        .help("Revision to list")
+                        .required(true)
+                        .value_name("ID"),
+                )
+                .arg(
+                    Arg::new("path")
+                        .help("Only list this file, directory or glob")
+                        .value_name("PATH"),
+                )
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .help("Output in JSON format")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("restore")
+                .about("Restore individual files or directories from a snapshot")
+                .arg(
+                    Arg::new("id")
+                        .help(
+                            "Revision to restore from (number, ID prefix, tag, latest~N, @{date})",
+                        )
+                        .required(true)
+                        .value_name("ID"),
+                )
+                .arg(
+                    Arg::new("paths")
+                        .help("Files, directories or glob patterns to restore (after --)")
+                        .required(true)
+                        .num_args(1..)
+                        .last(true)
+                        .value_name("PATHS"),
+                )
+                .arg(
+                    Arg::new("to")
+                        .long("to")
+                        .help(
+                            "Write the files into this directory instead of the working directory",
+                        )
+                        .value_name("DIR")
+                        .value_parser(clap::value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .help("Only show what would be restored")
+                        .action(clap::ArgAction::SetTrue),
                 ),
         )
         .subcommand(
             Command::new("tag")
-                .about("Tag a snapshot")
+                .about("Tag a snapshot, or manage tags")
+                .args_conflicts_with_subcommands(true)
                 .arg(
                     Arg::new("name")
                         .help("Tag name")
@@ -103,9 +334,72 @@ fn main() {
                 )
                 .arg(
                     Arg::new("id")
-                        .help("Snapshot ID to tag")
+                        .help("Revision to tag")
                         .required(true)
                         .value_name("ID"),
+                )
+                .arg(
+                    Arg::new("message")
+                        .short('m')
+                        .long("message")
+                        .help("Create an annotated tag with this message")
+                        .value_name("MSG"),
+                )
+                .subcommand(
+                    Command::new("list").about("List tags").arg(
+                        Arg::new("json")
+                            .long("json")
+                            .help("Output in JSON format")
+                            .action(clap::ArgAction::SetTrue),
+                    ),
+                )
+                .subcommand(
+                    Command::new("delete")
+                        .about("Delete a tag")
+                        .arg(Arg::new("name").help("Tag name").required(true)),
+                )
+                .subcommand(
+                    Command::new("move")
+                        .about("Point an existing tag at another snapshot")
+                        .arg(Arg::new("name").help("Tag name").required(true))
+                        .arg(
+                            Arg::new("id")
+                                .help("Revision to point the tag at")
+                                .required(true),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("timeline")
+                .about("Manage timelines (parallel lines of snapshots)")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("create")
+                        .about("Create a timeline starting at a snapshot")
+                        .arg(Arg::new("name").help("Timeline name").required(true))
+                        .arg(
+                            Arg::new("id")
+                                .help("Revision to start from (defaults to HEAD)")
+                                .value_name("ID"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("switch")
+                        .about("Switch to a timeline and restore its latest snapshot")
+                        .arg(Arg::new("name").help("Timeline name").required(true))
+                        .arg(
+                            Arg::new("force")
+                                .short('f')
+                                .long("force")
+                                .help("Switch even if the working directory has unsaved changes")
+                                .action(clap::ArgAction::SetTrue),
+                        ),
+                )
+                .subcommand(Command::new("list").about("List timelines"))
+                .subcommand(
+                    Command::new("delete")
+                        .about("Delete a timeline (its snapshots are kept)")
+                        .arg(Arg::new("name").help("Timeline name").required(true)),
                 ),
         )
         .subcommand(
@@ -142,7 +436,7 @@ fn main() {
                 .alias("gc")
                 .arg(
                     Arg::new("target")
-                        .help("Snapshot number/ID to remove, or range (e.g., 1-3, abc123-def456)")
+                        .help("Revision to remove, or range (e.g., 1..3, v1.0..latest~2, 1-3)")
                         .value_name("TARGET"),
                 )
                 .arg(
@@ -157,6 +451,13 @@ fn main() {
                         .help("Perform more aggressive cleanup and optimization")
                         .action(clap::ArgAction::SetTrue),
                 )
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .short('f')
+                        .help("Also remove HEAD, tagged snapshots and timeline tips (deletes their tags)")
+                        .action(clap::ArgAction::SetTrue),
+                )
                 .arg(
                     Arg::new("prune-expired")
                         .long("prune-expired")
@@ -164,6 +465,45 @@ fn main() {
                         .action(clap::ArgAction::SetTrue),
                 ),
         )
+        .subcommand(
+            Command::new("trash")
+                .about("Inspect snapshots removed by rm or --prune-expired")
+                .subcommand_required(true)
+                .subcommand(Command::new("list").about("List snapshots in the trash")),
+        )
+        .subcommand(
+            Command::new("undelete")
+                .about("Restore a removed snapshot from the trash")
+                .arg(
+                    Arg::new("id")
+                        .help("Snapshot ID prefix or number of the removed snapshot")
+                        .required(true)
+                        .value_name("ID"),
+                ),
+        )
+        .subcommand(
+            Command::new("squash")
+                .about("Merge a contiguous range of snapshots into one")
+                .arg(
+                    Arg::new("range")
+                        .help("Range to squash, e.g. 3..7 or v1.0..latest")
+                        .required(true)
+                        .value_name("FROM..TO"),
+                )
+                .arg(
+                    Arg::new("message")
+                        .short('m')
+                        .long("message")
+                        .help("Message for the squashed snapshot (defaults to the combined messages)")
+                        .value_name("MESSAGE"),
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .help("Only show what would be squashed")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
         .subcommand(
             Command::new("stats")
                 .about("Show repository statistics")
@@ -187,6 +527,10 @@ fn main() {
 
     let matches = app.get_matches();
 
+    if let Some(&jobs) = matches.get_one::<usize>("jobs") {
+        rustory::utils::configure_jobs(jobs);
+    }
+
     let result = match matches.subcommand() {
         Some(("init", sub_matches)) => {
             let path = sub_matches.get_one::<PathBuf>("path").cloned();
@@ -199,7 +543,10 @@ fn main() {
         }
         Some(("history", sub_matches)) => {
             let json = sub_matches.get_flag("json");
-            HistoryCommand::execute(json)
+            let lineage = sub_matches.get_flag("lineage");
+            let all = sub_matches.get_flag("all");
+            let timeline = sub_matches.get_one::<String>("timeline").cloned();
+            HistoryCommand::execute(json, lineage, all, timeline)
         }
         Some(("status", sub_matches)) => {
             let verbose = sub_matches.get_flag("verbose");
@@ -209,19 +556,106 @@ fn main() {
         Some(("diff", sub_matches)) => {
             let id1 = sub_matches.get_one::<String>("id1").cloned();
             let id2 = sub_matches.get_one::<String>("id2").cloned();
-            DiffCommand::execute(id1, id2)
+            let context = *sub_matches.get_one::<usize>("unified").unwrap();
+            DiffCommand::execute(id1, id2, context)
         }
         Some(("back", sub_matches)) | Some(("rollback", sub_matches)) => {
             let id = sub_matches.get_one::<String>("id").unwrap().clone();
             let restore = sub_matches.get_flag("restore");
             let keep_index = sub_matches.get_flag("keep-index");
-            BackCommand::execute(id, restore, keep_index)
+            let merge = sub_matches.get_flag("merge");
+            BackCommand::execute(id, restore, keep_index, merge)
         }
-        Some(("tag", sub_matches)) => {
-            let name = sub_matches.get_one::<String>("name").unwrap().clone();
+        Some(("show", sub_matches)) => {
             let id = sub_matches.get_one::<String>("id").unwrap().clone();
-            TagCommand::execute(name, id)
+            let stat = sub_matches.get_flag("stat");
+            let context = *sub_matches.get_one::<usize>("unified").unwrap();
+            ShowCommand::execute(id, stat, context)
+        }
+        Some(("cat", sub_matches)) => {
+            let spec = sub_matches.get_one::<String>("spec").unwrap().clone();
+            CatCommand::execute(spec)
         }
+        Some(("log", sub_matches)) => {
+            let path = sub_matches.get_one::<String>("path").unwrap().clone();
+            let patch = sub_matches.get_flag("patch");
+            let follow = sub_matches.get_flag("follow");
+            let context = *sub_matches.get_one::<usize>("unified").unwrap();
+            LogCommand::execute(path, patch, follow, context)
+        }
+        Some(("blame", sub_matches)) => {
+            let path = sub_matches.get_one::<String>("path").unwrap().clone();
+            let id = sub_matches.get_one::<String>("id").cloned();
+            let json = sub_matches.get_flag("json");
+            BlameCommand::execute(path, id, json)
+        }
+        Some(("grep", sub_matches)) => {
+            let pattern = sub_matches.get_one::<String>("pattern").unwrap().clone();
+            let revs: Vec<String> = sub_matches
+                .get_many::<String>("revs")
+                .map(|values| values.cloned().collect())
+                .unwrap_or_default();
+            let paths: Vec<String> = sub_matches
+                .get_many::<String>("paths")
+                .map(|values| values.cloned().collect())
+                .unwrap_or_default();
+            let ignore_case = sub_matches.get_flag("ignore-case");
+            let files_only = sub_matches.get_flag("files-with-matches");
+            GrepCommand::execute(pattern, revs, paths, ignore_case, files_only)
+        }
+        Some(("ls", sub_matches)) => {
+            let id = sub_matches.get_one::<String>("id").unwrap().clone();
+            let path = sub_matches.get_one::<String>("path").cloned();
+            let json = sub_matches.get_flag("json");
+            LsCommand::execute(id, path, json)
+        }
+        Some(("restore", sub_matches)) => {
+            let id = sub_matches.get_one::<String>("id").unwrap().clone();
+            let paths = sub_matches
+                .get_many::<String>("paths")
+                .unwrap()
+                .cloned()
+                .collect();
+            let to = sub_matches.get_one::<PathBuf>("to").cloned();
+            let dry_run = sub_matches.get_flag("dry-run");
+            RestoreCommand::execute(id, paths, to, dry_run)
+        }
+        Some(("tag", sub_matches)) => match sub_matches.subcommand() {
+            Some(("list", list_matches)) => TagCommand::list(list_matches.get_flag("json")),
+            Some(("delete", delete_matches)) => {
+                let name = delete_matches.get_one::<String>("name").unwrap().clone();
+                TagCommand::delete(name)
+            }
+            Some(("move", move_matches)) => {
+                let name = move_matches.get_one::<String>("name").unwrap().clone();
+                let id = move_matches.get_one::<String>("id").unwrap().clone();
+                TagCommand::move_tag(name, id)
+            }
+            _ => {
+                let name = sub_matches.get_one::<String>("name").unwrap().clone();
+                let id = sub_matches.get_one::<String>("id").unwrap().clone();
+                let message = sub_matches.get_one::<String>("message").cloned();
+                TagCommand::execute(name, id, message)
+            }
+        },
+        Some(("timeline", sub_matches)) => match sub_matches.subcommand() {
+            Some(("create", create_matches)) => {
+                let name = create_matches.get_one::<String>("name").unwrap().clone();
+                let id = create_matches.get_one::<String>("id").cloned();
+                TimelineCommand::create(name, id)
+            }
+            Some(("switch", switch_matches)) => {
+                let name = switch_matches.get_one::<String>("name").unwrap().clone();
+                let force = switch_matches.get_flag("force");
+                TimelineCommand::switch(name, force)
+            }
+            Some(("list", _)) => TimelineCommand::list(),
+            Some(("delete", delete_matches)) => {
+                let name = delete_matches.get_one::<String>("name").unwrap().clone();
+                TimelineCommand::delete(name)
+            }
+            _ => unreachable!(),
+        },
         Some(("ignore", sub_matches)) => {
             let action = sub_matches.get_one::<String>("action").cloned();
             IgnoreCommand::execute(action)
@@ -237,13 +671,28 @@ fn main() {
             let dry_run = sub_matches.get_flag("dry-run");
             let aggressive = sub_matches.get_flag("aggressive");
             let prune_expired = sub_matches.get_flag("prune-expired");
+            let force = sub_matches.get_flag("force");
 
             if let Some(target) = target {
-                UtilsCommand::remove_snapshots(target, dry_run)
+                UtilsCommand::remove_snapshots(target, dry_run, force)
             } else {
                 UtilsCommand::gc(dry_run, aggressive, prune_expired)
             }
         }
+        Some(("trash", sub_matches)) => match sub_matches.subcommand() {
+            Some(("list", _)) => TrashCommand::list(),
+            _ => unreachable!(),
+        },
+        Some(("undelete", sub_matches)) => {
+            let id = sub_matches.get_one::<String>("id").unwrap().clone();
+            TrashCommand::undelete(id)
+        }
+        Some(("squash", sub_matches)) => {
+            let range = sub_matches.get_one::<String>("range").unwrap().clone();
+            let message = sub_matches.get_one::<String>("message").cloned();
+            let dry_run = sub_matches.get_flag("dry-run");
+            SquashCommand::execute(range, message, dry_run)
+        }
         Some(("stats", sub_matches)) => {
             let json = sub_matches.get_flag("json");
             UtilsCommand::stats(json)
@@ -1,74 +1,774 @@
 use anyhow::Result;
 use sha1::{Digest, Sha1};
+use std::collections::HashSet;
 use std::fs;
-use std::io::Write;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::chunking::Chunker;
+use crate::compression::Codec;
+use crate::config::Config;
+use crate::delta;
+use crate::pack::{self, Pack, PackEntry, PackWriter};
+
+/// 对象格式：
+/// - 旧版本仓库中的对象没有头部，直接是 gzip 压缩的文件内容
+/// - 版本 1：魔数 + 版本 + 对象类型，负载固定使用 gzip 压缩
+/// - 版本 2：魔数 + 版本 + 对象类型 + 压缩算法，负载使用对应算法压缩
+const OBJECT_MAGIC: &[u8] = b"RSO";
+const OBJECT_VERSION: u8 = 2;
+const OBJECT_VERSION_GZIP_ONLY: u8 = 1;
+const MAX_HEADER_LEN: usize = 6;
+
+/// 解析后的对象头
+#[derive(Debug, Clone, Copy)]
+struct ObjectHeader {
+    kind: ObjectKind,
+    codec: Codec,
+    len: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ObjectKind {
+    /// 完整的文件内容
+    Blob,
+    /// 按内容分块存储的文件，负载为有序的块哈希列表
+    ChunkList,
+    /// 相对于另一个对象的二进制差量，负载为基础对象哈希、差量链深度和差量数据
+    Delta,
+}
+
+impl ObjectKind {
+    fn tag(self) -> u8 {
+        match self {
+            ObjectKind::Blob => b'b',
+            ObjectKind::ChunkList => b'c',
+            ObjectKind::Delta => b'd',
+        }
+    }
+
+    fn from_tag(tag: u8) -> Result<Self> {
+        match tag {
+            b'b' => Ok(ObjectKind::Blob),
+            b'c' => Ok(ObjectKind::ChunkList),
+            b'd' => Ok(ObjectKind::Delta),
+            _ => Err(anyhow::anyhow!("Unknown object type: {:#04x}", tag)),
+        }
+    }
+}
+
+/// 分块列表中的一项
+#[derive(Debug, Clone)]
+pub struct ChunkRef {
+    pub hash: String,
+    pub size: u64,
+}
+
+/// 差量对象的基础对象信息
+#[derive(Debug, Clone)]
+pub struct DeltaInfo {
+    pub base: String,
+    pub depth: u8,
+}
+
+/// 差量负载中基础对象哈希的长度
+const DELTA_BASE_LEN: usize = 40;
+
+/// 超过该大小的文件不尝试差量存储，避免计算差量时占用过多内存
+const DELTA_MAX_FILE_SIZE: usize = 32 * 1024 * 1024;
+
+/// 超过该大小的文件以流的方式边读取边哈希、压缩，不整个读入内存
+const STREAM_THRESHOLD: u64 = DELTA_MAX_FILE_SIZE as u64;
+const STREAM_BUFFER_SIZE: usize = 64 * 1024;
+
+/// 临时文件序号，保证同一进程内的临时文件名不冲突
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// 几何重新打包时，比累计大小的这么多倍还小的打包文件会被合并
+const GEOMETRIC_FACTOR: u64 = 2;
+
+/// 重新打包的结果
+#[derive(Debug, Default)]
+pub struct RepackSummary {
+    pub packed_objects: usize,
+    pub dropped_objects: usize,
+    pub removed_packs: usize,
+    pub freed_bytes: u64,
+}
 
 pub struct ObjectStore {
     objects_dir: PathBuf,
+    /// 打包文件在首次访问时加载
+    packs: OnceLock<Vec<Pack>>,
+    /// 超过该大小（字节）的文件按内容分块存储，None 表示不分块
+    chunk_threshold: Option<u64>,
+    chunker: Chunker,
+    /// 差量链的最大深度，None 表示不使用差量存储
+    delta_max_chain: Option<u8>,
+    codec: Codec,
+    level: i32,
+    /// 这些扩展名的文件不压缩
+    skip_extensions: Vec<String>,
 }
 
 impl ObjectStore {
     pub fn new(objects_dir: PathBuf) -> Self {
-        Self { objects_dir }
+        Self {
+            objects_dir,
+            packs: OnceLock::new(),
+            chunk_threshold: None,
+            chunker: Chunker::default(),
+            delta_max_chain: None,
+            codec: Codec::Gzip,
+            level: Codec::Gzip.default_level(),
+            skip_extensions: Vec::new(),
+        }
     }
 
-    pub fn store_file(&mut self, file_path: &Path) -> Result<String> {
-        let content = fs::read(file_path)?;
-        self.store_content(&content)
+    /// 根据配置调整存储策略
+    pub fn apply_config(&mut self, config: &Config) {
+        self.chunk_threshold = if config.chunking_enabled {
+            Some(config.chunk_threshold_mb * 1024 * 1024)
+        } else {
+            None
+        };
+        self.delta_max_chain = if config.delta_enabled {
+            Some(config.delta_max_chain.clamp(1, u8::MAX as usize) as u8)
+        } else {
+            None
+        };
+        self.codec = config.compression.codec;
+        self.level = config.compression.effective_level();
+        self.skip_extensions = config
+            .compression
+            .skip_extensions
+            .iter()
+            .map(|ext| ext.to_lowercase())
+            .collect();
     }
 
-    pub fn store_content(&mut self, content: &[u8]) -> Result<String> {
-        let mut hasher = Sha1::new();
-        hasher.update(content);
-        let hash = format!("{:x}", hasher.finalize());
+    pub fn store_file(&self, file_path: &Path) -> Result<String> {
+        self.store_file_with_base(file_path, None)
+    }
+
+    /// 存储文件，如果提供了同一路径上一个版本的对象哈希，尝试以差量形式存储
+    pub fn store_file_with_base(&self, file_path: &Path, base: Option<&str>) -> Result<String> {
+        let codec = self.codec_for_path(file_path);
+        let size = fs::metadata(file_path)?.len();
+
+        if size <= STREAM_THRESHOLD {
+            let content = fs::read(file_path)?;
+            return self.store_with_codec(&content, base, codec);
+        }
 
+        // 大文件不做差量，一次读取同时完成哈希和压缩
+        if self
+            .chunk_threshold
+            .is_some_and(|threshold| size >= threshold)
+        {
+            self.store_chunked_stream(file_path, codec)
+        } else {
+            self.store_blob_stream(file_path, codec)
+        }
+    }
+
+    pub fn store_content(&self, content: &[u8]) -> Result<String> {
+        self.store_content_with_base(content, None)
+    }
+
+    pub fn store_content_with_base(&self, content: &[u8], base: Option<&str>) -> Result<String> {
+        self.store_with_codec(content, base, self.codec)
+    }
+
+    /// 已经是压缩格式的文件（按扩展名判断）不再压缩
+    fn codec_for_path(&self, path: &Path) -> Codec {
+        if self.skips_compression(path) {
+            Codec::None
+        } else {
+            self.codec
+        }
+    }
+
+    /// 文件扩展名是否在跳过压缩的列表中
+    pub fn skips_compression(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| self.skip_extensions.contains(&ext.to_lowercase()))
+    }
+
+    fn store_with_codec(&self, content: &[u8], base: Option<&str>, codec: Codec) -> Result<String> {
+        let hash = Self::hash_content(content);
         let object_path = self.get_object_path(&hash);
 
-        // 如果对象已存在，不需要重复存储
-        if !object_path.exists() {
-            if let Some(parent) = object_path.parent() {
-                fs::create_dir_all(parent)?;
+        // 如果对象已存在（松散或已打包），不需要重复存储
+        if !self.exists(&hash) {
+            let should_chunk = self
+                .chunk_threshold
+                .is_some_and(|threshold| content.len() as u64 >= threshold);
+
+            if should_chunk {
+                let mut list = String::new();
+                for chunk in self.chunker.chunks(content) {
+                    self.store_chunk(chunk, codec, &mut list)?;
+                }
+                self.write_chunk_list(&object_path, &list)?;
+            } else {
+                let full = self.encode(ObjectKind::Blob, content, codec)?;
+                let data = match base {
+                    Some(base) => self
+                        .encode_delta(base, content, codec)?
+                        .filter(|d| d.len() < full.len()),
+                    None => None,
+                };
+                self.write_object(&object_path, data.as_deref().unwrap_or(&full))?;
             }
+        }
+
+        Ok(hash)
+    }
 
-            // 使用压缩存储
-            let mut encoder =
-                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
-            encoder.write_all(content)?;
-            let compressed = encoder.finish()?;
+    /// 尝试生成相对于 base 的差量对象，不满足条件时返回 None
+    fn encode_delta(&self, base: &str, content: &[u8], codec: Codec) -> Result<Option<Vec<u8>>> {
+        let Some(max_chain) = self.delta_max_chain else {
+            return Ok(None);
+        };
+
+        if content.len() > DELTA_MAX_FILE_SIZE || base.len() != DELTA_BASE_LEN || !self.exists(base)
+        {
+            return Ok(None);
+        }
+
+        let depth = match self.object_kind(base)? {
+            ObjectKind::Blob => 1,
+            ObjectKind::Delta => self.delta_info(base)?.map_or(1, |info| info.depth + 1),
+            ObjectKind::ChunkList => return Ok(None),
+        };
+        if depth > max_chain {
+            return Ok(None);
+        }
 
-            fs::write(object_path, compressed)?;
+        let base_content = self.get_content(base)?;
+        if base_content.len() > DELTA_MAX_FILE_SIZE {
+            return Ok(None);
+        }
+
+        let mut payload = base.as_bytes().to_vec();
+        payload.push(depth);
+        payload.extend(delta::encode(&base_content, content));
+
+        Ok(Some(self.encode(ObjectKind::Delta, &payload, codec)?))
+    }
+
+    /// 以流的方式存储普通对象：先写入临时文件，得到哈希后再移动到最终位置
+    fn store_blob_stream(&self, file_path: &Path, codec: Codec) -> Result<String> {
+        let temp_path = self.temp_object_path()?;
+
+        let hash = match self.write_blob_stream(file_path, &temp_path, codec) {
+            Ok(hash) => hash,
+            Err(e) => {
+                let _ = fs::remove_file(&temp_path);
+                return Err(e);
+            }
+        };
+
+        if self.exists(&hash) {
+            fs::remove_file(&temp_path)?;
+        } else {
+            Self::commit_object(&temp_path, &self.get_object_path(&hash))?;
         }
 
         Ok(hash)
     }
 
-    pub fn get_content(&self, hash: &str) -> Result<Vec<u8>> {
-        let object_path = self.get_object_path(hash);
+    fn write_blob_stream(
+        &self,
+        file_path: &Path,
+        temp_path: &Path,
+        codec: Codec,
+    ) -> Result<String> {
+        let mut input = fs::File::open(file_path)?;
+        let mut output = BufWriter::new(fs::File::create(temp_path)?);
+        output.write_all(&Self::header_bytes(ObjectKind::Blob, codec))?;
+
+        let mut encoder = codec.encoder(output, self.level_for(codec))?;
+        let mut hasher = Sha1::new();
+        let mut buffer = vec![0u8; STREAM_BUFFER_SIZE];
 
-        if !object_path.exists() {
-            return Err(anyhow::anyhow!("Object {} not found", hash));
+        loop {
+            let read = input.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+            encoder.write_all(&buffer[..read])?;
         }
 
-        let compressed = fs::read(object_path)?;
-        let mut decoder = flate2::read::GzDecoder::new(compressed.as_slice());
-        let mut content = Vec::new();
-        std::io::Read::read_to_end(&mut decoder, &mut content)?;
+        encoder.finish()?.flush()?;
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    /// 以流的方式分块存储：缓冲区中始终保留至少一个最大块的数据，
+    /// 切分结果与整个文件一次性分块时相同
+    fn store_chunked_stream(&self, file_path: &Path, codec: Codec) -> Result<String> {
+        let mut input = fs::File::open(file_path)?;
+        let mut hasher = Sha1::new();
+        let mut list = String::new();
+        let max_size = self.chunker.max_size();
+        let mut buffer: Vec<u8> = Vec::with_capacity(max_size + STREAM_BUFFER_SIZE);
+        let mut eof = false;
+
+        loop {
+            while !eof && buffer.len() < max_size {
+                let start = buffer.len();
+                buffer.resize(start + STREAM_BUFFER_SIZE, 0);
+                let read = input.read(&mut buffer[start..])?;
+                buffer.truncate(start + read);
+                hasher.update(&buffer[start..]);
+                eof = read == 0;
+            }
+
+            if buffer.is_empty() {
+                break;
+            }
 
-        Ok(content)
+            let len = self.chunker.next_boundary(&buffer);
+            self.store_chunk(&buffer[..len], codec, &mut list)?;
+            buffer.drain(..len);
+        }
+
+        let hash = format!("{:x}", hasher.finalize());
+        if !self.exists(&hash) {
+            self.write_chunk_list(&self.get_object_path(&hash), &list)?;
+        }
+        Ok(hash)
     }
 
-    pub fn restore_file(&self, hash: &str, target_path: &Path) -> Result<()> {
-        let content = self.get_content(hash)?;
+    /// 存储单个块，并在块列表中追加一行记录
+    fn store_chunk(&self, chunk: &[u8], codec: Codec, list: &mut String) -> Result<()> {
+        let chunk_hash = Self::hash_content(chunk);
+        if !self.exists(&chunk_hash) {
+            let chunk_path = self.get_object_path(&chunk_hash);
+            self.write_object(&chunk_path, &self.encode(ObjectKind::Blob, chunk, codec)?)?;
+        }
+        list.push_str(&format!("{} {}\n", chunk_hash, chunk.len()));
+        Ok(())
+    }
+
+    fn write_chunk_list(&self, object_path: &Path, list: &str) -> Result<()> {
+        let data = self.encode(ObjectKind::ChunkList, list.as_bytes(), self.codec)?;
+        self.write_object(object_path, &data)
+    }
 
+    pub fn get_content(&self, hash: &str) -> Result<Vec<u8>> {
+        let (kind, payload) = self.read_object(hash)?;
+
+        match kind {
+            ObjectKind::Blob => Ok(payload),
+            ObjectKind::ChunkList => {
+                let mut content = Vec::new();
+                for chunk in Self::parse_chunk_list(&payload)? {
+                    content.extend(self.get_content(&chunk.hash)?);
+                }
+                Ok(content)
+            }
+            ObjectKind::Delta => {
+                let (info, delta) = Self::parse_delta(&payload)?;
+                let base_content = self.get_content(&info.base)?;
+                delta::apply(&base_content, delta)
+            }
+        }
+    }
+
+    /// 校验对象本身：能够解压、结构有效，blob 的内容哈希与对象名一致。
+    /// 块列表和增量对象只校验自身，所引用的块和基础对象单独校验
+    pub fn verify_object(&self, hash: &str) -> Result<()> {
+        let (header, mut reader) = self.open_object(hash)?;
+
+        match header.kind {
+            ObjectKind::Blob => {
+                // 边解压边计算哈希，不把整个对象读入内存
+                let mut hasher = Sha1::new();
+                std::io::copy(&mut reader, &mut hasher)?;
+                let actual = format!("{:x}", hasher.finalize());
+                if actual != hash {
+                    return Err(anyhow::anyhow!(
+                        "Object {} has content hash {}",
+                        hash,
+                        actual
+                    ));
+                }
+            }
+            ObjectKind::ChunkList | ObjectKind::Delta => {
+                let mut payload = Vec::new();
+                reader.read_to_end(&mut payload)?;
+                if header.kind == ObjectKind::ChunkList {
+                    Self::parse_chunk_list(&payload)?;
+                } else {
+                    Self::parse_delta(&payload)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 恢复文件：先以流的方式写入同目录下的临时文件，完成后再替换目标文件
+    pub fn restore_file(&self, hash: &str, target_path: &Path) -> Result<()> {
         if let Some(parent) = target_path.parent() {
             fs::create_dir_all(parent)?;
         }
 
-        fs::write(target_path, content)?;
+        let file_name = target_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("file");
+        let temp_path = target_path.with_file_name(format!(
+            ".{}.rustory-tmp-{}",
+            file_name,
+            std::process::id()
+        ));
+
+        let result = (|| -> Result<()> {
+            let mut file = BufWriter::new(fs::File::create(&temp_path)?);
+            self.write_content(hash, &mut file)?;
+            file.flush()?;
+
+            // 覆盖已有文件时保留其权限
+            if let Ok(metadata) = fs::metadata(target_path) {
+                fs::set_permissions(&temp_path, metadata.permissions())?;
+            }
+            Ok(())
+        })();
+
+        match result {
+            Ok(()) => Ok(fs::rename(&temp_path, target_path)?),
+            Err(e) => {
+                let _ = fs::remove_file(&temp_path);
+                Err(e)
+            }
+        }
+    }
+
+    /// 将对象内容写入 writer，普通对象和分块对象都不需要整个读入内存
+    pub fn write_content(&self, hash: &str, writer: &mut impl Write) -> Result<()> {
+        let (header, mut reader) = self.open_object(hash)?;
+
+        match header.kind {
+            ObjectKind::Blob => {
+                std::io::copy(&mut reader, writer)?;
+            }
+            ObjectKind::ChunkList => {
+                let mut payload = Vec::new();
+                reader.read_to_end(&mut payload)?;
+                for chunk in Self::parse_chunk_list(&payload)? {
+                    self.write_content(&chunk.hash, writer)?;
+                }
+            }
+            // 差量对象的大小受 DELTA_MAX_FILE_SIZE 限制
+            ObjectKind::Delta => writer.write_all(&self.get_content(hash)?)?,
+        }
+
+        Ok(())
+    }
+
+    /// 打开对象并返回解压后的负载读取器
+    fn open_object(&self, hash: &str) -> Result<(ObjectHeader, Box<dyn Read + '_>)> {
+        let object_path = self.get_object_path(hash);
+        let raw: Box<dyn Read> = if object_path.exists() {
+            Box::new(fs::File::open(object_path)?)
+        } else {
+            match self.find_packed(hash) {
+                Some((pack, entry)) => Box::new(pack.reader(entry)?),
+                None => return Err(anyhow::anyhow!("Object {} not found", hash)),
+            }
+        };
+
+        let mut reader = BufReader::new(raw);
+        let header = Self::parse_header(reader.fill_buf()?)?;
+        reader.consume(header.len);
+
+        Ok((header, header.codec.decoder(reader)?))
+    }
+
+    /// 获取对象类型
+    pub fn object_kind(&self, hash: &str) -> Result<ObjectKind> {
+        Ok(self.read_header(hash)?.kind)
+    }
+
+    /// 获取对象使用的压缩算法
+    pub fn object_codec(&self, hash: &str) -> Result<Codec> {
+        Ok(self.read_header(hash)?.codec)
+    }
+
+    /// 只读取对象开头的几个字节解析对象头，不读取整个对象
+    fn read_header(&self, hash: &str) -> Result<ObjectHeader> {
+        let object_path = self.get_object_path(hash);
+        let raw: Box<dyn Read> = if object_path.exists() {
+            Box::new(fs::File::open(object_path)?)
+        } else {
+            match self.find_packed(hash) {
+                Some((pack, entry)) => Box::new(pack.reader(entry)?),
+                None => return Err(anyhow::anyhow!("Object {} not found", hash)),
+            }
+        };
+
+        let mut header = Vec::with_capacity(MAX_HEADER_LEN);
+        raw.take(MAX_HEADER_LEN as u64).read_to_end(&mut header)?;
+        Self::parse_header(&header)
+    }
+
+    /// 获取分块对象引用的块列表，普通对象返回空列表
+    pub fn chunk_refs(&self, hash: &str) -> Result<Vec<ChunkRef>> {
+        if self.object_kind(hash)? != ObjectKind::ChunkList {
+            return Ok(Vec::new());
+        }
+
+        let (_, payload) = self.read_object(hash)?;
+        Self::parse_chunk_list(&payload)
+    }
+
+    /// 获取对象内容的原始大小（分块对象为各块大小之和），以流的方式读取，不占用与对象大小相当的内存
+    pub fn content_size(&self, hash: &str) -> Result<u64> {
+        let (header, mut reader) = self.open_object(hash)?;
+
+        match header.kind {
+            ObjectKind::Blob => Ok(std::io::copy(&mut reader, &mut std::io::sink())?),
+            ObjectKind::ChunkList => {
+                let mut payload = Vec::new();
+                reader.read_to_end(&mut payload)?;
+                Ok(Self::parse_chunk_list(&payload)?
+                    .iter()
+                    .map(|chunk| chunk.size)
+                    .sum())
+            }
+            ObjectKind::Delta => {
+                delta::target_size(Self::parse_delta(&Self::delta_prefix(reader)?)?.1)
+            }
+        }
+    }
+
+    /// 获取差量对象的基础对象，非差量对象返回 None
+    pub fn delta_info(&self, hash: &str) -> Result<Option<DeltaInfo>> {
+        let (header, reader) = self.open_object(hash)?;
+        if header.kind != ObjectKind::Delta {
+            return Ok(None);
+        }
+
+        Ok(Some(Self::parse_delta(&Self::delta_prefix(reader)?)?.0))
+    }
+
+    /// 读取差量负载的开头：基础对象哈希、链深度以及记录基础和目标大小的两个 varint
+    fn delta_prefix(reader: impl Read) -> Result<Vec<u8>> {
+        let mut prefix = Vec::new();
+        reader
+            .take((DELTA_BASE_LEN + 1 + 2 * 10) as u64)
+            .read_to_end(&mut prefix)?;
+        Ok(prefix)
+    }
+
+    /// 将差量对象改写为完整对象，使其不再依赖基础对象
+    pub fn undeltify(&mut self, hash: &str) -> Result<()> {
+        let codec = self.object_codec(hash)?;
+        let temp_path =
+            self.write_temp_object(ObjectKind::Blob, codec, self.level_for(codec), |mut out| {
+                self.write_content(hash, &mut out)
+            })?;
+        Self::commit_object(&temp_path, &self.get_object_path(hash))
+    }
+
+    /// 以流的方式编码对象并写入临时文件，返回临时文件路径
+    fn write_temp_object(
+        &self,
+        kind: ObjectKind,
+        codec: Codec,
+        level: i32,
+        fill: impl FnOnce(&mut dyn Write) -> Result<()>,
+    ) -> Result<PathBuf> {
+        let temp_path = self.temp_object_path()?;
+
+        let result = (|| -> Result<()> {
+            let mut output = BufWriter::new(fs::File::create(&temp_path)?);
+            output.write_all(&Self::header_bytes(kind, codec))?;
+            let mut encoder = codec.encoder(output, level)?;
+            fill(&mut encoder)?;
+            encoder.finish()?.flush()?;
+            Ok(())
+        })();
+
+        match result {
+            Ok(()) => Ok(temp_path),
+            Err(e) => {
+                let _ = fs::remove_file(&temp_path);
+                Err(e)
+            }
+        }
+    }
+
+    fn parse_delta(payload: &[u8]) -> Result<(DeltaInfo, &[u8])> {
+        if payload.len() <= DELTA_BASE_LEN {
+            return Err(anyhow::anyhow!("Truncated delta object"));
+        }
+
+        let base = std::str::from_utf8(&payload[..DELTA_BASE_LEN])?.to_string();
+        let depth = payload[DELTA_BASE_LEN];
+        Ok((DeltaInfo { base, depth }, &payload[DELTA_BASE_LEN + 1..]))
+    }
+
+    /// 读取对象并解压负载
+    fn read_object(&self, hash: &str) -> Result<(ObjectKind, Vec<u8>)> {
+        let raw = self.read_raw(hash)?;
+        let header = Self::parse_header(&raw)?;
+        let payload = header.codec.decompress(&raw[header.len..])?;
+        Ok((header.kind, payload))
+    }
+
+    /// 解析对象头，没有头部的旧格式对象视为 gzip 压缩的 blob
+    fn parse_header(raw: &[u8]) -> Result<ObjectHeader> {
+        let legacy = ObjectHeader {
+            kind: ObjectKind::Blob,
+            codec: Codec::Gzip,
+            len: 0,
+        };
+
+        let version_pos = OBJECT_MAGIC.len();
+        if raw.len() <= version_pos + 1 || !raw.starts_with(OBJECT_MAGIC) {
+            return Ok(legacy);
+        }
+
+        let kind = ObjectKind::from_tag(raw[version_pos + 1])?;
+        match raw[version_pos] {
+            OBJECT_VERSION_GZIP_ONLY => Ok(ObjectHeader {
+                kind,
+                codec: Codec::Gzip,
+                len: version_pos + 2,
+            }),
+            OBJECT_VERSION if raw.len() >= MAX_HEADER_LEN => Ok(ObjectHeader {
+                kind,
+                codec: Codec::from_tag(raw[version_pos + 2])?,
+                len: MAX_HEADER_LEN,
+            }),
+            version => Err(anyhow::anyhow!(
+                "Unsupported object format version {}",
+                version
+            )),
+        }
+    }
+
+    /// 生成带头部的对象数据；使用当前配置的算法时采用配置的压缩级别
+    fn encode(&self, kind: ObjectKind, payload: &[u8], codec: Codec) -> Result<Vec<u8>> {
+        Self::encode_with_level(kind, payload, codec, self.level_for(codec))
+    }
+
+    fn level_for(&self, codec: Codec) -> i32 {
+        if codec == self.codec {
+            self.level
+        } else {
+            codec.default_level()
+        }
+    }
+
+    fn header_bytes(kind: ObjectKind, codec: Codec) -> Vec<u8> {
+        let mut header = OBJECT_MAGIC.to_vec();
+        header.push(OBJECT_VERSION);
+        header.push(kind.tag());
+        header.push(codec.tag());
+        header
+    }
+
+    fn encode_with_level(
+        kind: ObjectKind,
+        payload: &[u8],
+        codec: Codec,
+        level: i32,
+    ) -> Result<Vec<u8>> {
+        let mut data = Self::header_bytes(kind, codec);
+        data.extend(codec.compress(payload, level)?);
+        Ok(data)
+    }
+
+    fn parse_chunk_list(payload: &[u8]) -> Result<Vec<ChunkRef>> {
+        let text = std::str::from_utf8(payload)?;
+        text.lines()
+            .map(|line| {
+                let (hash, size) = line
+                    .split_once(' ')
+                    .ok_or_else(|| anyhow::anyhow!("Invalid chunk list entry: {}", line))?;
+                Ok(ChunkRef {
+                    hash: hash.to_string(),
+                    size: size.parse()?,
+                })
+            })
+            .collect()
+    }
+
+    fn hash_content(content: &[u8]) -> String {
+        let mut hasher = Sha1::new();
+        hasher.update(content);
+        format!("{:x}", hasher.finalize())
+    }
+
+    /// 写入对象：先写临时文件再重命名，中断时不会留下不完整的对象
+    fn write_object(&self, object_path: &Path, data: &[u8]) -> Result<()> {
+        let temp_path = self.temp_object_path()?;
+        if let Err(e) = fs::write(&temp_path, data) {
+            let _ = fs::remove_file(&temp_path);
+            return Err(e.into());
+        }
+        Self::commit_object(&temp_path, object_path)
+    }
+
+    fn commit_object(temp_path: &Path, object_path: &Path) -> Result<()> {
+        if let Some(parent) = object_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(temp_path, object_path)?;
         Ok(())
     }
 
+    /// 临时对象文件位于 objects/tmp/ 下，与对象在同一文件系统上以便原子重命名
+    fn temp_object_path(&self) -> Result<PathBuf> {
+        let temp_dir = self.objects_dir.join("tmp");
+        fs::create_dir_all(&temp_dir)?;
+        Ok(temp_dir.join(format!(
+            "obj-{}-{}",
+            std::process::id(),
+            TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        )))
+    }
+
+    /// 读取对象的原始字节，优先使用松散对象，其次查找打包文件
+    fn read_raw(&self, hash: &str) -> Result<Vec<u8>> {
+        let object_path = self.get_object_path(hash);
+        if object_path.exists() {
+            return Ok(fs::read(object_path)?);
+        }
+
+        match self.find_packed(hash) {
+            Some((pack, entry)) => pack.read(entry),
+            None => Err(anyhow::anyhow!("Object {} not found", hash)),
+        }
+    }
+
+    fn packs(&self) -> &[Pack] {
+        self.packs.get_or_init(|| {
+            Pack::load_all(&self.pack_dir()).unwrap_or_else(|e| {
+                eprintln!("Warning: Failed to load pack files: {}", e);
+                Vec::new()
+            })
+        })
+    }
+
+    fn find_packed(&self, hash: &str) -> Option<(&Pack, &PackEntry)> {
+        self.packs()
+            .iter()
+            .find_map(|pack| pack.find(hash).map(|entry| (pack, entry)))
+    }
+
+    fn pack_dir(&self) -> PathBuf {
+        self.objects_dir.join("pack")
+    }
+
     fn get_object_path(&self, hash: &str) -> PathBuf {
         // 使用前两个字符作为子目录，避免单个目录文件过多
         let (prefix, suffix) = hash.split_at(2);
@@ -76,46 +776,191 @@ impl ObjectStore {
     }
 
     pub fn exists(&self, hash: &str) -> bool {
-        self.get_object_path(hash).exists()
+        self.get_object_path(hash).exists() || self.find_packed(hash).is_some()
     }
 
     /// 获取对象的文件大小
     pub fn get_object_size(&self, hash: &str) -> Result<u64> {
         let object_path = self.get_object_path(hash);
 
-        if !object_path.exists() {
-            return Err(anyhow::anyhow!("Object {} not found", hash));
+        if object_path.exists() {
+            let metadata = std::fs::metadata(&object_path)?;
+            return Ok(metadata.len());
         }
 
-        let metadata = std::fs::metadata(&object_path)?;
-        Ok(metadata.len())
+        match self.find_packed(hash) {
+            Some((_, entry)) => Ok(entry.length),
+            None => Err(anyhow::anyhow!("Object {} not found", hash)),
+        }
     }
 
-    /// 列出所有对象的哈希值
+    /// 列出所有对象的哈希值（包括松散对象和已打包对象）
     pub fn list_all_objects(&self) -> Result<Vec<String>> {
+        let mut objects = self.list_loose_objects()?;
+        let mut seen: HashSet<String> = objects.iter().cloned().collect();
+
+        for pack in self.packs() {
+            for entry in pack.entries() {
+                let hash = pack::encode_hash(&entry.hash);
+                if seen.insert(hash.clone()) {
+                    objects.push(hash);
+                }
+            }
+        }
+
+        Ok(objects)
+    }
+
+    /// 列出所有松散对象的哈希值
+    pub fn list_loose_objects(&self) -> Result<Vec<String>> {
         let mut objects = Vec::new();
 
         for entry in walkdir::WalkDir::new(&self.objects_dir).max_depth(2) {
             let entry = entry?;
-            if entry.file_type().is_file() && entry.depth() == 2 {
-                if let Some(filename) = entry.path().file_name().and_then(|n| n.to_str()) {
-                    if let Some(parent) = entry
-                        .path()
-                        .parent()
-                        .and_then(|p| p.file_name())
-                        .and_then(|n| n.to_str())
-                    {
-                        let hash = format!("{}{}", parent, filename);
-                        objects.push(hash);
-                    }
-                }
+            if entry.file_type().is_file()
+                && entry.depth() == 2
+                && let Some(filename) = entry.path().file_name().and_then(|n| n.to_str())
+                && let Some(parent) = entry
+                    .path()
+                    .parent()
+                    .and_then(|p| p.file_name())
+                    .and_then(|n| n.to_str())
+                && parent.len() == 2
+                && parent.chars().all(|c| c.is_ascii_hexdigit())
+            {
+                let hash = format!("{}{}", parent, filename);
+                objects.push(hash);
             }
         }
 
         Ok(objects)
     }
 
-    /// 删除对象
+    /// 打包文件数量和其中的对象数量
+    pub fn pack_summary(&self) -> (usize, usize) {
+        let packs = self.packs();
+        (packs.len(), packs.iter().map(|pack| pack.len()).sum())
+    }
+
+    /// 将被引用的松散对象写入打包文件，丢弃未被引用的对象。
+    ///
+    /// 打包文件中有未被引用的对象或 full 为 true（`gc --aggressive`）时，
+    /// 所有被引用的对象重写为一个打包文件；否则只把新的松散对象和较小的打包文件
+    /// 合并为一个新打包文件（几何重新打包），大的打包文件不会被反复复制
+    pub fn repack(&mut self, referenced: &HashSet<String>, full: bool) -> Result<RepackSummary> {
+        let mut summary = RepackSummary::default();
+
+        let packs = Pack::load_all(&self.pack_dir())?;
+        let loose: Vec<String> = self
+            .list_loose_objects()?
+            .into_iter()
+            .filter(|hash| referenced.contains(hash))
+            .collect();
+
+        let has_garbage = packs.iter().any(|pack| {
+            pack.entries()
+                .iter()
+                .any(|entry| !referenced.contains(&pack::encode_hash(&entry.hash)))
+        });
+
+        let selected: Vec<&Pack> = if full || has_garbage {
+            packs.iter().collect()
+        } else {
+            self.geometric_selection(&packs, &loose)?
+        };
+
+        // 没有新的松散对象、也没有需要合并的打包文件时无需重新打包
+        if loose.is_empty() && !has_garbage && selected.len() <= 1 {
+            return Ok(summary);
+        }
+
+        let mut writer = PackWriter::new(&self.pack_dir())?;
+        let mut written = HashSet::new();
+
+        // 松散对象优先（例如重新压缩过的对象会覆盖打包中的旧版本）
+        for hash in &loose {
+            writer.add_reader(hash, fs::File::open(self.get_object_path(hash))?)?;
+            written.insert(hash.clone());
+        }
+
+        for pack in &selected {
+            for entry in pack.entries() {
+                let hash = pack::encode_hash(&entry.hash);
+                if !referenced.contains(&hash) {
+                    summary.dropped_objects += 1;
+                    summary.freed_bytes += entry.length;
+                } else if written.insert(hash.clone()) {
+                    writer.add_reader(&hash, pack.reader(entry)?)?;
+                }
+            }
+        }
+
+        summary.packed_objects = writer.len();
+        let new_index = writer.finish()?;
+
+        // 新打包文件已经完整写入，此时才删除旧的打包文件和松散对象
+        for pack in &selected {
+            if new_index.as_deref() != Some(pack.index_path()) {
+                pack.remove()?;
+                summary.removed_packs += 1;
+            }
+        }
+
+        for hash in &loose {
+            self.remove_object(hash)?;
+        }
+        self.remove_empty_dirs()?;
+
+        self.packs = OnceLock::new();
+        Ok(summary)
+    }
+
+    /// 选出需要与新的松散对象合并的打包文件：
+    /// 包含被松散对象取代的旧版本的打包文件必须重写，其余按大小从小到大，
+    /// 小于已选部分累计大小 GEOMETRIC_FACTOR 倍的打包文件一并合并。
+    /// 保留下来的打包文件大小呈几何级数增长，每个对象被复制的次数是对数级的
+    fn geometric_selection<'a>(
+        &self,
+        packs: &'a [Pack],
+        loose: &[String],
+    ) -> Result<Vec<&'a Pack>> {
+        let loose_set: HashSet<&str> = loose.iter().map(|hash| hash.as_str()).collect();
+        let mut total = 0u64;
+        for hash in loose {
+            total += fs::metadata(self.get_object_path(hash))?.len();
+        }
+
+        let (mut selected, mut rest): (Vec<&Pack>, Vec<&Pack>) = packs.iter().partition(|pack| {
+            pack.entries()
+                .iter()
+                .any(|entry| loose_set.contains(pack::encode_hash(&entry.hash).as_str()))
+        });
+        total += selected.iter().map(|pack| pack.size()).sum::<u64>();
+
+        rest.sort_by_key(|pack| pack.size());
+        for pack in rest {
+            if pack.size() >= total.saturating_mul(GEOMETRIC_FACTOR) {
+                break;
+            }
+            total += pack.size();
+            selected.push(pack);
+        }
+
+        Ok(selected)
+    }
+
+    /// 删除对象目录下的空子目录
+    fn remove_empty_dirs(&self) -> Result<()> {
+        for entry in fs::read_dir(&self.objects_dir)? {
+            let path = entry?.path();
+            if path.is_dir() && fs::read_dir(&path)?.next().is_none() {
+                fs::remove_dir(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 删除松散对象；已打包的对象在下次重新打包时被丢弃
     pub fn remove_object(&self, hash: &str) -> Result<()> {
         let object_path = self.get_object_path(hash);
 
@@ -138,31 +983,32 @@ impl ObjectStore {
         Ok(content1 == content2)
     }
 
-    /// 重新压缩对象以获得更好的压缩比
-    pub fn recompress_object(&mut self, hash: &str) -> Result<u64> {
-        let object_path = self.get_object_path(hash);
-
-        if !object_path.exists() {
-            return Err(anyhow::anyhow!("Object {} not found", hash));
+    /// 使用指定的压缩算法和最高压缩级别重新压缩对象，返回处理后的对象大小
+    ///
+    /// 压缩算法与 codec 不同的对象总是被转换；算法相同时只有结果更小才替换。
+    /// codec 为 `Codec::None` 时对象以不压缩的形式保存
+    pub fn recompress_object(&mut self, hash: &str, codec: Codec) -> Result<u64> {
+        let original_size = self.get_object_size(hash)?;
+
+        // 边解压边重新压缩到临时文件（已打包的对象重新压缩后写为松散对象，下次打包时替换旧版本）
+        let (header, mut reader) = self.open_object(hash)?;
+        if header.codec == Codec::None && codec == Codec::None {
+            return Ok(original_size);
         }
 
-        // 读取并解压现有内容
-        let compressed = std::fs::read(&object_path)?;
-        let mut decoder = flate2::read::GzDecoder::new(compressed.as_slice());
-        let mut content = Vec::new();
-        std::io::Read::read_to_end(&mut decoder, &mut content)?;
-
-        // 使用更高级别的压缩重新压缩
-        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
-        std::io::Write::write_all(&mut encoder, &content)?;
-        let new_compressed = encoder.finish()?;
+        let temp_path = self.write_temp_object(header.kind, codec, codec.max_level(), |out| {
+            std::io::copy(&mut reader, out)?;
+            Ok(())
+        })?;
+        drop(reader);
 
-        // 只有在新压缩文件更小时才替换
-        if new_compressed.len() < compressed.len() {
-            std::fs::write(&object_path, &new_compressed)?;
-            Ok(new_compressed.len() as u64)
+        let new_size = fs::metadata(&temp_path)?.len();
+        if header.codec != codec || new_size < original_size {
+            Self::commit_object(&temp_path, &self.get_object_path(hash))?;
+            Ok(new_size)
         } else {
-            Ok(compressed.len() as u64)
+            fs::remove_file(&temp_path)?;
+            Ok(original_size)
         }
     }
 }
@@ -0,0 +1,357 @@
+use anyhow::{Result, anyhow};
+use sha1::{Digest, Sha1};
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// 打包文件：多个对象的原始数据依次拼接在一起，配合 .idx 索引按哈希定位。
+/// 打包内对象的字节与松散对象文件完全相同，读取后可以用同样的方式解码。
+const PACK_MAGIC: &[u8] = b"RSPK";
+const INDEX_MAGIC: &[u8] = b"RSPI";
+const PACK_VERSION: u32 = 1;
+const HASH_LEN: usize = 20;
+const INDEX_ENTRY_LEN: usize = HASH_LEN + 8 + 8;
+
+#[derive(Debug, Clone)]
+pub struct PackEntry {
+    pub hash: [u8; HASH_LEN],
+    pub offset: u64,
+    pub length: u64,
+}
+
+pub struct Pack {
+    pack_path: PathBuf,
+    index_path: PathBuf,
+    /// 按哈希排序，便于二分查找
+    entries: Vec<PackEntry>,
+}
+
+impl Pack {
+    /// 通过 .idx 文件打开一个打包文件
+    pub fn open(index_path: &Path) -> Result<Self> {
+        let data = fs::read(index_path)?;
+        if data.len() < INDEX_MAGIC.len() + 8 || !data.starts_with(INDEX_MAGIC) {
+            return Err(anyhow!("Invalid pack index: {}", index_path.display()));
+        }
+
+        let mut pos = INDEX_MAGIC.len();
+        let version = u32::from_be_bytes(data[pos..pos + 4].try_into()?);
+        if version != PACK_VERSION {
+            return Err(anyhow!("Unsupported pack index version {}", version));
+        }
+        pos += 4;
+        let count = u32::from_be_bytes(data[pos..pos + 4].try_into()?) as usize;
+        pos += 4;
+
+        if data.len() != pos + count * INDEX_ENTRY_LEN {
+            return Err(anyhow!("Truncated pack index: {}", index_path.display()));
+        }
+
+        let mut entries = Vec::with_capacity(count);
+        for _ in 0..count {
+            let hash: [u8; HASH_LEN] = data[pos..pos + HASH_LEN].try_into()?;
+            pos += HASH_LEN;
+            let offset = u64::from_be_bytes(data[pos..pos + 8].try_into()?);
+            pos += 8;
+            let length = u64::from_be_bytes(data[pos..pos + 8].try_into()?);
+            pos += 8;
+            entries.push(PackEntry {
+                hash,
+                offset,
+                length,
+            });
+        }
+
+        Ok(Self {
+            pack_path: index_path.with_extension("pack"),
+            index_path: index_path.to_path_buf(),
+            entries,
+        })
+    }
+
+    /// 加载目录下所有打包文件
+    pub fn load_all(pack_dir: &Path) -> Result<Vec<Self>> {
+        let mut packs = Vec::new();
+
+        if pack_dir.exists() {
+            let mut index_paths: Vec<PathBuf> = fs::read_dir(pack_dir)?
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("idx"))
+                .collect();
+            index_paths.sort();
+
+            for index_path in index_paths {
+                // 只有 .pack 文件存在时索引才有效（写入时先生成 .pack 再生成 .idx）
+                if index_path.with_extension("pack").exists() {
+                    packs.push(Self::open(&index_path)?);
+                }
+            }
+        }
+
+        Ok(packs)
+    }
+
+    pub fn find(&self, hash: &str) -> Option<&PackEntry> {
+        let key = decode_hash(hash).ok()?;
+        self.entries
+            .binary_search_by(|entry| entry.hash.cmp(&key))
+            .ok()
+            .map(|i| &self.entries[i])
+    }
+
+    pub fn read(&self, entry: &PackEntry) -> Result<Vec<u8>> {
+        let mut file = File::open(&self.pack_path)?;
+        file.seek(SeekFrom::Start(entry.offset))?;
+        let mut data = vec![0u8; entry.length as usize];
+        file.read_exact(&mut data)?;
+        Ok(data)
+    }
+
+    /// 以流的方式读取打包中的对象，避免将大对象整个读入内存
+    pub fn reader(&self, entry: &PackEntry) -> Result<impl Read + use<>> {
+        let mut file = File::open(&self.pack_path)?;
+        file.seek(SeekFrom::Start(entry.offset))?;
+        Ok(file.take(entry.length))
+    }
+
+    pub fn index_path(&self) -> &Path {
+        &self.index_path
+    }
+
+    pub fn entries(&self) -> &[PackEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 打包中所有对象的字节数
+    pub fn size(&self) -> u64 {
+        self.entries.iter().map(|entry| entry.length).sum()
+    }
+
+    /// 删除打包文件（先删索引，保证不会留下指向不存在数据的索引）
+    pub fn remove(&self) -> Result<()> {
+        if self.index_path.exists() {
+            fs::remove_file(&self.index_path)?;
+        }
+        if self.pack_path.exists() {
+            fs::remove_file(&self.pack_path)?;
+        }
+        Ok(())
+    }
+}
+
+/// 逐个追加对象生成新的打包文件
+pub struct PackWriter {
+    pack_dir: PathBuf,
+    temp_path: PathBuf,
+    writer: BufWriter<File>,
+    offset: u64,
+    entries: Vec<PackEntry>,
+}
+
+impl PackWriter {
+    pub fn new(pack_dir: &Path) -> Result<Self> {
+        fs::create_dir_all(pack_dir)?;
+
+        let temp_path = pack_dir.join(format!("tmp-pack-{}", std::process::id()));
+        let mut writer = BufWriter::new(File::create(&temp_path)?);
+        writer.write_all(PACK_MAGIC)?;
+        writer.write_all(&PACK_VERSION.to_be_bytes())?;
+
+        Ok(Self {
+            pack_dir: pack_dir.to_path_buf(),
+            temp_path,
+            writer,
+            offset: (PACK_MAGIC.len() + 4) as u64,
+            entries: Vec::new(),
+        })
+    }
+
+    /// 从读取器复制对象数据，大对象不需要整个读入内存
+    pub fn add_reader(&mut self, hash: &str, mut reader: impl Read) -> Result<()> {
+        let length = std::io::copy(&mut reader, &mut self.writer)?;
+        self.entries.push(PackEntry {
+            hash: decode_hash(hash)?,
+            offset: self.offset,
+            length,
+        });
+        self.offset += length;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 写入索引并将打包文件移动到最终位置，返回索引路径；没有对象时不生成文件
+    pub fn finish(mut self) -> Result<Option<PathBuf>> {
+        self.writer.flush()?;
+        drop(self.writer);
+
+        if self.entries.is_empty() {
+            fs::remove_file(&self.temp_path)?;
+            return Ok(None);
+        }
+
+        self.entries.sort_by_key(|entry| entry.hash);
+        self.entries.dedup_by(|a, b| a.hash == b.hash);
+
+        // 以包含的对象哈希命名打包文件
+        let mut hasher = Sha1::new();
+        for entry in &self.entries {
+            hasher.update(entry.hash);
+        }
+        let name = format!("pack-{:x}", hasher.finalize());
+
+        let mut index =
+            Vec::with_capacity(INDEX_MAGIC.len() + 8 + self.entries.len() * INDEX_ENTRY_LEN);
+        index.extend_from_slice(INDEX_MAGIC);
+        index.extend_from_slice(&PACK_VERSION.to_be_bytes());
+        index.extend_from_slice(&(self.entries.len() as u32).to_be_bytes());
+        for entry in &self.entries {
+            index.extend_from_slice(&entry.hash);
+            index.extend_from_slice(&entry.offset.to_be_bytes());
+            index.extend_from_slice(&entry.length.to_be_bytes());
+        }
+
+        let pack_path = self.pack_dir.join(format!("{}.pack", name));
+        let index_path = self.pack_dir.join(format!("{}.idx", name));
+        let temp_index_path = self
+            .pack_dir
+            .join(format!("tmp-idx-{}", std::process::id()));
+
+        fs::rename(&self.temp_path, &pack_path)?;
+        fs::write(&temp_index_path, index)?;
+        fs::rename(&temp_index_path, &index_path)?;
+
+        Ok(Some(index_path))
+    }
+}
+
+pub fn encode_hash(hash: &[u8; HASH_LEN]) -> String {
+    hash.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn decode_hash(hash: &str) -> Result<[u8; HASH_LEN]> {
+    if hash.len() != HASH_LEN * 2 {
+        return Err(anyhow!("Invalid object hash: {}", hash));
+    }
+
+    let mut bytes = [0u8; HASH_LEN];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hash[i * 2..i * 2 + 2], 16)
+            .map_err(|_| anyhow!("Invalid object hash: {}", hash))?;
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TempDir;
+
+    fn hash_of(data: &[u8]) -> String {
+        format!("{:x}", Sha1::digest(data))
+    }
+
+    fn write_pack(dir: &Path, objects: &[&[u8]]) -> PathBuf {
+        let mut writer = PackWriter::new(dir).unwrap();
+        for data in objects {
+            writer.add_reader(&hash_of(data), *data).unwrap();
+        }
+        writer.finish().unwrap().unwrap()
+    }
+
+    #[test]
+    fn write_then_read_objects() {
+        let dir = TempDir::new();
+        let objects: [&[u8]; 3] = [b"first object", b"", b"third object with more bytes"];
+        let index_path = write_pack(&dir.0, &objects);
+
+        let pack = Pack::open(&index_path).unwrap();
+        assert_eq!(pack.len(), 3);
+        assert_eq!(
+            pack.size(),
+            objects.iter().map(|o| o.len() as u64).sum::<u64>()
+        );
+
+        for data in objects {
+            let entry = pack.find(&hash_of(data)).unwrap();
+            assert_eq!(pack.read(entry).unwrap(), data);
+
+            let mut streamed = Vec::new();
+            pack.reader(entry)
+                .unwrap()
+                .read_to_end(&mut streamed)
+                .unwrap();
+            assert_eq!(streamed, data);
+        }
+    }
+
+    #[test]
+    fn lookup_of_missing_or_invalid_hash() {
+        let dir = TempDir::new();
+        let pack = Pack::open(&write_pack(&dir.0, &[b"a", b"b"])).unwrap();
+
+        assert!(pack.find(&hash_of(b"c")).is_none());
+        assert!(pack.find("not a hash").is_none());
+        assert!(pack.find("").is_none());
+    }
+
+    #[test]
+    fn index_is_sorted_and_deduplicated() {
+        let dir = TempDir::new();
+        let data: Vec<Vec<u8>> = (0..50)
+            .map(|i| format!("object {}", i).into_bytes())
+            .collect();
+        let mut objects: Vec<&[u8]> = data.iter().map(|d| d.as_slice()).collect();
+        objects.push(objects[0]);
+        let pack = Pack::open(&write_pack(&dir.0, &objects)).unwrap();
+
+        assert_eq!(pack.len(), 50);
+        assert!(pack.entries().windows(2).all(|w| w[0].hash < w[1].hash));
+        for object in &data {
+            assert!(pack.find(&hash_of(object)).is_some());
+        }
+    }
+
+    #[test]
+    fn load_all_ignores_index_without_pack() {
+        let dir = TempDir::new();
+        let index_path = write_pack(&dir.0, &[b"kept"]);
+        let orphan = write_pack(&dir.0, &[b"orphan"]);
+        fs::remove_file(orphan.with_extension("pack")).unwrap();
+
+        let packs = Pack::load_all(&dir.0).unwrap();
+        assert_eq!(packs.len(), 1);
+        assert_eq!(packs[0].index_path(), index_path);
+    }
+
+    #[test]
+    fn truncated_index_is_rejected() {
+        let dir = TempDir::new();
+        let index_path = write_pack(&dir.0, &[b"x", b"y"]);
+        let data = fs::read(&index_path).unwrap();
+        fs::write(&index_path, &data[..data.len() - 1]).unwrap();
+
+        assert!(Pack::open(&index_path).is_err());
+    }
+
+    #[test]
+    fn empty_writer_creates_no_files() {
+        let dir = TempDir::new();
+        assert!(PackWriter::new(&dir.0).unwrap().finish().unwrap().is_none());
+        assert_eq!(fs::read_dir(&dir.0).unwrap().count(), 0);
+    }
+}
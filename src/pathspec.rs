@@ -0,0 +1,56 @@
+use anyhow::{Result, anyhow};
+use globset::{Glob, GlobMatcher};
+use std::path::{Path, PathBuf};
+
+/// 命令行中的路径参数：普通路径匹配文件本身或目录下的文件，含通配符时按 glob 匹配。
+/// 路径相对于当前目录，匹配时使用相对于仓库根目录的路径
+pub enum PathSpec {
+    Path(PathBuf),
+    Glob(GlobMatcher),
+}
+
+impl PathSpec {
+    pub fn new(root: &Path, current_dir: &Path, pattern: &str) -> Result<Self> {
+        let path = repo_relative(root, current_dir, pattern.trim_end_matches('/'))?;
+
+        if pattern.contains(['*', '?', '[', '{']) {
+            let glob = Glob::new(&path.to_string_lossy())?;
+            Ok(PathSpec::Glob(glob.compile_matcher()))
+        } else {
+            Ok(PathSpec::Path(path))
+        }
+    }
+
+    pub fn matches(&self, path: &Path) -> bool {
+        match self {
+            // 仓库根目录（`.`）匹配所有文件
+            PathSpec::Path(base) => base.as_os_str().is_empty() || path.starts_with(base),
+            PathSpec::Glob(glob) => {
+                glob.is_match(path) || path.ancestors().skip(1).any(|dir| glob.is_match(dir))
+            }
+        }
+    }
+}
+
+/// 将相对于当前目录的路径（或仓库内的绝对路径）转换为相对于仓库根目录的路径
+pub fn repo_relative(root: &Path, current_dir: &Path, path: &str) -> Result<PathBuf> {
+    normalize(&current_dir.join(path))
+        .strip_prefix(root)
+        .map(|path| path.to_path_buf())
+        .map_err(|_| anyhow!("error: '{}' is outside the repository", path))
+}
+
+/// 去掉路径中的 `.` 并处理 `..`
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
@@ -0,0 +1,359 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::snapshot::SnapshotManager;
+
+/// 旧版本在 config.toml 中保存标签时使用的键前缀
+const LEGACY_TAG_PREFIX: &str = "tag.";
+
+/// 这些名字是 `rustory tag` 的子命令，不能用作标签名
+const RESERVED_TAG_NAMES: &[&str] = &["list", "delete", "move", "latest"];
+
+/// 这些名字是 `rustory timeline` 的子命令，不能用作时间线名
+const RESERVED_TIMELINE_NAMES: &[&str] = &["create", "switch", "list", "delete", "latest"];
+
+/// 默认时间线，旧仓库升级时已有的快照都归入这条时间线
+pub const DEFAULT_TIMELINE: &str = "main";
+
+/// 标签，每个标签保存为 `.rustory/refs/tags/<name>` 下的一个 JSON 文件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tag {
+    pub name: String,
+    pub snapshot_id: String,
+    pub created: DateTime<Utc>,
+    /// 附注标签的说明，轻量标签没有
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+}
+
+impl Tag {
+    pub fn new(name: &str, snapshot_id: &str, message: Option<String>) -> Self {
+        // 只有附注标签记录作者
+        let author = message.as_ref().map(|_| current_user());
+
+        Self {
+            name: name.to_string(),
+            snapshot_id: snapshot_id.to_string(),
+            created: Utc::now(),
+            message,
+            author,
+        }
+    }
+}
+
+/// 时间线（分支），每条时间线保存为 `.rustory/refs/timelines/<name>` 下的一个 JSON 文件，
+/// 指向该时间线上最新的快照
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Timeline {
+    pub name: String,
+    pub snapshot_id: String,
+    pub created: DateTime<Utc>,
+}
+
+impl Timeline {
+    pub fn new(name: &str, snapshot_id: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            snapshot_id: snapshot_id.to_string(),
+            created: Utc::now(),
+        }
+    }
+}
+
+pub struct RefStore {
+    refs_dir: PathBuf,
+}
+
+impl RefStore {
+    pub fn new(refs_dir: PathBuf) -> Self {
+        Self { refs_dir }
+    }
+
+    fn tags_dir(&self) -> PathBuf {
+        self.refs_dir.join("tags")
+    }
+
+    fn timelines_dir(&self) -> PathBuf {
+        self.refs_dir.join("timelines")
+    }
+
+    /// 记录当前时间线名称的文件
+    fn current_timeline_path(&self) -> PathBuf {
+        self.refs_dir.join("current")
+    }
+
+    /// 检查标签名是否合法：不能与快照编号、修订表达式或子命令混淆
+    pub fn validate_tag_name(name: &str) -> Result<()> {
+        Self::validate_ref_name("tag", name, RESERVED_TAG_NAMES)
+    }
+
+    pub fn validate_timeline_name(name: &str) -> Result<()> {
+        Self::validate_ref_name("timeline", name, RESERVED_TIMELINE_NAMES)
+    }
+
+    fn validate_ref_name(kind: &str, name: &str, reserved: &[&str]) -> Result<()> {
+        let valid_chars = name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'));
+
+        if name.is_empty()
+            || !valid_chars
+            || name.starts_with(['.', '-'])
+            || name.contains("..")
+            || name.chars().all(|c| c.is_ascii_digit())
+        {
+            return Err(anyhow!(
+                "Invalid {} name '{}': use letters, digits, '.', '-' or '_', and not only digits",
+                kind,
+                name
+            ));
+        }
+
+        if reserved.contains(&name) {
+            return Err(anyhow!(
+                "'{}' is reserved and cannot be a {} name",
+                name,
+                kind
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn get_tag(&self, name: &str) -> Result<Option<Tag>> {
+        if Self::validate_tag_name(name).is_err() {
+            return Ok(None);
+        }
+        read_ref(&self.tags_dir().join(name))
+    }
+
+    /// 列出所有标签，按名称排序
+    pub fn list_tags(&self) -> Result<Vec<Tag>> {
+        let mut tags = Vec::new();
+
+        let tags_dir = self.tags_dir();
+        if tags_dir.exists() {
+            for entry in fs::read_dir(&tags_dir)? {
+                let path = entry?.path();
+                if path.is_file()
+                    && let Some(name) = path.file_name().and_then(|n| n.to_str())
+                    && let Some(tag) = self.get_tag(name)?
+                {
+                    tags.push(tag);
+                }
+            }
+        }
+
+        tags.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(tags)
+    }
+
+    pub fn write_tag(&self, tag: &Tag) -> Result<()> {
+        Self::validate_tag_name(&tag.name)?;
+
+        write_ref(&self.tags_dir(), &tag.name, tag)
+    }
+
+    pub fn delete_tag(&self, name: &str) -> Result<Tag> {
+        let tag = self
+            .get_tag(name)?
+            .ok_or_else(|| anyhow!("Tag '{}' not found", name))?;
+        fs::remove_file(self.tags_dir().join(name))?;
+        Ok(tag)
+    }
+
+    /// 快照ID到标签名列表的映射
+    pub fn tags_by_snapshot(&self) -> Result<HashMap<String, Vec<String>>> {
+        let mut map: HashMap<String, Vec<String>> = HashMap::new();
+        for tag in self.list_tags()? {
+            map.entry(tag.snapshot_id).or_default().push(tag.name);
+        }
+        Ok(map)
+    }
+
+    pub fn get_timeline(&self, name: &str) -> Result<Option<Timeline>> {
+        if Self::validate_timeline_name(name).is_err() {
+            return Ok(None);
+        }
+        read_ref(&self.timelines_dir().join(name))
+    }
+
+    /// 列出所有时间线，按名称排序
+    pub fn list_timelines(&self) -> Result<Vec<Timeline>> {
+        let mut timelines = Vec::new();
+
+        let timelines_dir = self.timelines_dir();
+        if timelines_dir.exists() {
+            for entry in fs::read_dir(&timelines_dir)? {
+                let path = entry?.path();
+                if path.is_file()
+                    && let Some(name) = path.file_name().and_then(|n| n.to_str())
+                    && let Some(timeline) = self.get_timeline(name)?
+                {
+                    timelines.push(timeline);
+                }
+            }
+        }
+
+        timelines.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(timelines)
+    }
+
+    pub fn write_timeline(&self, timeline: &Timeline) -> Result<()> {
+        Self::validate_timeline_name(&timeline.name)?;
+        write_ref(&self.timelines_dir(), &timeline.name, timeline)
+    }
+
+    /// 删除时间线，时间线上的快照保留。不能删除当前时间线
+    pub fn delete_timeline(&self, name: &str) -> Result<Timeline> {
+        if self.current_timeline()? == name {
+            return Err(anyhow!(
+                "Cannot delete the current timeline '{}'; switch to another timeline first",
+                name
+            ));
+        }
+
+        let timeline = self
+            .get_timeline(name)?
+            .ok_or_else(|| anyhow!("Timeline '{}' not found", name))?;
+        fs::remove_file(self.timelines_dir().join(name))?;
+        Ok(timeline)
+    }
+
+    /// 当前时间线名称，没有记录时为默认时间线
+    pub fn current_timeline(&self) -> Result<String> {
+        let path = self.current_timeline_path();
+        if path.exists() {
+            let name = fs::read_to_string(path)?.trim().to_string();
+            if !name.is_empty() {
+                return Ok(name);
+            }
+        }
+        Ok(DEFAULT_TIMELINE.to_string())
+    }
+
+    pub fn set_current_timeline(&self, name: &str) -> Result<()> {
+        Self::validate_timeline_name(name)?;
+        fs::create_dir_all(&self.refs_dir)?;
+
+        let path = self.current_timeline_path();
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, format!("{}\n", name))?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    /// 新快照创建后，当前时间线前进到该快照
+    pub fn advance_current_timeline(&self, snapshot_id: &str) -> Result<()> {
+        let name = self.current_timeline()?;
+        let timeline = match self.get_timeline(&name)? {
+            Some(mut timeline) => {
+                timeline.snapshot_id = snapshot_id.to_string();
+                timeline
+            }
+            None => Timeline::new(&name, snapshot_id),
+        };
+        self.write_timeline(&timeline)
+    }
+
+    /// 旧仓库没有时间线，将默认时间线指向当前快照
+    pub fn ensure_default_timeline(&self, snapshots: &SnapshotManager) -> Result<()> {
+        if self.timelines_dir().exists() {
+            return Ok(());
+        }
+
+        if let Some(head) = snapshots.head()? {
+            self.write_timeline(&Timeline::new(DEFAULT_TIMELINE, &head))?;
+        }
+        Ok(())
+    }
+
+    /// 包含指定快照的时间线（快照在时间线的父快照链上），按名称排序
+    pub fn timelines_containing(
+        &self,
+        snapshots: &SnapshotManager,
+        snapshot_id: &str,
+    ) -> Result<Vec<String>> {
+        let mut names = Vec::new();
+        for timeline in self.list_timelines()? {
+            if snapshots
+                .lineage(&timeline.snapshot_id)?
+                .iter()
+                .any(|entry| entry.snapshot_id == snapshot_id)
+            {
+                names.push(timeline.name);
+            }
+        }
+        Ok(names)
+    }
+
+    /// 将旧版本保存在 config.toml 中的 `tag.<name>` 迁移到标签存储，
+    /// 返回从配置中移除的条目数量（大于 0 时调用方需要保存配置）
+    pub fn migrate_config_tags(
+        &self,
+        config: &mut Config,
+        snapshots: &SnapshotManager,
+    ) -> Result<usize> {
+        let legacy: Vec<(String, String)> = config
+            .tags
+            .iter()
+            .filter_map(|(key, value)| {
+                key.strip_prefix(LEGACY_TAG_PREFIX)
+                    .map(|name| (name.to_string(), value.clone()))
+            })
+            .collect();
+
+        for (name, value) in &legacy {
+            // 旧标签可能保存的是短ID，尽量解析为完整ID
+            let snapshot_id = snapshots
+                .resolve_id_prefix(value)
+                .unwrap_or_else(|_| value.clone());
+
+            if Self::validate_tag_name(name).is_ok() && self.get_tag(name)?.is_none() {
+                self.write_tag(&Tag::new(name, &snapshot_id, None))?;
+            } else {
+                eprintln!(
+                    "Warning: Could not migrate tag '{}' from config.toml; it was dropped",
+                    name
+                );
+            }
+            config
+                .tags
+                .remove(&format!("{}{}", LEGACY_TAG_PREFIX, name));
+        }
+
+        Ok(legacy.len())
+    }
+}
+
+fn read_ref<T: serde::de::DeserializeOwned>(path: &Path) -> Result<Option<T>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let content = fs::read_to_string(path)?;
+    Ok(Some(serde_json::from_str(&content)?))
+}
+
+/// 写入引用文件（先写临时文件再重命名）
+fn write_ref<T: Serialize>(dir: &Path, name: &str, value: &T) -> Result<()> {
+    fs::create_dir_all(dir)?;
+
+    let temp_path = dir.join(format!(".{}.tmp", name));
+    fs::write(&temp_path, serde_json::to_string_pretty(value)?)?;
+    fs::rename(&temp_path, dir.join(name))?;
+    Ok(())
+}
+
+fn current_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
@@ -1,12 +1,35 @@
 use anyhow::{Result, anyhow};
+use rayon::prelude::*;
+use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
+use crate::compression::Codec;
 use crate::config::Config;
 use crate::index::IndexManager;
 use crate::objects::ObjectStore;
+use crate::refs::{RefStore, Tag};
+use crate::restore::Conflicts;
+use crate::retention::RetentionPolicy;
 use crate::snapshot::SnapshotManager;
 
+/// `.rustory/` 中记录仓库状态的文件，清理时绝不删除
+const REPOSITORY_FILES: &[&str] = &[
+    "config.toml",
+    "index.json",
+    "history.log",
+    "HEAD",
+    "ignore",
+    "conflicts.json",
+    "refs/current",
+];
+
+/// 其中的文件（以 `.` 开头的临时文件除外）都是仓库数据的目录
+const REPOSITORY_DIRS: &[&str] = &["snapshots", "trash", "refs/tags", "refs/timelines"];
+
+/// 临时文件至少存在这么久（秒）才会被清理，避免影响正在运行的进程
+const TEMP_FILE_GRACE_SECS: u64 = 3600;
+
 pub struct Repository {
     pub root: PathBuf,
     pub rustory_dir: PathBuf,
@@ -14,6 +37,7 @@ pub struct Repository {
     pub object_store: ObjectStore,
     pub index_manager: IndexManager,
     pub snapshot_manager: SnapshotManager,
+    pub refs: RefStore,
 }
 
 impl Repository {
@@ -26,13 +50,27 @@ impl Repository {
             ));
         }
 
-        let config = Config::load(&rustory_dir)?;
-        let object_store = ObjectStore::new(rustory_dir.join("objects"));
+        let mut config = Config::load(&rustory_dir)?;
+        crate::utils::configure_jobs(config.jobs);
+        let mut object_store = ObjectStore::new(rustory_dir.join("objects"));
+        object_store.apply_config(&config);
         let index_manager = IndexManager::new(rustory_dir.join("index.json"));
         let snapshot_manager = SnapshotManager::new(
             rustory_dir.join("snapshots"),
             rustory_dir.join("history.log"),
+            rustory_dir.join("HEAD"),
+            rustory_dir.join("trash"),
         );
+        let refs = RefStore::new(rustory_dir.join("refs"));
+
+        // 回滚或清理上次删除快照中断时留下的暂存文件
+        snapshot_manager.recover_dropped()?;
+
+        // 旧版本把标签保存在 config.toml 中，迁移到标签存储
+        if refs.migrate_config_tags(&mut config, &snapshot_manager)? > 0 {
+            config.save(&rustory_dir)?;
+        }
+        refs.ensure_default_timeline(&snapshot_manager)?;
 
         Ok(Self {
             root,
@@ -41,6 +79,7 @@ impl Repository {
             object_store,
             index_manager,
             snapshot_manager,
+            refs,
         })
     }
 
@@ -51,6 +90,8 @@ impl Repository {
         fs::create_dir_all(&rustory_dir)?;
         fs::create_dir_all(rustory_dir.join("objects"))?;
         fs::create_dir_all(rustory_dir.join("snapshots"))?;
+        fs::create_dir_all(rustory_dir.join("refs").join("tags"))?;
+        fs::create_dir_all(rustory_dir.join("refs").join("timelines"))?;
 
         // 创建默认忽略文件（先创建这个文件，这样在扫描时就能被使用）
         let ignore_content = r#"# rustory ignore rules (gitignore style)
@@ -73,6 +114,9 @@ out/
 .idea/
 *.iml
 
+# Version control
+.git/
+
 # rustory itself
 .rustory/
 
@@ -85,12 +129,16 @@ rustory-rollback/
         let config = Config::default();
         config.save(&rustory_dir)?;
 
-        let object_store = ObjectStore::new(rustory_dir.join("objects"));
+        let mut object_store = ObjectStore::new(rustory_dir.join("objects"));
+        object_store.apply_config(&config);
         let index_manager = IndexManager::new(rustory_dir.join("index.json"));
         let snapshot_manager = SnapshotManager::new(
             rustory_dir.join("snapshots"),
             rustory_dir.join("history.log"),
+            rustory_dir.join("HEAD"),
+            rustory_dir.join("trash"),
         );
+        let refs = RefStore::new(rustory_dir.join("refs"));
 
         let mut repo = Self {
             root,
@@ -99,6 +147,7 @@ rustory-rollback/
             object_store,
             index_manager,
             snapshot_manager,
+            refs,
         };
 
         // 创建初始快照
@@ -130,12 +179,13 @@ rustory-rollback/
             &mut self.index_manager,
             message,
         )?;
+        self.refs.advance_current_timeline(&snapshot_id)?;
 
         // 如果启用了自动 GC，在创建快照后运行
-        if self.config.gc_auto_enabled {
-            if let Err(e) = self.auto_gc() {
-                eprintln!("Warning: Auto GC failed: {}", e);
-            }
+        if self.config.gc_auto_enabled
+            && let Err(e) = self.auto_gc()
+        {
+            eprintln!("Warning: Auto GC failed: {}", e);
         }
 
         Ok(snapshot_id)
@@ -156,17 +206,59 @@ rustory-rollback/
             println!("Running in dry-run mode (no changes will be made)");
         }
 
+        // 先合并连续的小快照、删除过期的快照，它们独占的对象在本次回收中一并清理
+        self.auto_squash(dry_run)?;
+
+        let mut dropped = Vec::new();
+        if prune_expired {
+            dropped = self.expired_snapshots(dry_run)?;
+            self.drop_snapshots(&dropped, dry_run)?;
+        }
+
+        self.collect_garbage(dry_run, aggressive, &dropped)
+    }
+
+    /// 回收不再被快照（包括回收站中未过期的快照）引用的对象；
+    /// 试运行时 dropped 中的快照视为已放入回收站
+    pub fn collect_garbage(
+        &mut self,
+        dry_run: bool,
+        aggressive: bool,
+        dropped: &[String],
+    ) -> Result<()> {
         if aggressive {
             println!("Running aggressive garbage collection...");
         }
 
+        // 回收站中超过保留期的快照永久删除，其余快照的对象继续保留
+        let mut excluded = self.purge_trash(dry_run)?;
+        if self.config.trash_grace_days == 0 {
+            excluded.extend(dropped.iter().cloned());
+        }
+
         // 收集所有被引用的对象哈希
-        let referenced_objects = self.collect_referenced_objects()?;
+        let referenced_objects = self.collect_referenced_objects(&excluded)?;
         println!(
             "Found {} objects referenced by snapshots",
             referenced_objects.len()
         );
 
+        // 删除基础对象之前，先把依赖它们的差量对象改写为完整对象
+        let rebased = self.rebase_orphaned_deltas(&referenced_objects, dry_run)?;
+        if rebased > 0 {
+            if dry_run {
+                println!(
+                    "Would rebase {} delta objects whose base is unreferenced",
+                    rebased
+                );
+            } else {
+                println!(
+                    "Rebased {} delta objects whose base is unreferenced",
+                    rebased
+                );
+            }
+        }
+
         // 查找所有存储的对象
         let stored_objects = self.object_store.list_all_objects()?;
         println!("Found {} objects in storage", stored_objects.len());
@@ -187,11 +279,11 @@ rustory-rollback/
 
         for object_hash in &unreferenced_objects {
             if !dry_run {
-                if let Ok(size) = self.object_store.get_object_size(object_hash) {
-                    if self.object_store.remove_object(object_hash).is_ok() {
-                        removed_count += 1;
-                        freed_bytes += size;
-                    }
+                if let Ok(size) = self.object_store.get_object_size(object_hash)
+                    && self.object_store.remove_object(object_hash).is_ok()
+                {
+                    removed_count += 1;
+                    freed_bytes += size;
                 }
             } else {
                 if let Ok(size) = self.object_store.get_object_size(object_hash) {
@@ -207,9 +299,23 @@ rustory-rollback/
             freed_bytes += additional_freed;
         }
 
-        // 如果启用了清理过期快照
-        if prune_expired {
-            self.prune_expired_snapshots(dry_run)?;
+        // 将松散对象合并为打包文件，同时丢弃打包文件中未被引用的对象
+        if dry_run {
+            let loose_count = self
+                .object_store
+                .list_loose_objects()?
+                .iter()
+                .filter(|hash| referenced_objects.contains(*hash))
+                .count();
+            println!("Would pack {} loose objects", loose_count);
+        } else {
+            let summary = self.object_store.repack(&referenced_objects, aggressive)?;
+            if summary.packed_objects > 0 || summary.removed_packs > 0 {
+                println!(
+                    "Packed {} objects into a pack file (replaced {} old packs, dropped {} unreferenced packed objects)",
+                    summary.packed_objects, summary.removed_packs, summary.dropped_objects
+                );
+            }
         }
 
         if dry_run {
@@ -236,6 +342,28 @@ rustory-rollback/
         Ok(())
     }
 
+    /// 永久删除回收站中超过保留期的快照，返回它们的ID
+    fn purge_trash(&self, dry_run: bool) -> Result<Vec<String>> {
+        let trash = self.snapshot_manager.trash();
+        let expired = trash.expired(self.config.trash_grace_days)?;
+        if expired.is_empty() {
+            return Ok(expired);
+        }
+
+        if dry_run {
+            println!(
+                "Would permanently remove {} snapshots from trash",
+                expired.len()
+            );
+        } else {
+            for id in &expired {
+                trash.remove(id)?;
+            }
+            println!("Permanently removed {} snapshots from trash", expired.len());
+        }
+        Ok(expired)
+    }
+
     /// 检查是否应该运行 GC
     fn should_run_gc(&self) -> Result<bool> {
         // 简单的策略：每 10 次提交运行一次 GC
@@ -243,82 +371,427 @@ rustory-rollback/
         Ok(history.len() % 10 == 0)
     }
 
-    /// 收集所有被快照引用的对象哈希
-    fn collect_referenced_objects(&self) -> Result<std::collections::HashSet<String>> {
+    /// 收集所有被快照引用的对象哈希（不包括 excluded 中的快照）
+    fn collect_referenced_objects(
+        &self,
+        excluded: &[String],
+    ) -> Result<std::collections::HashSet<String>> {
         use std::collections::HashSet;
         use std::fs;
 
-        let mut referenced = HashSet::new();
-
         // 读取所有快照文件
         let snapshots_dir = self.rustory_dir.join("snapshots");
+        let mut snapshot_paths = Vec::new();
         if snapshots_dir.exists() {
             for entry in fs::read_dir(&snapshots_dir)? {
-                let entry = entry?;
-                let path = entry.path();
-
-                if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("json") {
-                    if let Ok(content) = fs::read_to_string(&path) {
-                        if let Ok(snapshot) =
-                            serde_json::from_str::<crate::SnapshotMetadata>(&content)
-                        {
-                            // 收集快照中所有文件的哈希
-                            for file_entry in snapshot.files.values() {
-                                referenced.insert(file_entry.hash.clone());
-                            }
-                        }
-                    }
+                let path = entry?.path();
+                let excluded = path
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .is_some_and(|id| excluded.iter().any(|e| e == id));
+                if path.is_file()
+                    && path.extension().and_then(|s| s.to_str()) == Some("json")
+                    && !excluded
+                {
+                    snapshot_paths.push(path);
                 }
             }
         }
 
+        // 并行解析快照，收集快照中所有文件的哈希。
+        // 任何快照无法读取时中止：跳过它会把它引用的对象当成垃圾删除
+        let snapshots = snapshot_paths
+            .par_iter()
+            .map(|path| {
+                fs::read_to_string(path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|content| {
+                        Ok(serde_json::from_str::<crate::SnapshotMetadata>(&content)?)
+                    })
+                    .map_err(|e| {
+                        anyhow::anyhow!(
+                            "Cannot read snapshot {}: {}; aborting garbage collection",
+                            path.display(),
+                            e
+                        )
+                    })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let mut referenced: HashSet<String> = snapshots
+            .into_iter()
+            .flat_map(|snapshot| {
+                snapshot
+                    .files
+                    .into_values()
+                    .map(|file_entry| file_entry.hash)
+            })
+            .collect();
+
+        // 回收站中的快照在保留期内可以恢复，它们的对象同样需要保留
+        for trashed in self.snapshot_manager.trash().list()? {
+            if !excluded.contains(&trashed.snapshot.id) {
+                referenced.extend(
+                    trashed
+                        .snapshot
+                        .files
+                        .into_values()
+                        .map(|file_entry| file_entry.hash),
+                );
+            }
+        }
+
+        // 分块存储的文件还引用了各个数据块；超过大小限制而没有保存的文件没有对象，
+        // 其他读取错误则中止，以免删除仍被引用的数据块
+        let chunks: Vec<Vec<crate::objects::ChunkRef>> = referenced
+            .par_iter()
+            .filter(|hash| self.object_store.exists(hash))
+            .map(|hash| {
+                self.object_store.chunk_refs(hash).map_err(|e| {
+                    anyhow::anyhow!(
+                        "Cannot read object {}: {}; aborting garbage collection",
+                        hash,
+                        e
+                    )
+                })
+            })
+            .collect::<Result<_>>()?;
+        referenced.extend(chunks.into_iter().flatten().map(|chunk| chunk.hash));
+
         Ok(referenced)
     }
 
-    /// 清理过期的快照
-    fn prune_expired_snapshots(&self, dry_run: bool) -> Result<()> {
-        use chrono::{Duration, Utc};
+    /// 将基础对象不再被引用的差量对象改写为完整对象，返回处理的数量
+    fn rebase_orphaned_deltas(
+        &mut self,
+        referenced: &std::collections::HashSet<String>,
+        dry_run: bool,
+    ) -> Result<usize> {
+        let mut orphaned = Vec::new();
+        for hash in referenced {
+            if let Ok(Some(info)) = self.object_store.delta_info(hash)
+                && !referenced.contains(&info.base)
+            {
+                orphaned.push(hash.clone());
+            }
+        }
+
+        if !dry_run {
+            for hash in &orphaned {
+                self.object_store.undeltify(hash)?;
+            }
+        }
+
+        Ok(orphaned.len())
+    }
+
+    /// 受保护的快照及原因：HEAD、各时间线上最新的快照，以及（include_tags 时）有标签指向的快照
+    pub fn protected_snapshots(&self, include_tags: bool) -> Result<HashMap<String, Vec<String>>> {
+        let mut protected: HashMap<String, Vec<String>> = HashMap::new();
 
-        // 从配置中获取保留策略
-        let keep_days = self.config.gc_keep_days.unwrap_or(30);
-        let keep_count = self.config.gc_keep_snapshots.unwrap_or(50);
+        if let Some(head) = self.snapshot_manager.head()? {
+            protected.entry(head).or_default().push("HEAD".to_string());
+        }
+        if include_tags {
+            for (id, tags) in self.refs.tags_by_snapshot()? {
+                for tag in tags {
+                    protected
+                        .entry(id.clone())
+                        .or_default()
+                        .push(format!("tag {}", tag));
+                }
+            }
+        }
+        for timeline in self.refs.list_timelines()? {
+            protected
+                .entry(timeline.snapshot_id)
+                .or_default()
+                .push(format!("timeline {}", timeline.name));
+        }
 
-        let cutoff_date = Utc::now() - Duration::days(keep_days as i64);
+        Ok(protected)
+    }
+
+    /// 删除快照并保持历史记录、HEAD、标签和时间线一致。
+    /// 调用方负责检查受保护的快照；指向被删除快照的标签会被删除，
+    /// 时间线回退到最近的保留下来的祖先
+    pub fn drop_snapshots(&self, snapshot_ids: &[String], dry_run: bool) -> Result<()> {
+        if snapshot_ids.is_empty() {
+            return Ok(());
+        }
 
-        // 获取所有快照的历史
         let history = self.snapshot_manager.list_history()?;
+        let describe = |id: &str| match history.iter().find(|entry| entry.snapshot_id == id) {
+            Some(entry) => format!(
+                "#{} {} \"{}\"",
+                entry.number,
+                crate::utils::short_id(id),
+                entry.message
+            ),
+            None => crate::utils::short_id(id),
+        };
 
-        // 按时间排序（最新的在前）
-        let mut snapshots_to_remove = Vec::new();
+        if dry_run {
+            for id in snapshot_ids {
+                println!("Would remove snapshot: {}", describe(id));
+            }
+            return Ok(());
+        }
 
-        // 保留最新的 keep_count 个快照
-        for (i, entry) in history.iter().enumerate() {
-            if i >= keep_count || entry.timestamp < cutoff_date {
-                snapshots_to_remove.push(entry.snapshot_id.clone());
+        // 删除之前计算时间线的新位置
+        let current = self.refs.current_timeline()?;
+        let mut retargeted = Vec::new();
+        for timeline in self.refs.list_timelines()? {
+            if !snapshot_ids.contains(&timeline.snapshot_id) {
+                continue;
             }
+            let ancestor = self
+                .snapshot_manager
+                .lineage(&timeline.snapshot_id)?
+                .into_iter()
+                .map(|entry| entry.snapshot_id)
+                .find(|id| !snapshot_ids.contains(id));
+            retargeted.push((timeline, ancestor));
         }
 
-        println!("Found {} snapshots to prune", snapshots_to_remove.len());
+        // 指向被删除快照的标签随快照一起放入回收站，恢复快照时一并恢复
+        let mut dropped_tags: HashMap<String, Vec<Tag>> = HashMap::new();
+        for tag in self.refs.list_tags()? {
+            if snapshot_ids.contains(&tag.snapshot_id) {
+                dropped_tags
+                    .entry(tag.snapshot_id.clone())
+                    .or_default()
+                    .push(tag);
+            }
+        }
+
+        self.snapshot_manager
+            .drop_snapshots(snapshot_ids, &dropped_tags)?;
+        let keep_in_trash = self.config.trash_grace_days > 0;
+        for id in snapshot_ids {
+            if keep_in_trash {
+                println!("Moved snapshot to trash: {}", describe(id));
+            } else {
+                println!("Removed snapshot: {}", describe(id));
+            }
+        }
+        if keep_in_trash {
+            println!(
+                "Deleted snapshots can be restored with 'rustory undelete <id>' within {} days",
+                self.config.trash_grace_days
+            );
+        }
 
-        for snapshot_id in &snapshots_to_remove {
-            let snapshot_path = self
-                .rustory_dir
-                .join("snapshots")
-                .join(format!("{}.json", snapshot_id));
+        for tag in dropped_tags.values().flatten() {
+            self.refs.delete_tag(&tag.name)?;
+            println!("Deleted tag {}", tag.name);
+        }
 
-            if !dry_run {
-                if snapshot_path.exists() {
-                    fs::remove_file(&snapshot_path)?;
-                    println!("Removed snapshot: {}", snapshot_id);
+        for (mut timeline, ancestor) in retargeted {
+            // 没有保留下来的祖先时删除时间线；当前时间线改为指向 HEAD
+            let target = match ancestor {
+                Some(id) => Some(id),
+                None if timeline.name == current => self.snapshot_manager.head()?,
+                None => None,
+            };
+            match target {
+                Some(id) => {
+                    timeline.snapshot_id = id;
+                    self.refs.write_timeline(&timeline)?;
+                    println!(
+                        "Moved timeline {} to {}",
+                        timeline.name,
+                        describe(&timeline.snapshot_id)
+                    );
                 }
-            } else {
-                println!("Would remove snapshot: {}", snapshot_id);
+                None if timeline.name != current => {
+                    self.refs.delete_timeline(&timeline.name)?;
+                    println!("Deleted timeline {}", timeline.name);
+                }
+                None => {}
             }
         }
 
         Ok(())
     }
 
+    /// 从回收站恢复快照，并重新创建删除时一并删除的标签（同名标签已指向其他快照时跳过）
+    pub fn undelete_snapshot(&self, snapshot_id: &str) -> Result<crate::HistoryEntry> {
+        let tags = self.snapshot_manager.trash().get(snapshot_id)?.tags;
+        let entry = self.snapshot_manager.undelete_snapshot(snapshot_id)?;
+
+        for tag in tags {
+            match self.refs.get_tag(&tag.name)? {
+                None => {
+                    self.refs.write_tag(&tag)?;
+                    println!("Restored tag {}", tag.name);
+                }
+                Some(existing) if existing.snapshot_id == tag.snapshot_id => {}
+                Some(_) => println!(
+                    "Tag {} now points to another snapshot; not restored",
+                    tag.name
+                ),
+            }
+        }
+
+        Ok(entry)
+    }
+
+    /// 将一段连续的快照合并为一个快照，并更新指向它们的 HEAD、标签和时间线，返回合并后的快照ID
+    pub fn squash_snapshots(
+        &self,
+        snapshot_ids: &[String],
+        message: Option<String>,
+    ) -> Result<String> {
+        // 范围中间的快照合并后不再存在，指向它们的引用需要先处理
+        let middle = &snapshot_ids[..snapshot_ids.len().saturating_sub(1)];
+        for tag in self.refs.list_tags()? {
+            if middle.contains(&tag.snapshot_id) {
+                return Err(anyhow!(
+                    "error: tag '{}' points inside the range; move or delete it first",
+                    tag.name
+                ));
+            }
+        }
+        for timeline in self.refs.list_timelines()? {
+            if middle.contains(&timeline.snapshot_id) {
+                return Err(anyhow!(
+                    "error: timeline '{}' ends inside the range",
+                    timeline.name
+                ));
+            }
+        }
+
+        let (squashed, remap) = self
+            .snapshot_manager
+            .squash_snapshots(snapshot_ids, message)?;
+
+        for mut tag in self.refs.list_tags()? {
+            if let Some(new_id) = remap.get(&tag.snapshot_id) {
+                tag.snapshot_id = new_id.clone();
+                self.refs.write_tag(&tag)?;
+            }
+        }
+        for mut timeline in self.refs.list_timelines()? {
+            if let Some(new_id) = remap.get(&timeline.snapshot_id) {
+                timeline.snapshot_id = new_id.clone();
+                self.refs.write_timeline(&timeline)?;
+            }
+        }
+
+        let mut conflicts = Conflicts::load(&self.rustory_dir)?;
+        if let Some(new_id) = remap.get(&conflicts.snapshot_id) {
+            conflicts.snapshot_id = new_id.clone();
+            conflicts.save(&self.rustory_dir)?;
+        }
+
+        Ok(squashed)
+    }
+
+    /// 自动合并在 gc_squash_within_minutes 分钟内连续创建的小快照（未设置时不执行）
+    fn auto_squash(&self, dry_run: bool) -> Result<()> {
+        let Some(minutes) = self.config.gc_squash_within_minutes else {
+            return Ok(());
+        };
+
+        let bursts = self.squash_candidates(minutes)?;
+        println!("Found {} bursts of small snapshots to squash", bursts.len());
+
+        // 合并只会重写编号更大的快照，从最新的一段开始处理，较早的各段ID保持不变
+        for burst in bursts.iter().rev() {
+            let (first, last) = (&burst[0], &burst[burst.len() - 1]);
+            let label = format!(
+                "#{}..#{} ({} snapshots)",
+                first.number,
+                last.number,
+                burst.len()
+            );
+
+            if dry_run {
+                println!("Would squash {}", label);
+                continue;
+            }
+
+            let ids: Vec<String> = burst
+                .iter()
+                .map(|entry| entry.snapshot_id.clone())
+                .collect();
+            match self.squash_snapshots(&ids, None) {
+                Ok(id) => println!("Squashed {} into {}", label, crate::utils::short_id(&id)),
+                Err(e) => println!("Skipping {}: {}", label, e),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 找出连续创建的小快照：每个都是前一个的子快照，间隔不超过 minutes 分钟，
+    /// 变更文件数不超过 gc_squash_max_changes。受保护的快照只能作为一段的最后一个
+    fn squash_candidates(&self, minutes: u64) -> Result<Vec<Vec<crate::HistoryEntry>>> {
+        let mut history = self.snapshot_manager.list_history()?;
+        history.sort_by_key(|entry| entry.number);
+        let protected = self.protected_snapshots(true)?;
+        let window = chrono::Duration::minutes(minutes as i64);
+
+        let mut bursts = Vec::new();
+        let mut burst: Vec<crate::HistoryEntry> = Vec::new();
+
+        for (i, entry) in history.iter().enumerate() {
+            let small =
+                entry.added + entry.modified + entry.deleted <= self.config.gc_squash_max_changes;
+            let parent = entry
+                .parent
+                .clone()
+                .or_else(|| i.checked_sub(1).map(|p| history[p].snapshot_id.clone()));
+
+            let extends = burst.last().is_some_and(|previous| {
+                small
+                    && parent.as_deref() == Some(previous.snapshot_id.as_str())
+                    && entry.timestamp - previous.timestamp <= window
+                    && !protected.contains_key(&previous.snapshot_id)
+            });
+
+            if !extends {
+                if burst.len() >= 2 {
+                    bursts.push(std::mem::take(&mut burst));
+                }
+                burst.clear();
+                if !small {
+                    continue;
+                }
+            }
+            burst.push(entry.clone());
+        }
+        if burst.len() >= 2 {
+            bursts.push(burst);
+        }
+
+        Ok(bursts)
+    }
+
+    /// 按保留策略选出要删除的快照，试运行时打印每个快照的保留原因
+    fn expired_snapshots(&self, dry_run: bool) -> Result<Vec<String>> {
+        let policy = RetentionPolicy::from_config(&self.config);
+        let history = self.snapshot_manager.list_history()?;
+        let decisions = policy.evaluate(
+            &history,
+            &self.refs.tags_by_snapshot()?,
+            &self.protected_snapshots(false)?,
+        );
+
+        if dry_run {
+            policy.print_table(&decisions);
+        }
+
+        let expired: Vec<String> = decisions
+            .into_iter()
+            .filter(|decision| !decision.keep())
+            .map(|decision| decision.entry.snapshot_id)
+            .collect();
+
+        println!("Found {} snapshots to prune", expired.len());
+        Ok(expired)
+    }
+
     /// 激进模式的额外优化功能
     fn run_aggressive_optimizations(&mut self, dry_run: bool) -> Result<u64> {
         let mut total_freed = 0u64;
@@ -334,10 +807,7 @@ rustory-rollback/
         // 3. 优化索引文件
         self.optimize_index(dry_run)?;
 
-        // 4. 整理和合并相似快照
-        self.optimize_snapshots(dry_run)?;
-
-        // 5. 重新组织对象存储结构
+        // 4. 重新组织对象存储结构
         self.reorganize_object_storage(dry_run)?;
 
         Ok(total_freed)
@@ -351,17 +821,38 @@ rustory-rollback/
         let mut total_saved = 0u64;
         let mut recompressed_count = 0;
 
+        let target_codec = self.config.compression.codec;
+        let mut migrated_count = 0;
+
+        // 只有扩展名在跳过压缩列表中的文件保持不压缩，其余对象都转换为配置的压缩算法
+        let uncompressed = self.uncompressed_objects()?;
+
         for object_hash in &objects {
             if let Ok(original_size) = self.object_store.get_object_size(object_hash) {
-                // 只重新压缩较大的对象 (>1KB)
-                if original_size > 1024 {
+                // 压缩算法与目标不同的对象总是转换，其余只重新压缩较大的对象 (>1KB)
+                let codec = self.object_store.object_codec(object_hash)?;
+                let target = if uncompressed.contains(object_hash) {
+                    Codec::None
+                } else {
+                    target_codec
+                };
+                let needs_migration = codec != target;
+
+                if needs_migration || (original_size > 1024 && target != Codec::None) {
                     if !dry_run {
-                        if let Ok(new_size) = self.object_store.recompress_object(object_hash) {
+                        if let Ok(new_size) =
+                            self.object_store.recompress_object(object_hash, target)
+                        {
+                            if needs_migration {
+                                migrated_count += 1;
+                            }
                             if new_size < original_size {
                                 total_saved += original_size - new_size;
                                 recompressed_count += 1;
                             }
                         }
+                    } else if needs_migration {
+                        migrated_count += 1;
                     } else {
                         // 估算可能节省的空间 (假设能节省5-10%)
                         let estimated_saved = original_size / 20; // 5%估算
@@ -384,10 +875,58 @@ rustory-rollback/
             );
         }
 
+        if migrated_count > 0 {
+            println!(
+                "    {} {} objects to {}",
+                if dry_run {
+                    "Would convert"
+                } else {
+                    "Converted"
+                },
+                migrated_count,
+                target_codec.name()
+            );
+        }
+
         Ok(total_saved)
     }
 
-    /// 清理临时文件和碎片 - 更激进的清理
+    /// 只被跳过压缩的文件（按扩展名判断）引用的对象，包括这些文件的数据块
+    fn uncompressed_objects(&self) -> Result<std::collections::HashSet<String>> {
+        use std::collections::HashSet;
+
+        let mut snapshots = Vec::new();
+        for entry in self.snapshot_manager.list_history()? {
+            if let Ok(snapshot) = self.snapshot_manager.load_snapshot(&entry.snapshot_id) {
+                snapshots.push(snapshot);
+            }
+        }
+        for trashed in self.snapshot_manager.trash().list()? {
+            snapshots.push(trashed.snapshot);
+        }
+
+        let mut skipped = HashSet::new();
+        let mut compressed = HashSet::new();
+        for snapshot in &snapshots {
+            for (path, file_entry) in &snapshot.files {
+                let set = if self.object_store.skips_compression(path) {
+                    &mut skipped
+                } else {
+                    &mut compressed
+                };
+                // 超过大小限制而未存储的文件没有对象
+                if set.insert(file_entry.hash.clone())
+                    && let Ok(chunks) = self.object_store.chunk_refs(&file_entry.hash)
+                {
+                    set.extend(chunks.into_iter().map(|chunk| chunk.hash));
+                }
+            }
+        }
+
+        Ok(skipped.difference(&compressed).cloned().collect())
+    }
+
+    /// 清理 rustory 自身留下的临时文件、空的对象子目录和损坏的松散对象
     fn cleanup_fragments(&self, dry_run: bool) -> Result<u64> {
         println!("  Cleaning up temporary files and fragments...");
 
@@ -395,118 +934,81 @@ rustory-rollback/
         let mut files_cleaned = 0;
         let mut dirs_cleaned = 0;
 
-        // 扩展临时文件模式
-        let temp_patterns = [
-            ".tmp", ".temp", "~", ".bak", ".swp", ".swo", ".orig", ".rej", ".log", ".lock", ".pid",
-        ];
-
-        // 查找临时文件和目录
+        // 只查看 rustory 创建临时文件的位置，其余文件一律不碰
         for entry in walkdir::WalkDir::new(&self.rustory_dir) {
             let entry = entry?;
             let path = entry.path();
+            let Ok(relative) = path.strip_prefix(&self.rustory_dir) else {
+                continue;
+            };
 
             if entry.file_type().is_file() {
-                let filename = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
-
-                // 检查是否是临时文件
-                let is_temp = temp_patterns
-                    .iter()
-                    .any(|pattern| filename.ends_with(pattern) || filename.starts_with("."));
-
-                // 检查是否是过期的锁文件或日志文件
-                let is_expired = if filename.contains(".lock") || filename.contains(".log") {
-                    if let Ok(metadata) = std::fs::metadata(path) {
-                        if let Ok(modified) = metadata.modified() {
-                            if let Ok(age) = modified.elapsed() {
-                                age.as_secs() > 3600 // 超过1小时的锁文件/日志
-                            } else {
-                                false
-                            }
-                        } else {
-                            false
-                        }
-                    } else {
-                        false
-                    }
-                } else {
-                    false
-                };
+                if !is_temp_file(relative) || is_repository_file(relative) {
+                    continue;
+                }
 
-                if is_temp || is_expired {
-                    if let Ok(metadata) = std::fs::metadata(path) {
-                        total_cleaned += metadata.len();
-                        files_cleaned += 1;
+                // 正在运行的进程可能还在写入，只清理足够旧的临时文件
+                let metadata = entry.metadata()?;
+                let expired = metadata
+                    .modified()
+                    .ok()
+                    .and_then(|modified| modified.elapsed().ok())
+                    .is_some_and(|age| age.as_secs() > TEMP_FILE_GRACE_SECS);
+                if !expired {
+                    continue;
+                }
 
-                        if !dry_run {
-                            if let Err(e) = std::fs::remove_file(path) {
-                                println!("    Warning: Failed to remove {}: {}", path.display(), e);
-                            }
-                        }
-                    }
+                total_cleaned += metadata.len();
+                files_cleaned += 1;
+                if !dry_run && let Err(e) = fs::remove_file(path) {
+                    println!("    Warning: Failed to remove {}: {}", path.display(), e);
                 }
-            } else if entry.file_type().is_dir() && entry.depth() > 0 {
-                // 检查空目录
-                if let Ok(entries) = std::fs::read_dir(path) {
-                    if entries.count() == 0 {
-                        dirs_cleaned += 1;
-                        if !dry_run {
-                            if let Err(e) = std::fs::remove_dir(path) {
-                                println!(
-                                    "    Warning: Failed to remove empty directory {}: {}",
-                                    path.display(),
-                                    e
-                                );
-                            }
-                        }
+            } else if entry.file_type().is_dir() && is_object_fanout_dir(relative) {
+                // 对象删除后留下的空子目录
+                if let Ok(entries) = fs::read_dir(path)
+                    && entries.count() == 0
+                {
+                    dirs_cleaned += 1;
+                    if !dry_run && let Err(e) = fs::remove_dir(path) {
+                        println!(
+                            "    Warning: Failed to remove empty directory {}: {}",
+                            path.display(),
+                            e
+                        );
                     }
                 }
             }
         }
 
-        // 额外清理：查找损坏的对象文件
-        let objects_dir = self.rustory_dir.join("objects");
-        if objects_dir.exists() {
-            for entry in walkdir::WalkDir::new(&objects_dir) {
-                let entry = entry?;
-                if entry.file_type().is_file() {
-                    let path = entry.path();
-
-                    // 尝试验证对象文件的完整性
-                    if let Ok(data) = std::fs::read(path) {
-                        if data.is_empty() || data.len() < 10 {
-                            // 可能是损坏的对象文件
-                            if let Ok(metadata) = std::fs::metadata(path) {
-                                total_cleaned += metadata.len();
-                                files_cleaned += 1;
-
-                                if !dry_run {
-                                    if let Err(e) = std::fs::remove_file(path) {
-                                        println!(
-                                            "    Warning: Failed to remove corrupted object {}: {}",
-                                            path.display(),
-                                            e
-                                        );
-                                    }
-                                }
-                            }
-                        }
-                    }
+        // 只有无法解压或哈希校验失败的松散对象才视为损坏
+        let mut corrupted = 0;
+        for hash in self.object_store.list_loose_objects()? {
+            if let Err(e) = self.object_store.verify_object(&hash) {
+                println!("    Corrupted object {}: {}", hash, e);
+                total_cleaned += self.object_store.get_object_size(&hash).unwrap_or(0);
+                corrupted += 1;
+
+                if !dry_run && let Err(e) = self.object_store.remove_object(&hash) {
+                    println!(
+                        "    Warning: Failed to remove corrupted object {}: {}",
+                        hash, e
+                    );
                 }
             }
         }
 
         if dry_run {
             println!(
-                "    Would clean {} temporary files, {} bytes",
-                files_cleaned, total_cleaned
+                "    Would clean {} temporary files and {} corrupted objects, {} bytes",
+                files_cleaned, corrupted, total_cleaned
             );
             if dirs_cleaned > 0 {
                 println!("    Would remove {} empty directories", dirs_cleaned);
             }
         } else {
             println!(
-                "    Cleaned {} temporary files, {} bytes",
-                files_cleaned, total_cleaned
+                "    Cleaned {} temporary files and {} corrupted objects, {} bytes",
+                files_cleaned, corrupted, total_cleaned
             );
             if dirs_cleaned > 0 {
                 println!("    Removed {} empty directories", dirs_cleaned);
@@ -573,204 +1075,428 @@ rustory-rollback/
         Ok(())
     }
 
-    /// 优化快照 - 合并相似的快照和清理冗余数据
-    fn optimize_snapshots(&self, dry_run: bool) -> Result<()> {
-        println!("  Optimizing snapshots...");
-
-        // 获取所有快照
-        let history = self.snapshot_manager.list_history()?;
-        let mut optimized_count = 0;
-        let mut cleaned_metadata = 0;
-
-        // 1. 查找连续的相似快照（例如，只有很小变更的快照）
-        let mut candidates_for_merge = Vec::new();
-        for i in 1..history.len() {
-            let current = &history[i];
-            let previous = &history[i - 1];
-
-            // 如果快照变更很小（比如只有1-2个文件变更），标记为合并候选
-            let total_changes = current.added + current.modified + current.deleted;
-            if total_changes <= 2 {
-                optimized_count += 1;
-
-                // 检查时间间隔是否很短（小于5分钟）
-                let current_time = current.timestamp;
-                let prev_time = previous.timestamp;
-                let time_diff = current_time.timestamp() - prev_time.timestamp();
-                if time_diff < 300 {
-                    // 5分钟内
-                    candidates_for_merge.push(current.snapshot_id.clone());
-                }
-            }
-        }
-
-        // 2. 查找空快照或只有元数据变更的快照
-        for snapshot in &history {
-            let total_changes = snapshot.added + snapshot.modified + snapshot.deleted;
-            if total_changes == 0 {
-                cleaned_metadata += 1;
-            }
-        }
+    /// 报告对象存储结构：松散对象与打包文件
+    fn reorganize_object_storage(&self, dry_run: bool) -> Result<()> {
+        println!("  Reorganizing object storage structure...");
 
-        // 3. 检测重复的快照（相同的文件状态）
-        let mut state_hashes = std::collections::HashMap::new();
-        let mut duplicate_snapshots = Vec::new();
+        let loose_count = self.object_store.list_loose_objects()?.len();
+        let (pack_count, packed_count) = self.object_store.pack_summary();
 
-        for snapshot in &history {
-            // 创建快照状态的简单哈希
-            let state_key = format!(
-                "{}:{}:{}",
-                snapshot.added, snapshot.modified, snapshot.deleted
-            );
-            if let Some(existing_id) = state_hashes.get(&state_key) {
-                if existing_id != &snapshot.snapshot_id {
-                    duplicate_snapshots.push(snapshot.snapshot_id.clone());
-                }
-            } else {
-                state_hashes.insert(state_key, snapshot.snapshot_id.clone());
-            }
-        }
-
-        let merged_count = candidates_for_merge.len() + duplicate_snapshots.len();
+        println!(
+            "    Loose objects: {}, pack files: {} ({} packed objects)",
+            loose_count, pack_count, packed_count
+        );
 
-        if dry_run {
-            println!("    Would optimize {} small snapshots", optimized_count);
-            if merged_count > 0 {
-                println!(
-                    "    Would merge/remove {} redundant snapshots",
-                    merged_count
-                );
-            }
-            if cleaned_metadata > 0 {
-                println!("    Would clean {} empty snapshots", cleaned_metadata);
-            }
-        } else {
-            // 实际的快照合并和清理逻辑
-            if merged_count > 0 {
+        if loose_count > 0 {
+            if dry_run {
                 println!(
-                    "    Found {} snapshots that could be optimized (merge logic not yet implemented)",
-                    merged_count
+                    "    Would consolidate {} loose objects into a pack file",
+                    loose_count
                 );
-            }
-            if cleaned_metadata > 0 {
-                println!(
-                    "    Found {} empty snapshots for potential cleanup",
-                    cleaned_metadata
-                );
-            }
-            if optimized_count > 0 {
+            } else {
                 println!(
-                    "    Analyzed {} small snapshots for optimization",
-                    optimized_count
+                    "    {} loose objects will be consolidated into a pack file",
+                    loose_count
                 );
             }
+        } else {
+            println!("    Object storage is fully packed");
         }
 
         Ok(())
     }
+}
 
-    /// 重新组织对象存储结构 - 更激进的存储优化
-    fn reorganize_object_storage(&self, dry_run: bool) -> Result<()> {
-        println!("  Reorganizing object storage structure...");
+/// rustory 自身创建的临时文件（路径相对于 `.rustory/`）：
+/// - `objects/tmp/*`：写入中的对象
+/// - `objects/pack/tmp-pack-*`、`objects/pack/tmp-idx-*`：写入中的打包文件
+/// - `HEAD.tmp`、`history.log.tmp`、`refs/current.tmp`、`refs/*/.<name>.tmp`：原子替换时的临时文件
+fn is_temp_file(relative: &Path) -> bool {
+    let Some(name) = relative.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    let parent = relative.parent().unwrap_or(Path::new(""));
+
+    if parent == Path::new("objects/tmp") {
+        return true;
+    }
+    if parent == Path::new("objects/pack") {
+        return name.starts_with("tmp-pack-") || name.starts_with("tmp-idx-");
+    }
+    if ["HEAD.tmp", "history.log.tmp", "refs/current.tmp"]
+        .iter()
+        .any(|file| relative == Path::new(file))
+    {
+        return true;
+    }
+    (parent == Path::new("refs/tags")
+        || parent == Path::new("refs/timelines")
+        || parent == Path::new("trash"))
+        && name.starts_with('.')
+        && name.ends_with(".tmp")
+}
 
-        let objects_dir = &self.rustory_dir.join("objects");
+/// 仓库状态文件和快照、引用目录中的数据文件
+fn is_repository_file(relative: &Path) -> bool {
+    if REPOSITORY_FILES
+        .iter()
+        .any(|file| relative == Path::new(file))
+    {
+        return true;
+    }
 
-        // 统计每个子目录的文件数量
-        let mut dir_stats = std::collections::HashMap::new();
-        let mut total_objects = 0;
-        let mut large_dirs = Vec::new();
+    let hidden = relative
+        .file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|name| name.starts_with('.'));
+    !hidden
+        && REPOSITORY_DIRS
+            .iter()
+            .any(|dir| relative.parent() == Some(Path::new(dir)))
+}
 
-        for entry in walkdir::WalkDir::new(objects_dir).max_depth(2) {
-            let entry = entry?;
-            if entry.file_type().is_dir() && entry.depth() == 1 {
-                let count = std::fs::read_dir(entry.path())?.count();
-                total_objects += count;
-                if let Some(dir_name) = entry.path().file_name().and_then(|n| n.to_str()) {
-                    dir_stats.insert(dir_name.to_string(), count);
-                    if count > 100 {
-                        // 标记包含大量文件的目录
-                        large_dirs.push(dir_name.to_string());
-                    }
-                }
-            }
-        }
+/// 松散对象所在的两位十六进制子目录
+fn is_object_fanout_dir(relative: &Path) -> bool {
+    relative.parent() == Some(Path::new("objects"))
+        && relative
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|name| name.len() == 2 && name.chars().all(|c| c.is_ascii_hexdigit()))
+}
 
-        // 分析存储效率
-        let max_files = dir_stats.values().max().unwrap_or(&0);
-        let min_files = dir_stats.values().min().unwrap_or(&0);
-        let avg_files = if !dir_stats.is_empty() {
-            total_objects / dir_stats.len()
-        } else {
-            0
-        };
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::refs::{Tag, Timeline};
+    use crate::revision;
+    use crate::test_utils::{TempDir, commit, repo_with_snapshots};
 
-        // 检查是否有空目录
-        let mut empty_dirs = 0;
-        for count in dir_stats.values() {
-            if *count == 0 {
-                empty_dirs += 1;
-            }
+    fn number_of(repo: &Repository, spec: &str) -> usize {
+        let id = revision::resolve(repo, spec).unwrap();
+        repo.snapshot_manager.load_snapshot(&id).unwrap().number
+    }
+
+    fn id_of(repo: &Repository, number: usize) -> String {
+        revision::resolve(repo, &number.to_string()).unwrap()
+    }
+
+    /// 快照中各文件的内容哈希
+    fn files_of(repo: &Repository, id: &str) -> HashMap<PathBuf, String> {
+        repo.snapshot_manager
+            .load_snapshot(id)
+            .unwrap()
+            .files
+            .into_iter()
+            .map(|(path, entry)| (path, entry.hash))
+            .collect()
+    }
+
+    #[test]
+    fn squash_renumbers_later_snapshots_and_moves_refs() {
+        let dir = TempDir::new();
+        let repo = repo_with_snapshots(&dir, 5);
+        let old: Vec<String> = (1..=6).map(|number| id_of(&repo, number)).collect();
+        repo.refs.write_tag(&Tag::new("v1", &old[5], None)).unwrap();
+        repo.refs.write_tag(&Tag::new("v0", &old[3], None)).unwrap();
+        let (range_files, tip_files) = (files_of(&repo, &old[3]), files_of(&repo, &old[5]));
+
+        let squashed = repo.squash_snapshots(&old[1..4], None).unwrap();
+
+        // 合并后的快照使用第一个快照的编号、最后一个快照的文件和第一个快照的父快照
+        let snapshot = repo.snapshot_manager.load_snapshot(&squashed).unwrap();
+        assert_eq!(snapshot.number, 2);
+        assert_eq!(snapshot.parent.as_deref(), Some(old[0].as_str()));
+        assert_eq!(files_of(&repo, &squashed), range_files);
+        assert_eq!(snapshot.message, "snapshot 2; snapshot 3; snapshot 4");
+
+        // 之后的快照前移编号并以新ID重写，旧的快照文件被删除
+        let history = repo.snapshot_manager.list_history().unwrap();
+        let mut numbers: Vec<usize> = history.iter().map(|entry| entry.number).collect();
+        numbers.sort();
+        assert_eq!(numbers, [1, 2, 3, 4]);
+        let log = fs::read_to_string(repo.rustory_dir.join("history.log")).unwrap();
+        assert_eq!(log.lines().count(), 4);
+        assert!(log.contains(&old[0]));
+        for id in &old[1..] {
+            assert!(repo.snapshot_manager.load_snapshot(id).is_err());
+            assert!(!log.contains(id.as_str()));
         }
+        assert_eq!(
+            repo.snapshot_manager
+                .load_snapshot(&id_of(&repo, 3))
+                .unwrap()
+                .parent
+                .as_deref(),
+            Some(squashed.as_str())
+        );
 
-        if dry_run {
-            println!("    Would analyze and reorganize object storage structure");
-            println!(
-                "    Total objects: {}, Average per directory: {}",
-                total_objects, avg_files
-            );
-            if !large_dirs.is_empty() {
-                println!(
-                    "    Would rebalance {} directories with >100 files",
-                    large_dirs.len()
-                );
-            }
-            if empty_dirs > 0 {
-                println!("    Would remove {} empty directories", empty_dirs);
-            }
-        } else {
-            println!("    Object storage analysis:");
-            println!("      Total objects: {}", total_objects);
-            println!(
-                "      Directories: {} (min: {}, max: {}, avg: {})",
-                dir_stats.len(),
-                min_files,
-                max_files,
-                avg_files
-            );
+        // 标签、时间线和 HEAD 指向重写后的快照
+        let tip = id_of(&repo, 4);
+        assert_eq!(repo.refs.get_tag("v1").unwrap().unwrap().snapshot_id, tip);
+        assert_eq!(
+            repo.refs.get_tag("v0").unwrap().unwrap().snapshot_id,
+            squashed
+        );
+        assert_eq!(
+            repo.refs.get_timeline("main").unwrap().unwrap().snapshot_id,
+            tip
+        );
+        assert_eq!(
+            repo.snapshot_manager.head().unwrap().as_deref(),
+            Some(tip.as_str())
+        );
 
-            if !large_dirs.is_empty() {
-                println!(
-                    "    Found {} directories with >100 files that could benefit from rebalancing",
-                    large_dirs.len()
-                );
-            }
+        // 修订表达式沿新的父快照链解析
+        assert_eq!(number_of(&repo, "latest"), 4);
+        assert_eq!(number_of(&repo, "latest~1"), 3);
+        assert_eq!(number_of(&repo, "latest~2"), 2);
+        assert_eq!(number_of(&repo, "v1~3"), 1);
+        assert_eq!(number_of(&repo, "v0"), 2);
+        assert_eq!(revision::resolve(&repo, "v0").unwrap(), squashed);
 
-            if empty_dirs > 0 {
-                println!("    Found {} empty directories for cleanup", empty_dirs);
-                // 实际清理空目录
-                for entry in walkdir::WalkDir::new(objects_dir).max_depth(1) {
-                    let entry = entry?;
-                    if entry.file_type().is_dir()
-                        && entry.depth() == 1
-                        && std::fs::read_dir(entry.path())?.count() == 0
-                    {
-                        if let Err(e) = std::fs::remove_dir(entry.path()) {
-                            println!("    Warning: Failed to remove empty directory: {}", e);
-                        }
-                    }
-                }
-            }
+        // 重写后的快照内容不变
+        assert_eq!(files_of(&repo, &tip), tip_files);
+    }
 
-            if *max_files > 1000 {
-                println!(
-                    "    Warning: Some directories have many files ({}), monitoring recommended",
-                    max_files
-                );
-            } else {
-                println!("    Object storage structure is well-balanced");
-            }
-        }
+    #[test]
+    fn squash_refuses_ranges_that_would_lose_refs_or_lineage() {
+        let dir = TempDir::new();
+        let mut repo = repo_with_snapshots(&dir, 2);
+        let (two, three) = (id_of(&repo, 2), id_of(&repo, 3));
+
+        // exp 从 #2 创建：main 为 1 - 2 - 3，exp 为 2 - 4
+        repo.refs
+            .write_timeline(&Timeline::new("exp", &two))
+            .unwrap();
+        repo.refs.set_current_timeline("exp").unwrap();
+        repo.snapshot_manager.set_head(&two).unwrap();
+        let four = commit(&mut repo);
+
+        let error = repo
+            .squash_snapshots(&[three.clone(), four.clone()], None)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("timeline 'main' ends inside"), "{}", error);
+
+        // #3 的父快照是 #2，不是 #1
+        let error = repo
+            .squash_snapshots(&[id_of(&repo, 1), three.clone()], None)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("not a child"), "{}", error);
+
+        let error = repo
+            .squash_snapshots(&[two.clone(), three.clone()], None)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("inside the range"), "{}", error);
+
+        repo.refs
+            .write_tag(&Tag::new("keep", &id_of(&repo, 1), None))
+            .unwrap();
+        let error = repo
+            .squash_snapshots(&[id_of(&repo, 1), two.clone()], None)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("tag 'keep'"), "{}", error);
+
+        // 拒绝后历史保持不变
+        assert_eq!(repo.snapshot_manager.list_history().unwrap().len(), 4);
+        assert_eq!(number_of(&repo, "latest~1"), 2);
+    }
 
-        Ok(())
+    #[test]
+    fn auto_squash_keeps_protected_snapshots_at_burst_ends() {
+        let dir = TempDir::new();
+        let mut repo = repo_with_snapshots(&dir, 5);
+        repo.config.gc_squash_within_minutes = Some(60);
+        repo.config.gc_squash_max_changes = 10;
+        let tagged = id_of(&repo, 3);
+        let tagged_files = files_of(&repo, &tagged);
+        repo.refs.write_tag(&Tag::new("v1", &tagged, None)).unwrap();
+
+        // 带标签的 #3 只能作为一段的最后一个
+        let bursts: Vec<Vec<usize>> = repo
+            .squash_candidates(60)
+            .unwrap()
+            .iter()
+            .map(|burst| burst.iter().map(|entry| entry.number).collect())
+            .collect();
+        assert_eq!(bursts, [vec![1, 2, 3], vec![4, 5, 6]]);
+
+        repo.auto_squash(false).unwrap();
+
+        let history = repo.snapshot_manager.list_history().unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(number_of(&repo, "v1"), 1);
+        assert_eq!(files_of(&repo, &id_of(&repo, 1)), tagged_files);
+        assert_eq!(number_of(&repo, "latest"), 2);
+        assert_eq!(number_of(&repo, "latest~1"), 1);
+    }
+
+    #[test]
+    fn undelete_reinserts_snapshot_and_relinks_children() {
+        let dir = TempDir::new();
+        let repo = repo_with_snapshots(&dir, 4);
+        let dropped = id_of(&repo, 3);
+        let files = files_of(&repo, &dropped);
+
+        repo.drop_snapshots(std::slice::from_ref(&dropped), false)
+            .unwrap();
+        assert_eq!(repo.snapshot_manager.list_history().unwrap().len(), 4);
+        assert_eq!(number_of(&repo, "4~1"), 2);
+        assert!(repo.snapshot_manager.trash().get(&dropped).is_ok());
+
+        let entry = repo.undelete_snapshot(&dropped).unwrap();
+
+        // 恢复后编号和ID不变，后续快照重新以它为父快照
+        assert_eq!(entry.number, 3);
+        assert_eq!(id_of(&repo, 3), dropped);
+        assert_eq!(files_of(&repo, &dropped), files);
+        assert_eq!(number_of(&repo, "4~1"), 3);
+        assert_eq!(number_of(&repo, "latest~2"), 3);
+        assert_eq!(number_of(&repo, "3~1"), 2);
+        assert!(repo.snapshot_manager.trash().list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn undelete_restores_tags_dropped_with_the_snapshot() {
+        let dir = TempDir::new();
+        let repo = repo_with_snapshots(&dir, 4);
+        let dropped = id_of(&repo, 3);
+        repo.refs
+            .write_tag(&Tag::new("v1", &dropped, None))
+            .unwrap();
+        repo.refs
+            .write_tag(&Tag::new("v2", &dropped, None))
+            .unwrap();
+
+        repo.drop_snapshots(std::slice::from_ref(&dropped), false)
+            .unwrap();
+        assert!(repo.refs.get_tag("v1").unwrap().is_none());
+        let mut trashed: Vec<String> = repo
+            .snapshot_manager
+            .trash()
+            .get(&dropped)
+            .unwrap()
+            .tags
+            .into_iter()
+            .map(|tag| tag.name)
+            .collect();
+        trashed.sort();
+        assert_eq!(trashed, ["v1", "v2"]);
+
+        // 期间被重新使用的标签名保持不变
+        let other = id_of(&repo, 2);
+        repo.refs.write_tag(&Tag::new("v2", &other, None)).unwrap();
+        repo.undelete_snapshot(&dropped).unwrap();
+
+        assert_eq!(
+            repo.refs.get_tag("v1").unwrap().unwrap().snapshot_id,
+            dropped
+        );
+        assert_eq!(repo.refs.get_tag("v2").unwrap().unwrap().snapshot_id, other);
+    }
+
+    #[test]
+    fn undelete_refuses_reused_numbers() {
+        let dir = TempDir::new();
+        let mut repo = repo_with_snapshots(&dir, 4);
+        let dropped = id_of(&repo, 5);
+
+        repo.drop_snapshots(std::slice::from_ref(&dropped), false)
+            .unwrap();
+        let replacement = commit(&mut repo);
+        assert_eq!(id_of(&repo, 5), replacement);
+
+        let err = repo.undelete_snapshot(&dropped).unwrap_err();
+        assert!(err.to_string().contains("#5 is now used"), "{}", err);
+        // 恢复失败时快照留在回收站中，历史记录不变
+        assert!(repo.snapshot_manager.trash().get(&dropped).is_ok());
+        assert_eq!(repo.snapshot_manager.list_history().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn purge_removes_snapshots_past_the_grace_period() {
+        let dir = TempDir::new();
+        let mut repo = repo_with_snapshots(&dir, 4);
+        let (old, recent) = (id_of(&repo, 2), id_of(&repo, 4));
+        repo.drop_snapshots(&[old.clone(), recent.clone()], false)
+            .unwrap();
+
+        let trash = repo.snapshot_manager.trash();
+        let mut trashed = trash.get(&old).unwrap();
+        trashed.deleted_at -= chrono::Duration::days(repo.config.trash_grace_days as i64 + 1);
+        trash.put(&trashed).unwrap();
+
+        assert_eq!(repo.purge_trash(true).unwrap(), vec![old.clone()]);
+        assert!(trash.get(&old).is_ok());
+        assert_eq!(repo.purge_trash(false).unwrap(), vec![old.clone()]);
+        assert!(trash.get(&old).is_err());
+        assert!(repo.undelete_snapshot(&old).is_err());
+        assert!(trash.get(&recent).is_ok());
+
+        // 宽限期为 0 时回收站中的快照立即过期
+        repo.config.trash_grace_days = 0;
+        assert_eq!(repo.purge_trash(false).unwrap(), vec![recent.clone()]);
+        assert!(repo.snapshot_manager.trash().list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn open_recovers_interrupted_snapshot_drops() {
+        let dir = TempDir::new();
+        let repo = repo_with_snapshots(&dir, 4);
+        let (kept, dropped) = (id_of(&repo, 2), id_of(&repo, 5));
+        repo.drop_snapshots(std::slice::from_ref(&dropped), false)
+            .unwrap();
+
+        // 模拟删除 #5 时在清理暂存文件前中断，删除 #2 时在重写历史记录前中断
+        let snapshots_dir = repo.rustory_dir.join("snapshots");
+        fs::write(
+            snapshots_dir.join(format!("{}.json.dropped", dropped)),
+            "{}",
+        )
+        .unwrap();
+        let entry = repo
+            .snapshot_manager
+            .list_history()
+            .unwrap()
+            .into_iter()
+            .find(|entry| entry.snapshot_id == kept)
+            .unwrap();
+        let snapshot = repo.snapshot_manager.load_snapshot(&kept).unwrap();
+        repo.snapshot_manager
+            .trash()
+            .put(&crate::trash::TrashedSnapshot {
+                deleted_at: chrono::Utc::now(),
+                entry,
+                snapshot,
+                tags: Vec::new(),
+            })
+            .unwrap();
+        fs::rename(
+            snapshots_dir.join(format!("{}.json", kept)),
+            snapshots_dir.join(format!("{}.json.dropped", kept)),
+        )
+        .unwrap();
+
+        let repo = Repository::new(dir.0.clone()).unwrap();
+
+        assert!(repo.snapshot_manager.load_snapshot(&kept).is_ok());
+        assert_eq!(number_of(&repo, "3~1"), 2);
+        assert!(repo.snapshot_manager.trash().get(&kept).is_err());
+        assert!(repo.snapshot_manager.trash().get(&dropped).is_ok());
+        let leftovers = fs::read_dir(&snapshots_dir)
+            .unwrap()
+            .filter(|entry| {
+                entry
+                    .as_ref()
+                    .unwrap()
+                    .file_name()
+                    .to_string_lossy()
+                    .ends_with(".dropped")
+            })
+            .count();
+        assert_eq!(leftovers, 0);
     }
 }
@@ -0,0 +1,420 @@
+use anyhow::Result;
+use colored::*;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::diff_engine::DiffEngine;
+use crate::index::IndexManager;
+use crate::objects::ObjectStore;
+use crate::{FileEntry, Index};
+
+/// 恢复时绝不触碰的目录（即使出现在快照或索引中）
+const PROTECTED_DIRS: &[&str] = &[".rustory", "rustory-rollback", ".git"];
+
+/// 冲突标记的起始行
+const CONFLICT_MARKER: &str = "<<<<<<< ";
+
+/// 合并恢复的结果
+#[derive(Debug, Default)]
+pub struct MergeOutcome {
+    /// 本地修改与快照内容成功合并的文件
+    pub merged: Vec<PathBuf>,
+    /// 写入了冲突标记的文件
+    pub conflicts: Vec<PathBuf>,
+    /// 无法合并（二进制文件，或快照中已删除）而保留本地版本的文件
+    pub kept: Vec<PathBuf>,
+}
+
+/// 未解决的合并冲突，保存在 `.rustory/conflicts.json`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Conflicts {
+    pub snapshot_id: String,
+    pub files: Vec<PathBuf>,
+}
+
+impl Conflicts {
+    fn path(rustory_dir: &Path) -> PathBuf {
+        rustory_dir.join("conflicts.json")
+    }
+
+    pub fn load(rustory_dir: &Path) -> Result<Self> {
+        let path = Self::path(rustory_dir);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, rustory_dir: &Path) -> Result<()> {
+        let path = Self::path(rustory_dir);
+        if self.files.is_empty() {
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+            return Ok(());
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// 仍含有冲突标记的文件；已解决的条目会从记录中移除
+    pub fn unresolved(root: &Path) -> Result<Vec<PathBuf>> {
+        let rustory_dir = root.join(".rustory");
+        let mut conflicts = Self::load(&rustory_dir)?;
+        if conflicts.files.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let before = conflicts.files.len();
+        conflicts.files.retain(|path| {
+            fs::read_to_string(root.join(path))
+                .map(|content| {
+                    content
+                        .lines()
+                        .any(|line| line.starts_with(CONFLICT_MARKER))
+                })
+                .unwrap_or(false)
+        });
+        if conflicts.files.len() != before {
+            conflicts.save(&rustory_dir)?;
+        }
+
+        Ok(conflicts.files)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestoreAction {
+    Create,
+    Overwrite,
+    Delete,
+}
+
+impl RestoreAction {
+    fn label(self) -> ColoredString {
+        match self {
+            RestoreAction::Create => "create   ".green(),
+            RestoreAction::Overwrite => "overwrite".yellow(),
+            RestoreAction::Delete => "delete   ".red(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PlannedChange {
+    pub path: PathBuf,
+    pub action: RestoreAction,
+    /// 要写入的对象哈希，删除时没有
+    pub hash: Option<String>,
+}
+
+/// 恢复计划
+///
+/// 只涉及目标快照或当前索引中记录的路径：目标快照中的文件被创建或覆盖，
+/// 只在当前索引中的文件被删除。未跟踪的文件和匹配忽略规则的文件保持不动。
+#[derive(Debug, Default)]
+pub struct RestorePlan {
+    pub changes: Vec<PlannedChange>,
+    /// 内容已与目标快照一致、无需改动的文件数
+    pub unchanged: usize,
+    /// 因匹配忽略规则或位于受保护目录而跳过的文件
+    pub skipped: Vec<PathBuf>,
+}
+
+impl RestorePlan {
+    /// 比较磁盘上的文件、当前索引（tracked）和目标快照（target）生成恢复计划
+    pub fn build(
+        root: &Path,
+        target: &HashMap<PathBuf, FileEntry>,
+        index_manager: &IndexManager,
+        tracked: &Index,
+    ) -> Result<Self> {
+        let matcher = ignore_matcher(root)?;
+        let mut plan = RestorePlan::default();
+
+        let paths: BTreeSet<&PathBuf> = target.keys().chain(tracked.files.keys()).collect();
+        for path in paths {
+            if is_protected(path) || matcher.matched_path_or_any_parents(path, false).is_ignore() {
+                plan.skipped.push(path.clone());
+                continue;
+            }
+
+            let full_path = root.join(path);
+            let on_disk = full_path.is_file();
+
+            match target.get(path) {
+                Some(entry) => {
+                    if !on_disk {
+                        plan.push(path, RestoreAction::Create, Some(&entry.hash));
+                    } else if index_manager.current_hash(tracked, root, path)? == entry.hash {
+                        plan.unchanged += 1;
+                    } else {
+                        plan.push(path, RestoreAction::Overwrite, Some(&entry.hash));
+                    }
+                }
+                None if on_disk => plan.push(path, RestoreAction::Delete, None),
+                None => {}
+            }
+        }
+
+        Ok(plan)
+    }
+
+    fn push(&mut self, path: &Path, action: RestoreAction, hash: Option<&str>) {
+        self.changes.push(PlannedChange {
+            path: path.to_path_buf(),
+            action,
+            hash: hash.map(|hash| hash.to_string()),
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    fn count(&self, action: RestoreAction) -> usize {
+        self.changes
+            .iter()
+            .filter(|change| change.action == action)
+            .count()
+    }
+
+    /// 打印恢复计划
+    pub fn print(&self) {
+        for change in &self.changes {
+            println!("  {} {}", change.action.label(), change.path.display());
+        }
+        for path in &self.skipped {
+            println!("  {} {}", "skip     ".dimmed(), path.display());
+        }
+
+        println!(
+            "{} to create, {} to overwrite, {} to delete, {} unchanged",
+            self.count(RestoreAction::Create),
+            self.count(RestoreAction::Overwrite),
+            self.count(RestoreAction::Delete),
+            self.unchanged
+        );
+    }
+
+    /// 备份将被覆盖或删除的文件，返回备份的文件数
+    pub fn backup(&self, root: &Path, backup_dir: &Path) -> Result<usize> {
+        let mut count = 0;
+        for change in &self.changes {
+            if change.action == RestoreAction::Create {
+                continue;
+            }
+
+            let backup_path = backup_dir.join(&change.path);
+            if let Some(parent) = backup_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(root.join(&change.path), backup_path)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// 执行恢复计划，删除文件后清理留下的空目录
+    pub fn apply(&self, root: &Path, object_store: &ObjectStore) -> Result<()> {
+        let mut emptied_dirs = BTreeSet::new();
+
+        for change in &self.changes {
+            let full_path = root.join(&change.path);
+            match (&change.action, &change.hash) {
+                (RestoreAction::Delete, _) => {
+                    fs::remove_file(&full_path)?;
+                    if let Some(parent) = full_path.parent() {
+                        emptied_dirs.insert(parent.to_path_buf());
+                    }
+                }
+                (_, Some(hash)) => object_store.restore_file(hash, &full_path)?,
+                (_, None) => {}
+            }
+        }
+
+        // 从最深的目录开始，逐级删除已经变空的目录（不删除仓库根目录）
+        for dir in emptied_dirs.iter().rev() {
+            let mut current = dir.as_path();
+            while current != root && current.starts_with(root) {
+                if fs::remove_dir(current).is_err() {
+                    break;
+                }
+                match current.parent() {
+                    Some(parent) => current = parent,
+                    None => break,
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 以合并方式执行恢复计划：索引中的版本为基础，工作区文件为本地版本，快照中的文件为目标版本。
+    /// 没有本地修改的文件按普通方式恢复
+    pub fn apply_merge(
+        &self,
+        root: &Path,
+        object_store: &ObjectStore,
+        index_manager: &IndexManager,
+        tracked: &Index,
+        theirs_label: &str,
+    ) -> Result<MergeOutcome> {
+        let mut outcome = MergeOutcome::default();
+        let mut plain = RestorePlan::default();
+
+        for change in &self.changes {
+            let full_path = root.join(&change.path);
+            let base = tracked.files.get(&change.path);
+
+            // 文件不存在或与索引一致时没有本地修改；
+            // 与扫描时一样检查 racy 和 inode/ctime，否则同一时间戳内的修改会被当成未修改而覆盖
+            let locally_modified = change.action != RestoreAction::Create
+                && base.map(|entry| entry.hash.as_str())
+                    != Some(
+                        index_manager
+                            .current_hash(tracked, root, &change.path)?
+                            .as_str(),
+                    );
+            if !locally_modified {
+                plain.changes.push(change.clone());
+                continue;
+            }
+
+            let Some(hash) = &change.hash else {
+                // 快照中已删除、本地有修改：保留本地版本
+                outcome.kept.push(change.path.clone());
+                continue;
+            };
+
+            let ours = fs::read(&full_path)?;
+            let theirs = object_store.get_content(hash)?;
+            let base = match base {
+                Some(entry) => object_store.get_content(&entry.hash)?,
+                None => Vec::new(),
+            };
+
+            if [&base, &ours, &theirs]
+                .iter()
+                .any(|content| DiffEngine::is_binary(content))
+            {
+                outcome.kept.push(change.path.clone());
+                continue;
+            }
+
+            let result = DiffEngine::merge3(&base, &ours, &theirs, "local", theirs_label);
+            fs::write(&full_path, result.content)?;
+
+            if result.conflicts > 0 {
+                outcome.conflicts.push(change.path.clone());
+            } else {
+                outcome.merged.push(change.path.clone());
+            }
+        }
+
+        plain.apply(root, object_store)?;
+        Ok(outcome)
+    }
+}
+
+/// 读取 `.rustory/ignore` 中的忽略规则
+fn ignore_matcher(root: &Path) -> Result<Gitignore> {
+    let mut builder = GitignoreBuilder::new(root);
+    let ignore_path = root.join(".rustory").join("ignore");
+    if ignore_path.exists()
+        && let Some(e) = builder.add(ignore_path)
+    {
+        return Err(e.into());
+    }
+    Ok(builder.build()?)
+}
+
+fn is_protected(path: &Path) -> bool {
+    PROTECTED_DIRS.iter().any(|dir| path.starts_with(dir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TempDir;
+    use std::time::{Duration, SystemTime};
+
+    fn write_with_mtime(path: &Path, content: &str, mtime: SystemTime) {
+        fs::write(path, content).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+    }
+
+    #[test]
+    fn plan_detects_edit_with_unchanged_size_and_mtime() {
+        let dir = TempDir::new();
+        let root = dir.0.as_path();
+        fs::create_dir_all(root.join(".rustory")).unwrap();
+        let index_manager = IndexManager::new(root.join(".rustory").join("index.json"));
+
+        // 文件的修改时间早于索引写入时间，索引中的哈希可以直接复用
+        let mtime = SystemTime::now() - Duration::from_secs(60);
+        write_with_mtime(&root.join("edited.txt"), "aaaa\n", mtime);
+        write_with_mtime(&root.join("same.txt"), "same\n", mtime);
+        let index = index_manager
+            .scan_directory(root, &Gitignore::empty())
+            .unwrap();
+        index_manager.save(&index).unwrap();
+        let target = index.files.clone();
+
+        // 写入相同长度的内容并恢复修改时间，只有 inode/ctime 能发现变化
+        write_with_mtime(&root.join("edited.txt"), "bbbb\n", mtime);
+
+        let tracked = index_manager.load().unwrap();
+        let plan = RestorePlan::build(root, &target, &index_manager, &tracked).unwrap();
+        assert_eq!(plan.unchanged, 1);
+        assert_eq!(plan.changes.len(), 1);
+        assert_eq!(plan.changes[0].path, Path::new("edited.txt"));
+        assert_eq!(plan.changes[0].action, RestoreAction::Overwrite);
+    }
+
+    #[test]
+    fn plan_creates_missing_and_deletes_untargeted_files() {
+        let dir = TempDir::new();
+        let root = dir.0.as_path();
+        fs::create_dir_all(root.join(".rustory")).unwrap();
+        let index_manager = IndexManager::new(root.join(".rustory").join("index.json"));
+
+        fs::write(root.join("old.txt"), "old\n").unwrap();
+        fs::write(root.join("new.txt"), "new\n").unwrap();
+        let index = index_manager
+            .scan_directory(root, &Gitignore::empty())
+            .unwrap();
+        index_manager.save(&index).unwrap();
+
+        // 目标快照只有 new.txt，且磁盘上的 new.txt 已被删除
+        let target: HashMap<PathBuf, FileEntry> = index
+            .files
+            .iter()
+            .filter(|(path, _)| path.as_path() == Path::new("new.txt"))
+            .map(|(path, entry)| (path.clone(), entry.clone()))
+            .collect();
+        fs::remove_file(root.join("new.txt")).unwrap();
+
+        let plan = RestorePlan::build(root, &target, &index_manager, &index).unwrap();
+        let actions: Vec<(&Path, RestoreAction)> = plan
+            .changes
+            .iter()
+            .map(|change| (change.path.as_path(), change.action))
+            .collect();
+        assert_eq!(
+            actions,
+            [
+                (Path::new("new.txt"), RestoreAction::Create),
+                (Path::new("old.txt"), RestoreAction::Delete),
+            ]
+        );
+    }
+}
@@ -0,0 +1,371 @@
+use chrono::{DateTime, Duration, FixedOffset, Local, Utc};
+use colored::*;
+use std::collections::HashMap;
+
+use crate::HistoryEntry;
+use crate::config::Config;
+use crate::utils;
+
+/// 按时间段保留快照的规则
+#[derive(Debug, Clone, Copy)]
+enum Period {
+    Hourly,
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+impl Period {
+    fn name(self) -> &'static str {
+        match self {
+            Period::Hourly => "hourly",
+            Period::Daily => "daily",
+            Period::Weekly => "weekly",
+            Period::Monthly => "monthly",
+            Period::Yearly => "yearly",
+        }
+    }
+
+    /// 快照所属的时间段，同一时间段内只保留最新的快照
+    fn bucket(self, time: &DateTime<FixedOffset>) -> String {
+        let format = match self {
+            Period::Hourly => "%Y-%m-%d %H",
+            Period::Daily => "%Y-%m-%d",
+            Period::Weekly => "%G-W%V",
+            Period::Monthly => "%Y-%m",
+            Period::Yearly => "%Y",
+        };
+        time.format(format).to_string()
+    }
+}
+
+/// 单个快照的保留结果，reasons 为空表示删除
+#[derive(Debug)]
+pub struct Decision {
+    pub entry: HistoryEntry,
+    pub reasons: Vec<String>,
+}
+
+impl Decision {
+    pub fn keep(&self) -> bool {
+        !self.reasons.is_empty()
+    }
+}
+
+/// 快照保留策略
+///
+/// 各规则取并集：快照只要被任意一条规则保留就不会被删除。
+/// 未配置 `[retention]` 时沿用旧的 `gc_keep_snapshots` 和 `gc_keep_days`：
+/// 只保留最新的 N 个快照中不早于 D 天的那些
+#[derive(Debug)]
+pub struct RetentionPolicy {
+    keep_last: Option<usize>,
+    periods: Vec<(Period, usize)>,
+    keep_tagged: bool,
+    /// 旧配置 gc_keep_days：早于此时间的快照不被 keep_last 保留
+    max_age: Option<(u32, DateTime<Utc>)>,
+    use_local_timezone: bool,
+}
+
+impl RetentionPolicy {
+    pub fn from_config(config: &Config) -> Self {
+        let retention = &config.retention;
+
+        if !retention.is_configured() {
+            let keep_days = config.gc_keep_days.unwrap_or(30);
+            return Self {
+                keep_last: Some(config.gc_keep_snapshots.unwrap_or(50)),
+                periods: Vec::new(),
+                keep_tagged: true,
+                max_age: Some((keep_days, Utc::now() - Duration::days(keep_days as i64))),
+                use_local_timezone: config.use_local_timezone,
+            };
+        }
+
+        let periods = [
+            (Period::Hourly, retention.keep_hourly),
+            (Period::Daily, retention.keep_daily),
+            (Period::Weekly, retention.keep_weekly),
+            (Period::Monthly, retention.keep_monthly),
+            (Period::Yearly, retention.keep_yearly),
+        ]
+        .into_iter()
+        .filter_map(|(period, count)| count.map(|count| (period, count)))
+        .collect();
+
+        Self {
+            keep_last: retention.keep_last,
+            periods,
+            keep_tagged: retention.keep_tagged,
+            max_age: None,
+            use_local_timezone: config.use_local_timezone,
+        }
+    }
+
+    /// 对历史中的每个快照（从新到旧）给出保留原因；
+    /// pinned 中的快照（HEAD、时间线末端等）总是保留
+    pub fn evaluate(
+        &self,
+        history: &[HistoryEntry],
+        tags: &HashMap<String, Vec<String>>,
+        pinned: &HashMap<String, Vec<String>>,
+    ) -> Vec<Decision> {
+        let mut remaining: Vec<usize> = self.periods.iter().map(|(_, count)| *count).collect();
+        let mut last_buckets: Vec<Option<String>> = vec![None; self.periods.len()];
+
+        let mut sorted = history.to_vec();
+        sorted.sort_by(|a, b| b.timestamp.cmp(&a.timestamp).then(b.number.cmp(&a.number)));
+
+        sorted
+            .into_iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let mut reasons = Vec::new();
+
+                if let Some(count) = self.keep_last
+                    && i < count
+                {
+                    match self.max_age {
+                        Some((days, cutoff)) if entry.timestamp >= cutoff => {
+                            reasons.push(format!("last {} within {} days", count, days))
+                        }
+                        Some(_) => {}
+                        None => reasons.push(format!("last {}", count)),
+                    }
+                }
+
+                // 每个时间段中最新的快照被保留，直到该规则的数量用完
+                let time = self.local_time(&entry.timestamp);
+                for (rule, (period, count)) in self.periods.iter().enumerate() {
+                    let bucket = period.bucket(&time);
+                    if remaining[rule] > 0 && last_buckets[rule].as_ref() != Some(&bucket) {
+                        remaining[rule] -= 1;
+                        reasons.push(format!(
+                            "{} {}/{}",
+                            period.name(),
+                            count - remaining[rule],
+                            count
+                        ));
+                        last_buckets[rule] = Some(bucket);
+                    }
+                }
+
+                if self.keep_tagged
+                    && let Some(names) = tags.get(&entry.snapshot_id)
+                {
+                    reasons.extend(names.iter().map(|name| format!("tag {}", name)));
+                }
+                if let Some(pinned) = pinned.get(&entry.snapshot_id) {
+                    reasons.extend(pinned.iter().cloned());
+                }
+
+                Decision { entry, reasons }
+            })
+            .collect()
+    }
+
+    /// 以表格形式说明每个快照被哪些规则保留
+    pub fn print_table(&self, decisions: &[Decision]) {
+        println!(
+            "{:<5} {:<8} {:<16} {:<6} Reason",
+            "#", "ID", "Time", "Action"
+        );
+        println!("{}", "-".repeat(68));
+
+        for decision in decisions {
+            let entry = &decision.entry;
+            let action = if decision.keep() {
+                format!("{:<6}", "keep").green()
+            } else {
+                format!("{:<6}", "remove").red()
+            };
+            println!(
+                "{:<5} {:<8} {:<16} {} {}",
+                entry.number,
+                utils::short_id(&entry.snapshot_id),
+                self.local_time(&entry.timestamp).format("%Y-%m-%d %H:%M"),
+                action,
+                decision.reasons.join(", ")
+            );
+        }
+
+        let kept = decisions.iter().filter(|d| d.keep()).count();
+        println!(
+            "{} snapshots kept, {} to remove",
+            kept,
+            decisions.len() - kept
+        );
+    }
+
+    fn local_time(&self, time: &DateTime<Utc>) -> DateTime<FixedOffset> {
+        if self.use_local_timezone {
+            time.with_timezone(&Local).fixed_offset()
+        } else {
+            time.fixed_offset()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(number: usize, time: &str) -> HistoryEntry {
+        HistoryEntry {
+            snapshot_id: format!("{:040}", number),
+            number,
+            timestamp: DateTime::parse_from_rfc3339(time)
+                .unwrap()
+                .with_timezone(&Utc),
+            added: 0,
+            modified: 0,
+            deleted: 0,
+            message: String::new(),
+            parent: None,
+            tags: Vec::new(),
+        }
+    }
+
+    fn policy(keep_last: Option<usize>, periods: Vec<(Period, usize)>) -> RetentionPolicy {
+        RetentionPolicy {
+            keep_last,
+            periods,
+            keep_tagged: true,
+            max_age: None,
+            use_local_timezone: false,
+        }
+    }
+
+    /// 保留的快照编号，从新到旧
+    fn kept(decisions: &[Decision]) -> Vec<usize> {
+        decisions
+            .iter()
+            .filter(|d| d.keep())
+            .map(|d| d.entry.number)
+            .collect()
+    }
+
+    fn evaluate(policy: &RetentionPolicy, history: &[HistoryEntry]) -> Vec<Decision> {
+        policy.evaluate(history, &HashMap::new(), &HashMap::new())
+    }
+
+    #[test]
+    fn daily_keeps_newest_per_day() {
+        let history = [
+            entry(1, "2026-10-01T08:00:00Z"),
+            entry(2, "2026-10-01T18:00:00Z"),
+            entry(3, "2026-10-02T09:00:00Z"),
+            entry(4, "2026-10-03T07:00:00Z"),
+            entry(5, "2026-10-03T21:00:00Z"),
+        ];
+
+        let decisions = evaluate(&policy(None, vec![(Period::Daily, 2)]), &history);
+        assert_eq!(kept(&decisions), [5, 3]);
+        assert_eq!(decisions[0].reasons, ["daily 1/2"]);
+        assert_eq!(decisions[2].reasons, ["daily 2/2"]);
+
+        // 数量足够时每天各保留一个
+        let decisions = evaluate(&policy(None, vec![(Period::Daily, 10)]), &history);
+        assert_eq!(kept(&decisions), [5, 3, 2]);
+    }
+
+    #[test]
+    fn weekly_uses_iso_weeks() {
+        // 2025-12-29（周一）和 2026-01-01 同属 2026-W01，2025-12-28 属于 2025-W52
+        let history = [
+            entry(1, "2025-12-28T12:00:00Z"),
+            entry(2, "2025-12-29T12:00:00Z"),
+            entry(3, "2026-01-01T12:00:00Z"),
+        ];
+
+        let decisions = evaluate(&policy(None, vec![(Period::Weekly, 5)]), &history);
+        assert_eq!(kept(&decisions), [3, 1]);
+    }
+
+    #[test]
+    fn monthly_and_yearly_buckets() {
+        let history = [
+            entry(1, "2024-06-15T00:00:00Z"),
+            entry(2, "2025-03-01T00:00:00Z"),
+            entry(3, "2025-03-20T00:00:00Z"),
+            entry(4, "2025-11-02T00:00:00Z"),
+        ];
+
+        let monthly = evaluate(&policy(None, vec![(Period::Monthly, 12)]), &history);
+        assert_eq!(kept(&monthly), [4, 3, 1]);
+
+        let yearly = evaluate(&policy(None, vec![(Period::Yearly, 1)]), &history);
+        assert_eq!(kept(&yearly), [4]);
+    }
+
+    #[test]
+    fn rules_are_combined() {
+        let history = [
+            entry(1, "2026-08-10T00:00:00Z"),
+            entry(2, "2026-09-10T00:00:00Z"),
+            entry(3, "2026-10-01T00:00:00Z"),
+            entry(4, "2026-10-02T00:00:00Z"),
+            entry(5, "2026-10-03T00:00:00Z"),
+        ];
+
+        let decisions = evaluate(&policy(Some(2), vec![(Period::Monthly, 3)]), &history);
+        assert_eq!(kept(&decisions), [5, 4, 2, 1]);
+        assert_eq!(decisions[0].reasons, ["last 2", "monthly 1/3"]);
+        assert_eq!(decisions[1].reasons, ["last 2"]);
+        assert!(decisions[2].reasons.is_empty());
+    }
+
+    #[test]
+    fn orders_by_time_not_input_order() {
+        // 输入顺序打乱，结果仍从新到旧
+        let history = [
+            entry(2, "2026-10-02T00:00:00Z"),
+            entry(3, "2026-10-03T00:00:00Z"),
+            entry(1, "2026-10-01T00:00:00Z"),
+        ];
+
+        let decisions = evaluate(&policy(Some(1), Vec::new()), &history);
+        let order: Vec<usize> = decisions.iter().map(|d| d.entry.number).collect();
+        assert_eq!(order, [3, 2, 1]);
+        assert_eq!(kept(&decisions), [3]);
+    }
+
+    #[test]
+    fn tags_and_pinned_are_always_kept() {
+        let history = [
+            entry(1, "2026-10-01T00:00:00Z"),
+            entry(2, "2026-10-02T00:00:00Z"),
+            entry(3, "2026-10-03T00:00:00Z"),
+        ];
+        let tags = HashMap::from([(history[0].snapshot_id.clone(), vec!["v1.0".to_string()])]);
+        let pinned = HashMap::from([(history[1].snapshot_id.clone(), vec!["HEAD".to_string()])]);
+
+        let mut policy = policy(Some(1), Vec::new());
+        let decisions = policy.evaluate(&history, &tags, &pinned);
+        assert_eq!(kept(&decisions), [3, 2, 1]);
+        assert_eq!(decisions[1].reasons, ["HEAD"]);
+        assert_eq!(decisions[2].reasons, ["tag v1.0"]);
+
+        policy.keep_tagged = false;
+        let decisions = policy.evaluate(&history, &tags, &pinned);
+        assert_eq!(kept(&decisions), [3, 2]);
+    }
+
+    #[test]
+    fn legacy_keep_last_respects_max_age() {
+        let now = Utc::now();
+        let mut recent = entry(2, "2026-01-01T00:00:00Z");
+        recent.timestamp = now - Duration::days(1);
+        let mut old = entry(1, "2026-01-01T00:00:00Z");
+        old.timestamp = now - Duration::days(40);
+
+        let policy = RetentionPolicy {
+            max_age: Some((30, now - Duration::days(30))),
+            ..policy(Some(10), Vec::new())
+        };
+        let decisions = evaluate(&policy, &[old, recent]);
+        assert_eq!(kept(&decisions), [2]);
+        assert_eq!(decisions[0].reasons, ["last 10 within 30 days"]);
+    }
+}
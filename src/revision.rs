@@ -0,0 +1,422 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+
+use crate::{HistoryEntry, Repository};
+
+/// 修订表达式解析
+///
+/// 支持的写法：
+/// - 快照编号：`3`
+/// - 快照ID前缀：`ab12cd`
+/// - 标签名：`v1.0`
+/// - 时间线名：`main`，表示该时间线上最新的快照
+/// - 最新快照：`latest`，即当前时间线上最新的快照
+/// - 沿父快照向前回溯 N 个快照：`latest~3`、`v1.0~2`
+/// - 时间：`@{2026-10-01}`、`@{2026-10-01 14:30}`、`@{yesterday}`、`@{3 days ago}`，
+///   表示该时间点或之前最近的一个快照（只有日期时取当天结束时）
+///
+/// 范围写法：`A..B`（包含两端，省略一端表示从最早或到最新），以及旧的 `N-M`、`ID-ID`。
+pub fn resolve(repo: &Repository, spec: &str) -> Result<String> {
+    let history = sorted_history(repo)?;
+    resolve_in(repo, &history, spec.trim())
+}
+
+/// 判断参数是否为范围表达式。旧的 `N-M`、`ID-ID` 写法也可能是标签或时间线名（如 `cafe-beef`），
+/// 存在同名的标签或时间线时按名称处理
+pub fn is_range(repo: &Repository, spec: &str) -> Result<bool> {
+    if spec.contains("..") {
+        return Ok(true);
+    }
+    Ok(legacy_range(spec).is_some() && !is_ref_name(repo, spec)?)
+}
+
+fn is_ref_name(repo: &Repository, spec: &str) -> Result<bool> {
+    Ok(repo.refs.get_tag(spec)?.is_some() || repo.refs.get_timeline(spec)?.is_some())
+}
+
+/// 解析范围表达式，按快照编号从旧到新返回范围内的快照ID
+pub fn resolve_range(repo: &Repository, spec: &str) -> Result<Vec<String>> {
+    let history = sorted_history(repo)?;
+
+    let (start, end) = match spec.split_once("..") {
+        Some((start, end)) => (start.trim(), end.trim()),
+        None if is_ref_name(repo, spec)? => return Err(anyhow!("Invalid range: '{}'", spec)),
+        None => legacy_range(spec).ok_or_else(|| anyhow!("Invalid range: '{}'", spec))?,
+    };
+
+    let position = |spec: &str| -> Result<usize> {
+        let id = resolve_in(repo, &history, spec)?;
+        history
+            .iter()
+            .position(|entry| entry.snapshot_id == id)
+            .ok_or_else(|| anyhow!("Snapshot {} is not in the history", spec))
+    };
+
+    if history.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let from = if start.is_empty() {
+        0
+    } else {
+        position(start)?
+    };
+    let to = if end.is_empty() {
+        history.len() - 1
+    } else {
+        position(end)?
+    };
+    let (from, to) = if from <= to { (from, to) } else { (to, from) };
+
+    Ok(history[from..=to]
+        .iter()
+        .map(|entry| entry.snapshot_id.clone())
+        .collect())
+}
+
+/// 按编号从旧到新排列的历史记录
+fn sorted_history(repo: &Repository) -> Result<Vec<HistoryEntry>> {
+    let mut history = repo.snapshot_manager.list_history()?;
+    history.sort_by_key(|entry| entry.number);
+    Ok(history)
+}
+
+fn resolve_in(repo: &Repository, history: &[HistoryEntry], spec: &str) -> Result<String> {
+    if spec.is_empty() {
+        return Err(anyhow!("error: empty revision"));
+    }
+
+    // 回溯：<rev>~N
+    if let Some((base, steps)) = spec.rsplit_once('~') {
+        let steps: usize = if steps.is_empty() {
+            1
+        } else {
+            steps
+                .parse()
+                .map_err(|_| anyhow!("error: invalid revision '{}'", spec))?
+        };
+        // 沿父快照回溯，其他时间线上编号更小的快照不计入
+        let id = resolve_in(repo, history, base)?;
+        let lineage = repo.snapshot_manager.lineage(&id)?;
+        if lineage.is_empty() {
+            return Err(anyhow!("error: snapshot {} is not in the history", base));
+        }
+        return lineage
+            .get(steps)
+            .map(|entry| entry.snapshot_id.clone())
+            .ok_or_else(|| anyhow!("error: '{}' goes past the first snapshot", spec));
+    }
+
+    // 最新快照为当前时间线的顶端；没有时间线记录或其快照已删除时取编号最大的快照
+    if spec == "latest" {
+        if let Some(timeline) = repo.refs.get_timeline(&repo.refs.current_timeline()?)?
+            && history
+                .iter()
+                .any(|entry| entry.snapshot_id == timeline.snapshot_id)
+        {
+            return Ok(timeline.snapshot_id);
+        }
+        return history
+            .last()
+            .map(|entry| entry.snapshot_id.clone())
+            .ok_or_else(|| anyhow!("error: no snapshots yet"));
+    }
+
+    // 时间：@{...}
+    if let Some(expr) = spec.strip_prefix("@{").and_then(|s| s.strip_suffix('}')) {
+        let time = parse_time(expr, Local::now())?;
+        return history
+            .iter()
+            .rev()
+            .find(|entry| entry.timestamp <= time)
+            .map(|entry| entry.snapshot_id.clone())
+            .ok_or_else(|| anyhow!("error: no snapshot at or before {}", expr));
+    }
+
+    // 快照编号
+    if let Ok(number) = spec.parse::<usize>()
+        && let Some(entry) = history.iter().find(|entry| entry.number == number)
+    {
+        return Ok(entry.snapshot_id.clone());
+    }
+
+    // 标签
+    if let Some(tag) = repo.refs.get_tag(spec)? {
+        return Ok(tag.snapshot_id);
+    }
+
+    // 时间线名表示该时间线上最新的快照
+    if let Some(timeline) = repo.refs.get_timeline(spec)? {
+        return Ok(timeline.snapshot_id);
+    }
+
+    repo.snapshot_manager.resolve_id_prefix(spec)
+}
+
+/// 旧的范围写法：`N-M` 或 `ID-ID`
+fn legacy_range(spec: &str) -> Option<(&str, &str)> {
+    let (start, end) = spec.split_once('-')?;
+    let is_hex = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_hexdigit());
+    (is_hex(start) && is_hex(end)).then_some((start, end))
+}
+
+/// 解析时间表达式，使用本地时区
+fn parse_time(expr: &str, now: DateTime<Local>) -> Result<DateTime<Utc>> {
+    let expr = expr.trim();
+    let invalid = || anyhow!("error: invalid date expression '{}'", expr);
+
+    match expr {
+        "now" => return Ok(now.with_timezone(&Utc)),
+        "yesterday" => return Ok((now - Duration::days(1)).with_timezone(&Utc)),
+        _ => {}
+    }
+
+    // N <单位> ago
+    if let Some(amount) = expr.strip_suffix(" ago") {
+        let (count, unit) = amount.trim().split_once(' ').ok_or_else(invalid)?;
+        let count: i64 = count.parse().map_err(|_| invalid())?;
+        let duration = match unit.trim_end_matches('s') {
+            "minute" => Duration::minutes(count),
+            "hour" => Duration::hours(count),
+            "day" => Duration::days(count),
+            "week" => Duration::weeks(count),
+            "month" => Duration::days(count * 30),
+            "year" => Duration::days(count * 365),
+            _ => return Err(invalid()),
+        };
+        return Ok((now - duration).with_timezone(&Utc));
+    }
+
+    if let Ok(time) = DateTime::parse_from_rfc3339(expr) {
+        return Ok(time.with_timezone(&Utc));
+    }
+
+    let naive = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(expr, format).ok())
+        .or_else(|| {
+            // 只有日期时取当天结束时，包含当天的所有快照
+            NaiveDate::parse_from_str(expr, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(23, 59, 59))
+        })
+        .ok_or_else(invalid)?;
+
+    Local
+        .from_local_datetime(&naive)
+        .latest()
+        .map(|time| time.with_timezone(&Utc))
+        .ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::refs::{Tag, Timeline};
+    use crate::test_utils::{TempDir, commit, repo_with_snapshots};
+    use std::fs;
+
+    fn number_of(repo: &Repository, spec: &str) -> usize {
+        let id = resolve(repo, spec).unwrap();
+        repo.snapshot_manager.load_snapshot(&id).unwrap().number
+    }
+
+    fn id_of(repo: &Repository, number: usize) -> String {
+        resolve(repo, &number.to_string()).unwrap()
+    }
+
+    #[test]
+    fn tag_names_take_precedence_over_legacy_ranges() {
+        let dir = TempDir::new();
+        let repo = repo_with_snapshots(&dir, 2);
+        assert!(is_range(&repo, "1-3").unwrap());
+
+        for name in ["cafe-beef", "add-bee"] {
+            repo.refs
+                .write_tag(&Tag::new(name, &id_of(&repo, 2), None))
+                .unwrap();
+            assert!(!is_range(&repo, name).unwrap());
+            assert_eq!(number_of(&repo, name), 2);
+            assert!(resolve_range(&repo, name).is_err());
+        }
+
+        // 没有同名标签时仍按旧的范围写法解析
+        assert_eq!(resolve_range(&repo, "1-3").unwrap().len(), 3);
+    }
+
+    #[test]
+    fn resolves_id_prefixes() {
+        let dir = TempDir::new();
+        let repo = repo_with_snapshots(&dir, 1);
+        let id = id_of(&repo, 2);
+
+        assert_eq!(resolve(&repo, &id).unwrap(), id);
+        assert_eq!(resolve(&repo, &id[..8]).unwrap(), id);
+        assert_eq!(resolve(&repo, &id[..8].to_uppercase()).unwrap(), id);
+
+        let error = resolve(&repo, &id[..3]).unwrap_err().to_string();
+        assert!(error.contains("too short"), "{}", error);
+        let error = resolve(&repo, "not-an-id").unwrap_err().to_string();
+        assert!(error.contains("not a valid"), "{}", error);
+    }
+
+    #[test]
+    fn ambiguous_id_prefix_lists_candidates() {
+        let dir = TempDir::new();
+        let repo = repo_with_snapshots(&dir, 1);
+
+        // 构造两个共享前缀的快照ID
+        let snapshots_dir = repo.rustory_dir.join("snapshots");
+        let first = format!("abcd0{}", "0".repeat(35));
+        let second = format!("abcd1{}", "1".repeat(35));
+        for id in [&first, &second] {
+            fs::write(snapshots_dir.join(format!("{}.json", id)), "{}").unwrap();
+        }
+
+        let error = resolve(&repo, "abcd").unwrap_err().to_string();
+        assert!(error.contains("ambiguous"), "{}", error);
+        assert!(
+            error.contains(&first) && error.contains(&second),
+            "{}",
+            error
+        );
+
+        assert_eq!(resolve(&repo, "abcd0").unwrap(), first);
+        assert_eq!(resolve(&repo, "abcd1").unwrap(), second);
+        let error = resolve(&repo, "abcd2").unwrap_err().to_string();
+        assert!(error.contains("not found"), "{}", error);
+    }
+
+    /// 切换到时间线并以指定快照为父快照继续提交，与 `timeline switch` 的效果相同
+    fn switch(repo: &Repository, timeline: &str, head: &str) {
+        repo.refs.set_current_timeline(timeline).unwrap();
+        repo.snapshot_manager.set_head(head).unwrap();
+    }
+
+    /// main: 1 - 2 - 3 - 5，exp: 2 - 4 - 6
+    fn repo_with_timelines(dir: &TempDir) -> Repository {
+        let mut repo = repo_with_snapshots(dir, 2);
+        let (two, three) = (id_of(&repo, 2), id_of(&repo, 3));
+        repo.refs
+            .write_timeline(&Timeline::new("exp", &two))
+            .unwrap();
+
+        switch(&repo, "exp", &two);
+        let four = commit(&mut repo);
+        switch(&repo, "main", &three);
+        commit(&mut repo);
+        switch(&repo, "exp", &four);
+        commit(&mut repo);
+        repo
+    }
+
+    fn numbers(repo: &Repository, ids: &[String]) -> Vec<usize> {
+        ids.iter()
+            .map(|id| repo.snapshot_manager.load_snapshot(id).unwrap().number)
+            .collect()
+    }
+
+    #[test]
+    fn ancestors_follow_parent_links() {
+        let dir = TempDir::new();
+        let repo = repo_with_timelines(&dir);
+
+        assert_eq!(number_of(&repo, "main~1"), 3);
+        assert_eq!(number_of(&repo, "main~2"), 2);
+        assert_eq!(number_of(&repo, "exp~1"), 4);
+        assert_eq!(number_of(&repo, "exp~2"), 2);
+        assert_eq!(number_of(&repo, "exp~"), 4);
+        assert_eq!(number_of(&repo, "5~3"), 1);
+        assert_eq!(number_of(&repo, "6~0"), 6);
+
+        let error = resolve(&repo, "exp~4").unwrap_err().to_string();
+        assert!(error.contains("past the first snapshot"), "{}", error);
+        assert!(resolve(&repo, "latest~x").is_err());
+    }
+
+    #[test]
+    fn latest_is_the_current_timeline_tip() {
+        let dir = TempDir::new();
+        let repo = repo_with_timelines(&dir);
+
+        // 当前时间线为 exp
+        assert_eq!(number_of(&repo, "latest"), 6);
+        assert_eq!(number_of(&repo, "latest~1"), 4);
+        assert_eq!(number_of(&repo, "latest~2"), 2);
+
+        switch(&repo, "main", &id_of(&repo, 5));
+        assert_eq!(number_of(&repo, "latest"), 5);
+        assert_eq!(number_of(&repo, "latest~1"), 3);
+        assert_eq!(number_of(&repo, "main"), 5);
+        assert_eq!(number_of(&repo, "exp"), 6);
+    }
+
+    #[test]
+    fn resolves_ranges() {
+        let dir = TempDir::new();
+        let repo = repo_with_snapshots(&dir, 4);
+        let range = |spec: &str| numbers(&repo, &resolve_range(&repo, spec).unwrap());
+
+        assert_eq!(range("2..4"), [2, 3, 4]);
+        assert_eq!(range("4..2"), [2, 3, 4]);
+        assert_eq!(range("4.."), [4, 5]);
+        assert_eq!(range("..2"), [1, 2]);
+        assert_eq!(range("latest~2..latest"), [3, 4, 5]);
+        assert_eq!(range("2-3"), [2, 3]);
+
+        let (two, four) = (id_of(&repo, 2), id_of(&repo, 4));
+        assert_eq!(range(&format!("{}-{}", &two[..8], &four[..8])), [2, 3, 4]);
+
+        assert!(is_range(&repo, "..").unwrap());
+        assert!(!is_range(&repo, "latest").unwrap());
+        assert!(resolve_range(&repo, "latest").is_err());
+    }
+
+    #[test]
+    fn resolves_times() {
+        let dir = TempDir::new();
+        let repo = repo_with_snapshots(&dir, 2);
+
+        assert_eq!(number_of(&repo, "@{now}"), 3);
+        // 所有快照都是刚创建的
+        for expr in ["@{1 minute ago}", "@{2000-01-01}"] {
+            let error = resolve(&repo, expr).unwrap_err().to_string();
+            assert!(error.contains("no snapshot at or before"), "{}", error);
+        }
+        assert!(resolve(&repo, "@{soon}").is_err());
+        assert!(resolve(&repo, "").is_err());
+    }
+
+    #[test]
+    fn parses_time_expressions() {
+        let now = Local.with_ymd_and_hms(2026, 10, 15, 12, 0, 0).unwrap();
+        let local = |y, m, d, h, min, s| {
+            Local
+                .with_ymd_and_hms(y, m, d, h, min, s)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        let parse = |expr: &str| parse_time(expr, now).unwrap();
+
+        assert_eq!(parse("now"), now.with_timezone(&Utc));
+        assert_eq!(parse("yesterday"), local(2026, 10, 14, 12, 0, 0));
+        assert_eq!(parse("3 days ago"), local(2026, 10, 12, 12, 0, 0));
+        assert_eq!(parse("1 day ago"), local(2026, 10, 14, 12, 0, 0));
+        assert_eq!(parse("2 hours ago"), local(2026, 10, 15, 10, 0, 0));
+        assert_eq!(parse("1 week ago"), local(2026, 10, 8, 12, 0, 0));
+        assert_eq!(parse("2 months ago"), local(2026, 8, 16, 12, 0, 0));
+
+        // 只有日期时取当天结束时
+        assert_eq!(parse("2026-10-01"), local(2026, 10, 1, 23, 59, 59));
+        assert_eq!(parse("2026-10-01 14:30"), local(2026, 10, 1, 14, 30, 0));
+        assert_eq!(parse("2026-10-01T14:30:15"), local(2026, 10, 1, 14, 30, 15));
+        assert_eq!(
+            parse("2026-10-01T14:30:00+02:00"),
+            Utc.with_ymd_and_hms(2026, 10, 1, 12, 30, 0).unwrap()
+        );
+
+        for invalid in ["soon", "3 fortnights ago", "x days ago", "2026-13-01"] {
+            assert!(parse_time(invalid, now).is_err(), "{}", invalid);
+        }
+    }
+}
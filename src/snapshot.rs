@@ -1,4 +1,7 @@
 use anyhow::Result;
+use rayon::prelude::*;
+use sha1::{Digest, Sha1};
+use std::collections::{HashMap, HashSet};
 use std::fs::{File, OpenOptions};
 use std::io::{BufRead, BufReader, Write};
 use std::path::{Path, PathBuf};
@@ -6,21 +9,40 @@ use std::path::{Path, PathBuf};
 use crate::config::Config;
 use crate::index::IndexManager;
 use crate::objects::ObjectStore;
-use crate::{HistoryEntry, SnapshotMetadata};
+use crate::refs::Tag;
+use crate::trash::{Trash, TrashedSnapshot};
+use crate::{FileEntry, HistoryEntry, SnapshotMetadata};
+
+/// 按前缀查找快照时要求的最短长度
+const MIN_ID_PREFIX_LEN: usize = 4;
 
 pub struct SnapshotManager {
     snapshots_dir: PathBuf,
     history_path: PathBuf,
+    /// 记录工作区当前基于哪个快照
+    head_path: PathBuf,
+    trash: Trash,
 }
 
 impl SnapshotManager {
-    pub fn new(snapshots_dir: PathBuf, history_path: PathBuf) -> Self {
+    pub fn new(
+        snapshots_dir: PathBuf,
+        history_path: PathBuf,
+        head_path: PathBuf,
+        trash_dir: PathBuf,
+    ) -> Self {
         Self {
             snapshots_dir,
             history_path,
+            head_path,
+            trash: Trash::new(trash_dir),
         }
     }
 
+    pub fn trash(&self) -> &Trash {
+        &self.trash
+    }
+
     pub fn create_snapshot(
         &mut self,
         root: &Path,
@@ -41,8 +63,13 @@ impl SnapshotManager {
         let mut modified = 0;
         let mut deleted = 0;
 
-        // 存储新文件到对象存储
-        for (path, entry) in &new_index.files {
+        // 按路径排序，保证警告输出顺序稳定
+        let mut paths: Vec<&PathBuf> = new_index.files.keys().collect();
+        paths.sort();
+
+        let mut to_store = Vec::new();
+        for path in paths {
+            let entry = &new_index.files[path];
             let full_path = root.join(path);
             if full_path.exists() {
                 // 检查文件大小
@@ -55,8 +82,6 @@ impl SnapshotManager {
                     continue;
                 }
 
-                object_store.store_file(&full_path)?;
-
                 match old_index.files.get(path) {
                     None => added += 1,
                     Some(old_entry) => {
@@ -65,9 +90,31 @@ impl SnapshotManager {
                         }
                     }
                 }
+
+                to_store.push((full_path, entry));
             }
         }
 
+        // 并行存储新文件到对象存储
+        let object_store: &ObjectStore = object_store;
+        to_store
+            .into_par_iter()
+            .try_for_each(|(full_path, entry)| -> Result<()> {
+                // 内容已在对象存储中时无需再次读取文件
+                if object_store.exists(&entry.hash) {
+                    return Ok(());
+                }
+
+                // 以同一路径的上一个版本作为差量基础
+                let base = old_index
+                    .files
+                    .get(&entry.path)
+                    .map(|old_entry| old_entry.hash.as_str())
+                    .filter(|hash| *hash != entry.hash);
+                object_store.store_file_with_base(&full_path, base)?;
+                Ok(())
+            })?;
+
         // 计算删除的文件
         for path in old_index.files.keys() {
             if !new_index.files.contains_key(path) {
@@ -75,37 +122,28 @@ impl SnapshotManager {
             }
         }
 
-        // 计算提交序号（基于现有历史记录数量）
-        let number = self.list_history().unwrap_or_default().len() + 1;
+        // 计算提交序号（已有最大序号加一，删除快照后序号也不会重复）
+        let history = self.list_history().unwrap_or_default();
+        let number = history.iter().map(|entry| entry.number).max().unwrap_or(0) + 1;
 
-        // 生成快照ID（基于时间戳、随机数和内容的哈希）
+        // 父快照是工作区当前所基于的快照（回滚后不一定是最新的快照）
+        let parent = self.head()?;
+
+        // 快照ID是快照清单（文件内容、父快照和元数据）的 SHA-1
         let timestamp = chrono::Utc::now();
-        use std::collections::hash_map::DefaultHasher;
-        use std::hash::{Hash, Hasher};
-
-        let mut hasher = DefaultHasher::new();
-        timestamp
-            .timestamp_nanos_opt()
-            .unwrap_or(0)
-            .hash(&mut hasher);
-        message.hash(&mut hasher);
-        std::process::id().hash(&mut hasher); // 进程ID增加唯一性
-
-        // 为了进一步避免冲突，加入一些随机性
-        use std::time::{SystemTime, UNIX_EPOCH};
-        let nanos = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .subsec_nanos();
-        nanos.hash(&mut hasher);
-
-        let hash = hasher.finish();
-        let snapshot_id = format!("{:x}", hash).chars().take(8).collect::<String>();
+        let snapshot_id = Self::compute_snapshot_id(
+            parent.as_deref(),
+            number,
+            &timestamp,
+            &message,
+            &new_index.files,
+        );
 
         // 创建快照元数据
         let snapshot = SnapshotMetadata {
             id: snapshot_id.clone(),
             number,
+            parent: parent.clone(),
             timestamp,
             message: message.clone(),
             added,
@@ -114,8 +152,14 @@ impl SnapshotManager {
             files: new_index.files.clone(),
         };
 
-        // 保存快照元数据
+        // 保存快照元数据，绝不覆盖已有的快照
         let snapshot_path = self.snapshots_dir.join(format!("{}.json", snapshot_id));
+        if snapshot_path.exists() {
+            return Err(anyhow::anyhow!(
+                "error: snapshot {} already exists",
+                snapshot_id
+            ));
+        }
         std::fs::create_dir_all(&self.snapshots_dir)?;
         let content = serde_json::to_string_pretty(&snapshot)?;
         std::fs::write(snapshot_path, content)?;
@@ -132,12 +176,43 @@ impl SnapshotManager {
             modified,
             deleted,
             message,
+            parent,
+            tags: Vec::new(),
         };
         self.append_history(&history_entry)?;
+        self.set_head(&snapshot_id)?;
 
         Ok(snapshot_id)
     }
 
+    /// 根据快照清单计算快照ID，文件按路径排序保证结果稳定
+    fn compute_snapshot_id(
+        parent: Option<&str>,
+        number: usize,
+        timestamp: &chrono::DateTime<chrono::Utc>,
+        message: &str,
+        files: &HashMap<PathBuf, FileEntry>,
+    ) -> String {
+        let mut hasher = Sha1::new();
+        hasher.update(format!("parent {}\n", parent.unwrap_or("")));
+        hasher.update(format!("number {}\n", number));
+        hasher.update(format!(
+            "timestamp {}\n",
+            timestamp.to_rfc3339_opts(chrono::SecondsFormat::Nanos, true)
+        ));
+        hasher.update(format!("message {} {}\n", message.len(), message));
+
+        let mut entries: Vec<&FileEntry> = files.values().collect();
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        for entry in entries {
+            hasher.update(format!("{} {} ", entry.hash, entry.size));
+            hasher.update(entry.path.to_string_lossy().as_bytes());
+            hasher.update([0u8]);
+        }
+
+        format!("{:x}", hasher.finalize())
+    }
+
     pub fn load_snapshot(&self, snapshot_id: &str) -> Result<SnapshotMetadata> {
         let snapshot_path = self.snapshots_dir.join(format!("{}.json", snapshot_id));
         if !snapshot_path.exists() {
@@ -170,24 +245,109 @@ impl SnapshotManager {
         Ok(entries)
     }
 
+    /// 工作区当前基于的快照。HEAD 文件不存在（旧仓库）或指向的快照已被删除时，
+    /// 视为基于最新的快照
+    pub fn head(&self) -> Result<Option<String>> {
+        if self.head_path.exists() {
+            let id = std::fs::read_to_string(&self.head_path)?.trim().to_string();
+            if !id.is_empty() && self.snapshots_dir.join(format!("{}.json", id)).exists() {
+                return Ok(Some(id));
+            }
+        }
+
+        Ok(self
+            .list_history()?
+            .into_iter()
+            .max_by_key(|entry| entry.number)
+            .map(|entry| entry.snapshot_id))
+    }
+
+    /// 更新 HEAD（先写临时文件再重命名）
+    pub fn set_head(&self, snapshot_id: &str) -> Result<()> {
+        let temp_path = self.head_path.with_extension("tmp");
+        std::fs::write(&temp_path, format!("{}\n", snapshot_id))?;
+        std::fs::rename(&temp_path, &self.head_path)?;
+        Ok(())
+    }
+
+    /// 从指定快照沿父快照回溯到第一个快照，返回的记录从新到旧排列。
+    /// 旧记录没有父快照信息时，按编号取前一个快照
+    pub fn lineage(&self, snapshot_id: &str) -> Result<Vec<HistoryEntry>> {
+        let mut history = self.list_history()?;
+        history.sort_by_key(|entry| entry.number);
+
+        let mut lineage = Vec::new();
+        let mut current = history
+            .iter()
+            .position(|entry| entry.snapshot_id == snapshot_id);
+
+        while let Some(position) = current {
+            let entry = &history[position];
+            current = match &entry.parent {
+                Some(parent) => history
+                    .iter()
+                    .position(|candidate| &candidate.snapshot_id == parent),
+                None => position.checked_sub(1),
+            };
+            lineage.push(entry.clone());
+        }
+
+        Ok(lineage)
+    }
+
+    /// 快照的父快照ID，旧快照没有记录时按编号取前一个快照
+    pub fn parent_id(&self, snapshot: &SnapshotMetadata) -> Result<Option<String>> {
+        // 父快照已被删除时，按历史记录中的父快照回溯
+        if let Some(parent) = &snapshot.parent
+            && self.snapshot_path(parent).exists()
+        {
+            return Ok(Some(parent.clone()));
+        }
+        Ok(self
+            .lineage(&snapshot.id)?
+            .into_iter()
+            .nth(1)
+            .map(|entry| entry.snapshot_id))
+    }
+
     fn append_history(&self, entry: &HistoryEntry) -> Result<()> {
-        let line = format!(
-            "{} {} {} {}/{}/{} msg=\"{}\"\n",
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.history_path)?;
+
+        file.write_all(Self::history_line(entry).as_bytes())?;
+        Ok(())
+    }
+
+    fn history_line(entry: &HistoryEntry) -> String {
+        let parent = entry
+            .parent
+            .as_ref()
+            .map(|parent| format!("parent={} ", parent))
+            .unwrap_or_default();
+        format!(
+            "{} {} {} {}/{}/{} {}msg=\"{}\"\n",
             entry.snapshot_id,
             entry.number,
             entry.timestamp.format("%Y-%m-%dT%H:%M:%S%.3fZ"),
             entry.added,
             entry.modified,
             entry.deleted,
+            parent,
             entry.message
-        );
-
-        let mut file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&self.history_path)?;
+        )
+    }
 
-        file.write_all(line.as_bytes())?;
+    /// 先写临时文件再重命名，原子地替换历史记录
+    fn write_history(&self, content: &str) -> Result<()> {
+        let temp_path = self.history_path.with_extension("log.tmp");
+        let written = std::fs::write(&temp_path, content)
+            .and_then(|_| std::fs::rename(&temp_path, &self.history_path));
+        if let Err(e) = written {
+            let _ = std::fs::remove_file(&temp_path);
+            return Err(e.into());
+        }
         Ok(())
     }
 
@@ -222,6 +382,13 @@ impl SnapshotManager {
         let modified = changes[1].parse()?;
         let deleted = changes[2].parse()?;
 
+        // 父快照（可选）: parent=<id>，位于消息之前
+        let parent = parts[changes_idx + 1..]
+            .iter()
+            .take_while(|part| !part.starts_with("msg="))
+            .find_map(|part| part.strip_prefix("parent="))
+            .map(|id| id.to_string());
+
         // 解析消息（在 msg="..." 之间）
         let msg_start = line.find("msg=\"").map(|i| i + 5);
         let msg_end = line.rfind('"');
@@ -238,6 +405,8 @@ impl SnapshotManager {
             modified,
             deleted,
             message,
+            parent,
+            tags: Vec::new(),
         })
     }
 
@@ -259,54 +428,554 @@ impl SnapshotManager {
         Ok(())
     }
 
-    /// 删除指定的快照
-    pub fn delete_snapshot(&self, snapshot_id: &str) -> Result<()> {
-        // 删除快照元数据文件
-        let snapshot_path = self.snapshots_dir.join(format!("{}.json", snapshot_id));
-        if snapshot_path.exists() {
-            std::fs::remove_file(&snapshot_path)?;
-        } else {
-            return Err(anyhow::anyhow!("Snapshot '{}' not found", snapshot_id));
+    /// 删除一组快照，作为一个整体完成：
+    /// 先把快照放入回收站并把元数据改名暂存，再原子地重写历史记录，
+    /// 失败时把暂存的文件改回原名并撤销回收站记录，进程中断时留下的暂存文件由 `recover_dropped` 处理。
+    /// 父快照被删除的记录改为指向最近的保留下来的祖先，HEAD 同样回退到保留下来的祖先
+    pub fn drop_snapshots(
+        &self,
+        snapshot_ids: &[String],
+        tags: &HashMap<String, Vec<Tag>>,
+    ) -> Result<()> {
+        let dropped: HashSet<&str> = snapshot_ids.iter().map(|id| id.as_str()).collect();
+        for id in &dropped {
+            if !self.snapshot_path(id).exists() {
+                return Err(anyhow::anyhow!("Snapshot '{}' not found", id));
+            }
         }
 
-        // 重写历史记录文件，删除对应的记录
-        self.remove_from_history(snapshot_id)?;
+        // 在修改任何文件之前，根据原来的历史计算保留下来的祖先
+        let surviving_ancestor = |id: &str| -> Result<Option<String>> {
+            Ok(self
+                .lineage(id)?
+                .into_iter()
+                .skip(1)
+                .map(|entry| entry.snapshot_id)
+                .find(|ancestor| !dropped.contains(ancestor.as_str())))
+        };
+
+        let mut new_history = String::new();
+        if self.history_path.exists() {
+            for line in std::fs::read_to_string(&self.history_path)?.lines() {
+                let mut parts = line.split_whitespace();
+                let Some(id) = parts.next() else {
+                    continue;
+                };
+                if dropped.contains(id) {
+                    continue;
+                }
+
+                // 没有保留下来的祖先时保留原来的 parent，它不再存在，回溯到此为止
+                let dropped_parent = parts
+                    .take_while(|part| !part.starts_with("msg="))
+                    .find_map(|part| part.strip_prefix("parent="))
+                    .filter(|parent| dropped.contains(parent));
+                match dropped_parent {
+                    Some(parent) => match surviving_ancestor(id)? {
+                        Some(ancestor) => new_history.push_str(&line.replacen(
+                            &format!("parent={}", parent),
+                            &format!("parent={}", ancestor),
+                            1,
+                        )),
+                        None => new_history.push_str(line),
+                    },
+                    None => new_history.push_str(line),
+                }
+                new_history.push('\n');
+            }
+        }
+
+        let new_head = match self.head()? {
+            Some(head) if dropped.contains(head.as_str()) => Some(surviving_ancestor(&head)?),
+            _ => None,
+        };
+
+        // 放入回收站，记录删除前的历史记录以便恢复
+        let history = self.list_history()?;
+        let deleted_at = chrono::Utc::now();
+        let mut trashed: Vec<&str> = Vec::new();
+        let discard_trash = |trashed: &[&str]| {
+            for id in trashed {
+                let _ = self.trash.remove(id);
+            }
+        };
+        for id in &dropped {
+            let put = self.load_snapshot(id).and_then(|snapshot| {
+                let entry = history
+                    .iter()
+                    .find(|entry| entry.snapshot_id == *id)
+                    .cloned()
+                    .unwrap_or_else(|| HistoryEntry {
+                        snapshot_id: snapshot.id.clone(),
+                        number: snapshot.number,
+                        timestamp: snapshot.timestamp,
+                        added: snapshot.added,
+                        modified: snapshot.modified,
+                        deleted: snapshot.deleted,
+                        message: snapshot.message.clone(),
+                        parent: snapshot.parent.clone(),
+                        tags: Vec::new(),
+                    });
+                self.trash.put(&TrashedSnapshot {
+                    deleted_at,
+                    entry,
+                    snapshot,
+                    tags: tags.get(*id).cloned().unwrap_or_default(),
+                })
+            });
+            if let Err(e) = put {
+                discard_trash(&trashed);
+                return Err(e);
+            }
+            trashed.push(id);
+        }
+
+        // 暂存快照元数据
+        let mut staged: Vec<(PathBuf, PathBuf)> = Vec::new();
+        for id in &dropped {
+            let path = self.snapshot_path(id);
+            let staged_path = path.with_extension("json.dropped");
+            if let Err(e) = std::fs::rename(&path, &staged_path) {
+                Self::unstage(&staged);
+                discard_trash(&trashed);
+                return Err(e.into());
+            }
+            staged.push((path, staged_path));
+        }
+
+        if let Err(e) = self.write_history(&new_history) {
+            Self::unstage(&staged);
+            discard_trash(&trashed);
+            return Err(e);
+        }
+
+        // 历史记录已经更新，以下步骤失败不影响一致性
+        match new_head {
+            Some(Some(ancestor)) => self.set_head(&ancestor)?,
+            Some(None) if self.head_path.exists() => std::fs::remove_file(&self.head_path)?,
+            _ => {}
+        }
+        for (_, staged_path) in &staged {
+            let _ = std::fs::remove_file(staged_path);
+        }
 
         Ok(())
     }
 
-    /// 从历史记录中删除指定的快照记录
-    fn remove_from_history(&self, snapshot_id: &str) -> Result<()> {
-        if !self.history_path.exists() {
-            return Ok(());
+    /// 从回收站恢复快照：写回快照元数据，并按编号把记录插回历史记录。
+    /// 父快照仍在回收站中时，沿回收站中的记录回溯到保留下来的祖先；
+    /// 删除时改为指向其祖先的后续快照重新指向恢复的快照
+    pub fn undelete_snapshot(&self, snapshot_id: &str) -> Result<HistoryEntry> {
+        let trashed = self.trash.get(snapshot_id)?;
+        if self.snapshot_path(snapshot_id).exists() {
+            return Err(anyhow::anyhow!(
+                "error: snapshot {} already exists",
+                snapshot_id
+            ));
         }
 
-        // 读取所有历史记录
-        let file = File::open(&self.history_path)?;
-        let reader = BufReader::new(file);
-        let mut new_lines = Vec::new();
+        let history = self.list_history()?;
+        if let Some(existing) = history
+            .iter()
+            .find(|entry| entry.number == trashed.entry.number)
+        {
+            return Err(anyhow::anyhow!(
+                "error: snapshot number #{} is now used by {}; cannot restore {}",
+                existing.number,
+                crate::utils::short_id(&existing.snapshot_id),
+                crate::utils::short_id(snapshot_id)
+            ));
+        }
 
-        for line in reader.lines() {
-            let line = line?;
-            if !line.starts_with(snapshot_id) {
-                new_lines.push(line);
+        // 跳过回收站中的快照，返回第一个仍存在（或就是要恢复的）的祖先
+        let in_history: HashSet<&str> = history
+            .iter()
+            .map(|entry| entry.snapshot_id.as_str())
+            .collect();
+        let resolve = |start: Option<String>| -> Option<String> {
+            let mut current = start;
+            let mut seen = HashSet::new();
+            while let Some(id) = current {
+                if id == snapshot_id || in_history.contains(id.as_str()) || !seen.insert(id.clone())
+                {
+                    return Some(id);
+                }
+                match self.trash.get(&id) {
+                    Ok(parent) => current = parent.entry.parent,
+                    Err(_) => return Some(id),
+                }
+            }
+            None
+        };
+
+        let mut entry = trashed.entry.clone();
+        entry.parent = resolve(entry.parent.take());
+
+        // 重建历史记录：按编号插入恢复的记录，并重新链接它的后续快照
+        let mut new_history = String::new();
+        let mut inserted = false;
+        if self.history_path.exists() {
+            for line in std::fs::read_to_string(&self.history_path)?.lines() {
+                let Ok(current) = self.parse_history_line(line) else {
+                    new_history.push_str(line);
+                    new_history.push('\n');
+                    continue;
+                };
+                if !inserted && current.number > entry.number {
+                    new_history.push_str(&Self::history_line(&entry));
+                    inserted = true;
+                }
+
+                let relinked = match &current.parent {
+                    Some(parent) if current.number > entry.number => {
+                        let original = self
+                            .load_snapshot(&current.snapshot_id)
+                            .ok()
+                            .and_then(|snapshot| snapshot.parent);
+                        (resolve(original).as_deref() == Some(snapshot_id)).then(|| {
+                            line.replacen(
+                                &format!("parent={}", parent),
+                                &format!("parent={}", snapshot_id),
+                                1,
+                            )
+                        })
+                    }
+                    _ => None,
+                };
+                new_history.push_str(relinked.as_deref().unwrap_or(line));
+                new_history.push('\n');
+            }
+        }
+        if !inserted {
+            new_history.push_str(&Self::history_line(&entry));
+        }
+
+        std::fs::create_dir_all(&self.snapshots_dir)?;
+        let snapshot_path = self.snapshot_path(snapshot_id);
+        std::fs::write(
+            &snapshot_path,
+            serde_json::to_string_pretty(&trashed.snapshot)?,
+        )?;
+        if let Err(e) = self.write_history(&new_history) {
+            let _ = std::fs::remove_file(&snapshot_path);
+            return Err(e);
+        }
+
+        self.trash.remove(snapshot_id)?;
+        Ok(entry)
+    }
+
+    /// 将一段连续的快照（按编号从旧到新，每个都是前一个的子快照）合并为一个快照。
+    ///
+    /// 合并后的快照保存最后一个快照的文件状态，以第一个快照的父快照为父快照，
+    /// 使用第一个快照的编号，并相对父快照重新计算增删改数量。编号更大的快照
+    /// 依次前移编号；编号和父快照是快照ID的一部分，这些快照会以新的ID重写。
+    /// 返回合并后的快照ID，以及所有旧ID到新ID的映射（范围内的快照都映射到合并后的快照）
+    pub fn squash_snapshots(
+        &self,
+        snapshot_ids: &[String],
+        message: Option<String>,
+    ) -> Result<(String, HashMap<String, String>)> {
+        if snapshot_ids.len() < 2 {
+            return Err(anyhow::anyhow!(
+                "error: squash needs at least two snapshots"
+            ));
+        }
+
+        let range: Vec<SnapshotMetadata> = snapshot_ids
+            .iter()
+            .map(|id| self.load_snapshot(id))
+            .collect::<Result<_>>()?;
+        let first = &range[0];
+        let last = &range[range.len() - 1];
+
+        for pair in range.windows(2) {
+            if self.parent_id(&pair[1])?.as_deref() != Some(pair[0].id.as_str()) {
+                return Err(anyhow::anyhow!(
+                    "error: snapshot #{} is not a child of #{}; only a contiguous run on one lineage can be squashed",
+                    pair[1].number,
+                    pair[0].number
+                ));
             }
         }
 
-        // 重写历史文件
-        std::fs::write(&self.history_path, new_lines.join("\n") + "\n")?;
+        // 编号不小于第一个快照的快照都要重写，先读取它们原来的父快照
+        let mut history = self.list_history()?;
+        history.sort_by_key(|entry| entry.number);
+        let mut later = Vec::new();
+        for entry in history.iter().filter(|entry| entry.number > last.number) {
+            let snapshot = self.load_snapshot(&entry.snapshot_id)?;
+            let parent = self.parent_id(&snapshot)?;
+            later.push((snapshot, parent));
+        }
+
+        // 范围中间的快照在合并后不再存在，不能有其他快照从它们创建
+        let middle: HashSet<&str> = range[..range.len() - 1]
+            .iter()
+            .map(|snapshot| snapshot.id.as_str())
+            .collect();
+        if let Some((child, _)) = later
+            .iter()
+            .find(|(_, parent)| parent.as_deref().is_some_and(|p| middle.contains(p)))
+        {
+            return Err(anyhow::anyhow!(
+                "error: snapshot #{} was created from a snapshot inside the range",
+                child.number
+            ));
+        }
+        let head = self.head()?;
+        if head.as_deref().is_some_and(|head| middle.contains(head)) {
+            return Err(anyhow::anyhow!(
+                "error: the working directory is based on a snapshot inside the range; go back to another snapshot first"
+            ));
+        }
+
+        // 合并后的快照
+        let parent = self.parent_id(first)?;
+        let parent_files = match &parent {
+            Some(parent) => self.load_snapshot(parent)?.files,
+            None => HashMap::new(),
+        };
+        let (added, modified, deleted) = Self::count_changes(&parent_files, &last.files);
+        // 默认合并各快照的说明（相邻重复的只保留一次）
+        let message = message.unwrap_or_else(|| {
+            let mut messages: Vec<&str> = range
+                .iter()
+                .map(|snapshot| snapshot.message.as_str())
+                .collect();
+            messages.dedup();
+            messages.join("; ")
+        });
+        let squashed = SnapshotMetadata {
+            id: Self::compute_snapshot_id(
+                parent.as_deref(),
+                first.number,
+                &last.timestamp,
+                &message,
+                &last.files,
+            ),
+            number: first.number,
+            parent,
+            timestamp: last.timestamp,
+            message,
+            added,
+            modified,
+            deleted,
+            files: last.files.clone(),
+        };
+
+        let mut remap: HashMap<String, String> = range
+            .iter()
+            .map(|snapshot| (snapshot.id.clone(), squashed.id.clone()))
+            .collect();
+        let mut rewritten = vec![squashed];
+
+        let shift = last.number - first.number;
+        for (mut snapshot, parent) in later {
+            let old_id = snapshot.id.clone();
+            snapshot.number -= shift;
+            snapshot.parent = parent.map(|parent| remap.get(&parent).cloned().unwrap_or(parent));
+            snapshot.id = Self::compute_snapshot_id(
+                snapshot.parent.as_deref(),
+                snapshot.number,
+                &snapshot.timestamp,
+                &snapshot.message,
+                &snapshot.files,
+            );
+            remap.insert(old_id, snapshot.id.clone());
+            rewritten.push(snapshot);
+        }
+
+        // 编号小于第一个快照的记录保持不变，其后依次写入重写的快照
+        let mut new_history = String::new();
+        if self.history_path.exists() {
+            for line in std::fs::read_to_string(&self.history_path)?.lines() {
+                let id = line.split_whitespace().next().unwrap_or("");
+                if !id.is_empty() && !remap.contains_key(id) {
+                    new_history.push_str(line);
+                    new_history.push('\n');
+                }
+            }
+        }
+        for snapshot in &rewritten {
+            new_history.push_str(&Self::history_line(&HistoryEntry {
+                snapshot_id: snapshot.id.clone(),
+                number: snapshot.number,
+                timestamp: snapshot.timestamp,
+                added: snapshot.added,
+                modified: snapshot.modified,
+                deleted: snapshot.deleted,
+                message: snapshot.message.clone(),
+                parent: snapshot.parent.clone(),
+                tags: Vec::new(),
+            }));
+        }
 
+        // 先写入新的快照元数据，历史记录替换成功之后才删除旧的
+        let mut written = Vec::new();
+        for snapshot in &rewritten {
+            let path = self.snapshot_path(&snapshot.id);
+            let result = serde_json::to_string_pretty(snapshot)
+                .map_err(anyhow::Error::from)
+                .and_then(|content| Ok(std::fs::write(&path, content)?));
+            if let Err(e) = result {
+                let _ = std::fs::remove_file(&path);
+                for path in &written {
+                    let _ = std::fs::remove_file(path);
+                }
+                return Err(e);
+            }
+            written.push(path);
+        }
+        if let Err(e) = self.write_history(&new_history) {
+            for path in &written {
+                let _ = std::fs::remove_file(path);
+            }
+            return Err(e);
+        }
+
+        if let Some(head) = head.and_then(|head| remap.get(&head)) {
+            self.set_head(head)?;
+        }
+        for (old_id, new_id) in &remap {
+            if old_id != new_id {
+                let _ = std::fs::remove_file(self.snapshot_path(old_id));
+            }
+        }
+
+        Ok((rewritten[0].id.clone(), remap))
+    }
+
+    /// 两个文件集合之间新增、修改和删除的文件数
+    fn count_changes(
+        old: &HashMap<PathBuf, FileEntry>,
+        new: &HashMap<PathBuf, FileEntry>,
+    ) -> (usize, usize, usize) {
+        let added = new.keys().filter(|path| !old.contains_key(*path)).count();
+        let modified = new
+            .iter()
+            .filter(|(path, entry)| old.get(*path).is_some_and(|old| old.hash != entry.hash))
+            .count();
+        let deleted = old.keys().filter(|path| !new.contains_key(*path)).count();
+        (added, modified, deleted)
+    }
+
+    /// 清理 `drop_snapshots` 中断时留下的暂存文件（`<id>.json.dropped`）：
+    /// 历史记录中仍有该快照时说明历史尚未重写，改回原名并撤销回收站记录；
+    /// 否则删除已经完成，回收站中有副本，直接删除暂存文件
+    pub fn recover_dropped(&self) -> Result<()> {
+        if !self.snapshots_dir.exists() {
+            return Ok(());
+        }
+
+        let mut staged = Vec::new();
+        for entry in std::fs::read_dir(&self.snapshots_dir)? {
+            let path = entry?.path();
+            if let Some(id) = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix(".json.dropped"))
+            {
+                staged.push((id.to_string(), path));
+            }
+        }
+        if staged.is_empty() {
+            return Ok(());
+        }
+
+        let in_history: HashSet<String> = self
+            .list_history()?
+            .into_iter()
+            .map(|entry| entry.snapshot_id)
+            .collect();
+        for (id, staged_path) in staged {
+            if in_history.contains(&id) && !self.snapshot_path(&id).exists() {
+                std::fs::rename(&staged_path, self.snapshot_path(&id))?;
+                self.trash.remove(&id)?;
+            } else {
+                std::fs::remove_file(&staged_path)?;
+            }
+        }
         Ok(())
     }
 
-    /// 根据序号查找快照ID
-    pub fn find_snapshot_by_number(&self, number: usize) -> Result<String> {
-        let history = self.list_history()?;
-        for entry in &history {
-            if entry.number == number {
-                return Ok(entry.snapshot_id.clone());
+    fn unstage(staged: &[(PathBuf, PathBuf)]) {
+        for (path, staged_path) in staged {
+            let _ = std::fs::rename(staged_path, path);
+        }
+    }
+
+    fn snapshot_path(&self, snapshot_id: &str) -> PathBuf {
+        self.snapshots_dir.join(format!("{}.json", snapshot_id))
+    }
+
+    /// 将唯一的ID前缀解析为完整的快照ID，前缀有歧义时列出所有候选
+    pub fn resolve_id_prefix(&self, prefix: &str) -> Result<String> {
+        let prefix = prefix.to_lowercase();
+        if prefix.is_empty() || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(anyhow::anyhow!(
+                "error: '{}' is not a valid snapshot number or ID",
+                prefix
+            ));
+        }
+
+        let ids = self.list_snapshot_ids()?;
+        if ids.contains(&prefix) {
+            return Ok(prefix);
+        }
+
+        if prefix.len() < MIN_ID_PREFIX_LEN {
+            return Err(anyhow::anyhow!(
+                "error: snapshot ID prefix '{}' is too short (use at least {} characters)",
+                prefix,
+                MIN_ID_PREFIX_LEN
+            ));
+        }
+
+        let mut matches: Vec<String> = ids
+            .into_iter()
+            .filter(|id| id.starts_with(&prefix))
+            .collect();
+
+        match matches.len() {
+            0 => Err(anyhow::anyhow!("error: snapshot '{}' not found", prefix)),
+            1 => Ok(matches.remove(0)),
+            _ => {
+                matches.sort();
+                let history = self.list_history().unwrap_or_default();
+                let candidates: Vec<String> = matches
+                    .iter()
+                    .map(
+                        |id| match history.iter().find(|entry| &entry.snapshot_id == id) {
+                            Some(entry) => {
+                                format!("  {}  #{} \"{}\"", id, entry.number, entry.message)
+                            }
+                            None => format!("  {}", id),
+                        },
+                    )
+                    .collect();
+                Err(anyhow::anyhow!(
+                    "error: snapshot ID prefix '{}' is ambiguous; candidates:\n{}",
+                    prefix,
+                    candidates.join("\n")
+                ))
+            }
+        }
+    }
+
+    /// 列出所有快照ID
+    fn list_snapshot_ids(&self) -> Result<Vec<String>> {
+        let mut ids = Vec::new();
+        if self.snapshots_dir.exists() {
+            for entry in std::fs::read_dir(&self.snapshots_dir)? {
+                let path = entry?.path();
+                if path.extension().and_then(|s| s.to_str()) == Some("json")
+                    && let Some(stem) = path.file_stem().and_then(|s| s.to_str())
+                {
+                    ids.push(stem.to_string());
+                }
             }
         }
-        Err(anyhow::anyhow!("Snapshot with number {} not found", number))
+        Ok(ids)
     }
 }
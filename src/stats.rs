@@ -1,6 +1,9 @@
 use anyhow::Result;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+
+use crate::objects::{ChunkRef, ObjectKind, ObjectStore};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RepositoryStats {
@@ -9,6 +12,10 @@ pub struct RepositoryStats {
     pub total_size_bytes: u64,
     pub compressed_size_bytes: u64,
     pub compression_ratio: f64,
+    pub chunked_files: usize,
+    pub unique_chunks: usize,
+    pub chunked_logical_bytes: u64,
+    pub delta_objects: usize,
     pub file_type_stats: HashMap<String, FileTypeStats>,
     pub timeline_stats: Vec<TimelineEntry>,
 }
@@ -27,6 +34,15 @@ pub struct TimelineEntry {
     pub files_changed: usize,
 }
 
+/// 单个对象的统计信息
+#[derive(Default)]
+struct ObjectStats {
+    stored_size: u64,
+    original_size: Option<u64>,
+    is_delta: bool,
+    chunk_refs: Option<Vec<ChunkRef>>,
+}
+
 pub struct StatsCollector;
 
 impl StatsCollector {
@@ -37,6 +53,10 @@ impl StatsCollector {
             total_size_bytes: 0,
             compressed_size_bytes: 0,
             compression_ratio: 0.0,
+            chunked_files: 0,
+            unique_chunks: 0,
+            chunked_logical_bytes: 0,
+            delta_objects: 0,
             file_type_stats: HashMap::new(),
             timeline_stats: Vec::new(),
         };
@@ -66,28 +86,57 @@ impl StatsCollector {
         objects_dir: &std::path::Path,
         stats: &mut RepositoryStats,
     ) -> Result<()> {
-        for entry in walkdir::WalkDir::new(objects_dir) {
-            let entry = entry?;
-            if entry.file_type().is_file() {
-                stats.total_objects += 1;
-                let metadata = entry.metadata()?;
-                stats.compressed_size_bytes += metadata.len();
-
-                // 尝试解压以获取原始大小
-                if let Ok(original_size) = Self::get_original_object_size(entry.path()) {
-                    stats.total_size_bytes += original_size;
+        let object_store = ObjectStore::new(objects_dir.to_path_buf());
+        let mut hashes = object_store.list_all_objects()?;
+        hashes.sort();
+
+        // 并行读取各对象的信息，再按哈希顺序汇总
+        let objects = hashes
+            .par_iter()
+            .map(|hash| -> Result<ObjectStats> {
+                let mut object = ObjectStats {
+                    stored_size: object_store.get_object_size(hash)?,
+                    ..Default::default()
+                };
+
+                match object_store.object_kind(hash) {
+                    Ok(ObjectKind::ChunkList) => {
+                        // 分块文件的数据已计入各个块对象，这里只统计逻辑大小
+                        object.chunk_refs = Some(object_store.chunk_refs(hash)?);
+                    }
+                    Ok(ObjectKind::Delta) => {
+                        object.is_delta = true;
+                        object.original_size = object_store.content_size(hash).ok();
+                    }
+                    Ok(ObjectKind::Blob) => {
+                        // 尝试解压以获取原始大小
+                        object.original_size = object_store.content_size(hash).ok();
+                    }
+                    Err(_) => {}
                 }
+
+                Ok(object)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut chunks = HashSet::new();
+        for object in objects {
+            stats.total_objects += 1;
+            stats.compressed_size_bytes += object.stored_size;
+            stats.total_size_bytes += object.original_size.unwrap_or(0);
+
+            if object.is_delta {
+                stats.delta_objects += 1;
+            }
+            if let Some(refs) = object.chunk_refs {
+                stats.chunked_files += 1;
+                stats.chunked_logical_bytes += refs.iter().map(|chunk| chunk.size).sum::<u64>();
+                chunks.extend(refs.into_iter().map(|chunk| chunk.hash));
             }
         }
-        Ok(())
-    }
 
-    fn get_original_object_size(path: &std::path::Path) -> Result<u64> {
-        let compressed = std::fs::read(path)?;
-        let mut decoder = flate2::read::GzDecoder::new(compressed.as_slice());
-        let mut content = Vec::new();
-        std::io::Read::read_to_end(&mut decoder, &mut content)?;
-        Ok(content.len() as u64)
+        stats.unique_chunks = chunks.len();
+        Ok(())
     }
 
     pub fn print_stats(stats: &RepositoryStats) {
@@ -154,5 +203,18 @@ impl StatsCollector {
         } else {
             println!("Space overhead: {}", space_saved_str.replace("-", ""));
         }
+
+        if stats.chunked_files > 0 {
+            println!(
+                "Chunked files: {} ({} logical, stored as {} unique chunks)",
+                stats.chunked_files,
+                crate::utils::format_size(stats.chunked_logical_bytes),
+                stats.unique_chunks
+            );
+        }
+
+        if stats.delta_objects > 0 {
+            println!("Delta-compressed objects: {}", stats.delta_objects);
+        }
     }
 }
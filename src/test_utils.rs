@@ -0,0 +1,57 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::Repository;
+
+/// 固定种子的伪随机数据（xorshift），同一种子总是生成相同的内容
+pub fn random_data(len: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 32) as u8
+        })
+        .collect()
+}
+
+/// 每个测试使用独立的临时目录，离开作用域时删除
+pub struct TempDir(pub PathBuf);
+
+impl TempDir {
+    pub fn new() -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "rustory-test-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// 初始化仓库（初始快照为 #1），再在当前时间线上创建 count 个快照
+pub fn repo_with_snapshots(dir: &TempDir, count: usize) -> Repository {
+    let mut repo = Repository::init(dir.0.clone()).unwrap();
+    for _ in 0..count {
+        commit(&mut repo);
+    }
+    repo
+}
+
+/// 修改工作区中的 file.txt 并创建快照，说明为 "snapshot <编号>"
+pub fn commit(repo: &mut Repository) -> String {
+    let number = repo.snapshot_manager.list_history().unwrap().len() + 1;
+    fs::write(repo.root.join("file.txt"), number.to_string()).unwrap();
+    repo.create_snapshot(format!("snapshot {}", number))
+        .unwrap()
+}
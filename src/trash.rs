@@ -0,0 +1,135 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::refs::Tag;
+use crate::{HistoryEntry, SnapshotMetadata};
+
+/// 回收站中被删除的快照，保存在 `.rustory/trash/<id>.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashedSnapshot {
+    pub deleted_at: DateTime<Utc>,
+    /// 删除时的历史记录
+    pub entry: HistoryEntry,
+    pub snapshot: SnapshotMetadata,
+    /// 删除快照时一并删除的标签，恢复快照时重新创建
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<Tag>,
+}
+
+impl TrashedSnapshot {
+    /// 超过保留天数后可以永久删除
+    pub fn expires_at(&self, grace_days: u32) -> DateTime<Utc> {
+        self.deleted_at + Duration::days(grace_days as i64)
+    }
+}
+
+/// 回收站：`rm` 和 `--prune-expired` 删除的快照先移到这里，保留期内可以恢复
+pub struct Trash {
+    dir: PathBuf,
+}
+
+impl Trash {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// 放入回收站（先写临时文件再重命名）
+    pub fn put(&self, trashed: &TrashedSnapshot) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+
+        let path = self.path(&trashed.snapshot.id);
+        let temp_path = self.dir.join(format!(".{}.tmp", trashed.snapshot.id));
+        fs::write(&temp_path, serde_json::to_string_pretty(trashed)?)?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    pub fn get(&self, snapshot_id: &str) -> Result<TrashedSnapshot> {
+        let path = self.path(snapshot_id);
+        if !path.exists() {
+            return Err(anyhow!("Snapshot '{}' is not in the trash", snapshot_id));
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn remove(&self, snapshot_id: &str) -> Result<()> {
+        let path = self.path(snapshot_id);
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// 回收站中的所有快照，最近删除的在前
+    pub fn list(&self) -> Result<Vec<TrashedSnapshot>> {
+        let mut trashed = Vec::new();
+        if !self.dir.exists() {
+            return Ok(trashed);
+        }
+
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|s| s.to_str()) != Some("json") {
+                continue;
+            }
+            match fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|content| Ok(serde_json::from_str::<TrashedSnapshot>(&content)?))
+            {
+                Ok(item) => trashed.push(item),
+                Err(e) => eprintln!("Warning: Failed to read {}: {}", path.display(), e),
+            }
+        }
+
+        trashed.sort_by_key(|item| std::cmp::Reverse(item.deleted_at));
+        Ok(trashed)
+    }
+
+    /// 超过保留期的快照ID
+    pub fn expired(&self, grace_days: u32) -> Result<Vec<String>> {
+        let now = Utc::now();
+        Ok(self
+            .list()?
+            .into_iter()
+            .filter(|trashed| trashed.expires_at(grace_days) <= now)
+            .map(|trashed| trashed.snapshot.id)
+            .collect())
+    }
+
+    /// 按快照编号或唯一的ID前缀查找回收站中的快照
+    pub fn resolve(&self, spec: &str) -> Result<String> {
+        let trashed = self.list()?;
+        let spec = spec.trim().trim_start_matches('#').to_lowercase();
+
+        if let Ok(number) = spec.parse::<usize>() {
+            let by_number: Vec<&TrashedSnapshot> = trashed
+                .iter()
+                .filter(|item| item.snapshot.number == number)
+                .collect();
+            if let [item] = by_number.as_slice() {
+                return Ok(item.snapshot.id.clone());
+            }
+        }
+
+        let matches: Vec<&TrashedSnapshot> = trashed
+            .iter()
+            .filter(|item| item.snapshot.id.starts_with(&spec))
+            .collect();
+        match matches.as_slice() {
+            [item] if spec.len() >= 4 || item.snapshot.id == spec => Ok(item.snapshot.id.clone()),
+            [] | [_] => Err(anyhow!("error: no snapshot '{}' in the trash", spec)),
+            _ => Err(anyhow!(
+                "error: '{}' matches {} snapshots in the trash; use a longer ID prefix",
+                spec,
+                matches.len()
+            )),
+        }
+    }
+
+    fn path(&self, snapshot_id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", snapshot_id))
+    }
+}
@@ -17,10 +17,26 @@ pub fn format_size(size: u64) -> String {
     }
 }
 
+/// 设置并行任务的线程数，0 表示使用所有 CPU 核心。
+/// 全局线程池只能初始化一次，因此先调用者生效（命令行 `--jobs` 优先于配置文件）。
+pub fn configure_jobs(jobs: usize) {
+    let _ = rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .build_global();
+}
+
 pub fn format_path(path: &Path) -> String {
     path.to_string_lossy().to_string()
 }
 
+/// 快照ID显示时使用的长度
+pub const SHORT_ID_LEN: usize = 8;
+
+/// 快照ID的缩写形式，用于界面显示
+pub fn short_id(id: &str) -> String {
+    truncate_hash(id, SHORT_ID_LEN)
+}
+
 pub fn truncate_hash(hash: &str, len: usize) -> String {
     if hash.len() > len {
         hash[..len].to_string()